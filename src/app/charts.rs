@@ -1,20 +1,36 @@
 use crate::{
     blcerr,
+    catalog::IndexType,
     compute::{
-        adapt_pricedev_to_initial_balance, best_rebalance_trigger, compute_balance_over_months,
-        rebalance_stats, unzip_balance_iter, BestRebalanceTrigger, MonthlyPayments, RebalanceData,
-        RebalanceStats, RebalanceTrigger,
+        adapt_pricedev_to_initial_balance, best_rebalance_trigger, bisection,
+        calibrate_random_walk, compute_balance_over_months, compute_total_balance,
+        convert_currency, deflate, deflate_cumulative, evaluate_over_months,
+        internal_rate_of_return, levels_to_returns, leveraged, monthly_returns,
+        net_monthly_dividend_yield, net_payments, portfolio_returns, random_walk_from_seed,
+        rebalance_stats, sharpe_ratio, unzip_balance_iter, with_hedging_cost,
+        with_reinvested_dividends, with_yearly_return, BestRebalanceTrigger, DividendPayouts, Fees,
+        GoalTarget, GoalVariable, Margin, MonthlyPayments, RebalanceData, RebalanceStats,
+        RebalanceTrigger, ReturnKind, RiskReturn,
+    },
+    core_types::{to_blc, BlcResult},
+    date::{date_after_nmonths, fill_between, Date},
+    io::{matrix_to_csv, returns_to_csv, ResamplingInfo},
+    optimize::{
+        correlation_matrix, covariance_matrix, mean, optimize_fractions, rolling_correlation,
+        Objective, WeightBounds,
     },
-    core_types::BlcResult,
-    date::{fill_between, Date},
 };
 
+use super::dataset_cache::DatasetVersion;
+use super::frontier::{weight_grid_fractions, Mix, WeightGrid, WEIGHT_GRID_STEPS};
 use super::month_slider::{MonthSlider, MonthSliderPair, SliderState};
+use super::rolling::{RollingWindows, WindowOutcome};
 use egui::Ui;
-use egui_plot::{Corner, GridMark, Legend, Line, Plot};
+use egui_plot::{Corner, GridMark, Legend, Line, LineStyle, Plot, VLine};
+use exmex::{parse_val, Express};
 use serde::{Deserialize, Serialize};
 use std::iter::Iterator;
-use std::{fmt::Display, iter, mem, ops::RangeInclusive, str::FromStr};
+use std::{borrow::Cow, fmt::Display, iter, mem, ops::RangeInclusive, str::FromStr};
 
 /// Intersects all timelines of all given charts
 fn start_end_date<'a>(charts: impl Iterator<Item = &'a Chart> + Clone) -> BlcResult<(Date, Date)> {
@@ -40,6 +56,51 @@ fn start_end_date<'a>(charts: impl Iterator<Item = &'a Chart> + Clone) -> BlcRes
     }
 }
 
+/// Union of the timelines of the price developments that is restricted by the auxiliary charts,
+/// e.g., inflation. The union ends with the first chart that ends.
+fn union_start_end_date<'a>(
+    charts: impl Iterator<Item = &'a Chart> + Clone,
+    auxiliary: impl Iterator<Item = &'a Chart> + Clone,
+) -> BlcResult<(Date, Date)> {
+    let start_date = charts.clone().filter_map(|c| c.dates.first()).min();
+    let start_date = start_date.max(auxiliary.clone().filter_map(|c| c.dates.first()).max());
+    let end_date = charts.chain(auxiliary).filter_map(|c| c.dates.last()).min();
+    match (start_date, end_date) {
+        (Some(start_date), Some(end_date)) if start_date < end_date => Ok((*start_date, *end_date)),
+        (Some(_), Some(_)) => Err(blcerr!("start date needs to be strictly before enddate")),
+        _ => Err(blcerr!(
+            "Add simulated or historical charts to compute your portfolio development"
+        )),
+    }
+}
+
+/// Values of the timeline from `start_date` on, `NaN` before the first date of `dates`
+fn nan_padded<'a>(
+    dates: &[Date],
+    start_date: Date,
+    values: Cow<'a, [f64]>,
+) -> BlcResult<Cow<'a, [f64]>> {
+    let n_missing = n_months_before(dates, start_date)?;
+    Ok(if n_missing > 0 {
+        Cow::Owned(
+            iter::repeat(f64::NAN)
+                .take(n_missing)
+                .chain(values.iter().copied())
+                .collect(),
+        )
+    } else {
+        values
+    })
+}
+
+/// Number of months between `start_date` and the first date if the latter is later
+fn n_months_before(dates: &[Date], start_date: Date) -> BlcResult<usize> {
+    match dates.first() {
+        Some(first) if *first > start_date => start_date.n_month_until(*first),
+        _ => Ok(0),
+    }
+}
+
 fn redestribute_fractions(mut fractions: Vec<f64>, to_redestribute: f64) -> Vec<f64> {
     let mut rest = 0.0;
     let new_fraction_increase = to_redestribute / fractions.len() as f64;
@@ -192,11 +253,244 @@ fn slice_by_date<'a, T>(
     Ok(&to_be_sliced[start_idx..end_idx])
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DividendMode {
+    #[default]
+    Reinvest,
+    Payout,
+}
+impl Display for DividendMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DividendMode::Reinvest => f.write_str("reinvest"),
+            DividendMode::Payout => f.write_str("pay out"),
+        }
+    }
+}
+
+/// Dividends that are not contained in a price index
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Dividends {
+    /// constant yearly dividend yield in percent, used if there is no series
+    pub yearly_yield: f64,
+    /// yearly dividend yields in percent over time
+    pub yield_series: Option<Box<Chart>>,
+    pub mode: DividendMode,
+    /// flat tax rate on dividends in percent
+    pub tax_rate: f64,
+}
+impl Dividends {
+    fn is_active(&self) -> bool {
+        self.yield_series.is_some() || self.yearly_yield > 0.0
+    }
+    fn net_monthly_yields(&self, start_date: Date, end_date: Date) -> BlcResult<Vec<f64>> {
+        Ok(if let Some(series) = &self.yield_series {
+            series
+                .sliced_values(start_date, end_date)?
+                .iter()
+                .map(|y| net_monthly_dividend_yield(*y, self.tax_rate))
+                .collect()
+        } else {
+            let n_months = start_date.n_month_until(end_date)? + 1;
+            vec![net_monthly_dividend_yield(self.yearly_yield, self.tax_rate); n_months]
+        })
+    }
+}
+
+/// Where the values of a chart come from
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Origin {
+    /// charts of sessions before the origin was recorded
+    #[default]
+    Unknown,
+    /// generated from `parameters`, random walks also record their seed
+    Simulated {
+        parameters: String,
+        seed: Option<u64>,
+    },
+    Downloaded(DatasetVersion),
+    /// from a local file or, without file name, from pasted text
+    Imported {
+        filename: Option<String>,
+    },
+    /// computed from an expression over other charts, recomputed when they change
+    Derived {
+        expression: String,
+    },
+    /// daily-reset leveraged or inverse product on another chart, recomputed when it changes
+    Leveraged {
+        underlying: String,
+        leverage: f64,
+        /// yearly borrowing cost in percent
+        borrowing_cost: f64,
+    },
+}
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Unknown => f.write_str("unknown origin"),
+            Origin::Simulated {
+                parameters,
+                seed: Some(seed),
+            } => write!(f, "simulated with {parameters}, seed {seed}"),
+            Origin::Simulated {
+                parameters,
+                seed: None,
+            } => write!(f, "created with {parameters}"),
+            Origin::Downloaded(version) => write!(f, "downloaded from {version}"),
+            Origin::Imported {
+                filename: Some(filename),
+            } => write!(f, "imported from {filename}"),
+            Origin::Imported { filename: None } => f.write_str("imported from pasted text"),
+            Origin::Derived { expression } => write!(f, "derived as {expression}"),
+            Origin::Leveraged {
+                underlying,
+                leverage,
+                borrowing_cost,
+            } => write!(
+                f,
+                "{leverage}x leveraged {underlying} with borrowing cost {borrowing_cost}%"
+            ),
+        }
+    }
+}
+
+/// Name of a chart in expressions, e.g., `msci_world` for `MSCI World`
+pub fn expression_identifier(name: &str) -> String {
+    let identifier = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{identifier}")
+    } else {
+        identifier
+    }
+}
+
+/// Evaluates `expression` over the intersection of the timelines of the charts it references.
+/// The expression is evaluated on the values returned by `values_of`, i.e., in the base currency
+/// with reinvested dividends.
+fn derived_chart<'a>(
+    name: String,
+    expression: &str,
+    charts: impl Iterator<Item = &'a Chart>,
+    values_of: impl Fn(&Chart, Date, Date) -> BlcResult<Vec<f64>>,
+) -> BlcResult<Chart> {
+    let expr = parse_val::<i32, f64>(expression).map_err(to_blc)?;
+    let var_names = expr.var_names();
+    let referenced = charts
+        .map(|c| (expression_identifier(&c.name), c))
+        .filter(|(identifier, _)| var_names.contains(identifier))
+        .collect::<Vec<_>>();
+    if referenced.is_empty() {
+        Err(blcerr!("the expression {expression} references no chart"))?;
+    }
+    let (start, end) = start_end_date(referenced.iter().map(|(_, c)| *c))?;
+    let inputs = referenced
+        .iter()
+        .map(|(identifier, c)| Ok((identifier.as_str(), values_of(c, start, end)?)))
+        .collect::<BlcResult<Vec<_>>>()?;
+    let inputs = inputs
+        .iter()
+        .map(|(identifier, values)| (*identifier, values.as_slice()))
+        .collect::<Vec<_>>();
+    let values = evaluate_over_months(&expr, &inputs, start)?;
+    let mut chart = Chart::new(name, fill_between(start, end), values);
+    chart.origin = Origin::Derived {
+        expression: expression.to_string(),
+    };
+    Ok(chart)
+}
+
+/// Leveraged product on `underlying` that inherits its currency and index type. The TER is
+/// charged as expense ratio.
+fn leveraged_chart(
+    name: String,
+    underlying: &Chart,
+    leverage: f64,
+    borrowing_cost: f64,
+) -> BlcResult<Chart> {
+    let values = leveraged(&underlying.values, leverage, borrowing_cost)?;
+    let mut chart = Chart::new(name, underlying.dates.clone(), values);
+    chart.currency = underlying.currency.clone();
+    chart.index_type = underlying.index_type;
+    chart.origin = Origin::Leveraged {
+        underlying: underlying.name.clone(),
+        leverage,
+        borrowing_cost,
+    };
+    Ok(chart)
+}
+
+/// Records that a chart has been extended backwards with a proxy
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Splice {
+    pub proxy: String,
+    pub proxy_origin: Origin,
+    /// first month of the proxy segment
+    pub start: Date,
+    /// first month of the original chart
+    pub join: Date,
+    /// factor the proxy has been multiplied with to match the chart at the join
+    pub scale: f64,
+}
+impl Display for Splice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "spliced with {} from {} until before {}, scaled by {:0.4}, {}",
+            self.proxy, self.start, self.join, self.scale, self.proxy_origin
+        )
+    }
+}
+
+/// Proxy to extend a short chart backwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpliceProxy {
+    /// index of a persisted chart
+    Chart(usize),
+    /// random walk with the return and volatility of the extended chart
+    Calibrated { n_months: usize, seed: u64 },
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Chart {
     name: String,
     dates: Vec<Date>,
     values: Vec<f64>,
+    /// yearly expense ratio (TER) in percent
+    #[serde(default)]
+    pub expense_ratio: f64,
+    /// currency code of the values, empty for the base currency of the portfolio
+    #[serde(default)]
+    pub currency: String,
+    /// currency-hedged share classes are not converted but charged with the hedging cost
+    #[serde(default)]
+    pub hedged: bool,
+    /// yearly hedging cost in percent
+    #[serde(default)]
+    pub hedging_cost: f64,
+    #[serde(default)]
+    pub dividends: Dividends,
+    /// in case the chart was imported from daily or weekly data
+    #[serde(default)]
+    pub resampling: Option<ResamplingInfo>,
+    #[serde(default)]
+    pub origin: Origin,
+    /// price or total return index if known
+    #[serde(default)]
+    pub index_type: Option<IndexType>,
+    /// backwards extensions, the latest one is last
+    #[serde(default)]
+    pub splices: Vec<Splice>,
 }
 impl Chart {
     pub fn name(&self) -> &str {
@@ -212,11 +506,118 @@ impl Chart {
             name,
             dates,
             values,
+            expense_ratio: 0.0,
+            currency: String::new(),
+            hedged: false,
+            hedging_cost: 0.0,
+            dividends: Dividends::default(),
+            resampling: None,
+            origin: Origin::Unknown,
+            index_type: None,
+            splices: vec![],
+        }
+    }
+
+    /// Derived charts are recomputed from other charts
+    fn is_derived(&self) -> bool {
+        matches!(
+            self.origin,
+            Origin::Derived { .. } | Origin::Leveraged { .. }
+        )
+    }
+
+    /// True if the chart is derived from the chart with the given name
+    fn references(&self, name: &str) -> bool {
+        match &self.origin {
+            Origin::Derived { expression } => {
+                let identifier = expression_identifier(name);
+                parse_val::<i32, f64>(expression)
+                    .is_ok_and(|expr| expr.var_names().contains(&identifier))
+            }
+            Origin::Leveraged { underlying, .. } => underlying == name,
+            _ => false,
+        }
+    }
+
+    /// Extends the chart backwards with the values of `proxy` before the first month of the
+    /// chart. The proxy is scaled to match the chart at the join.
+    pub fn spliced(&self, proxy: &Chart) -> BlcResult<Chart> {
+        let (Some(join), Some(first_value)) = (self.dates.first(), self.values.first()) else {
+            return Err(blcerr!("cannot splice empty chart {}", self.name));
+        };
+        let join_idx = proxy
+            .dates
+            .iter()
+            .position(|d| d == join)
+            .ok_or_else(|| blcerr!("proxy {} does not contain {join}", proxy.name))?;
+        if join_idx == 0 {
+            Err(blcerr!("proxy {} does not start before {join}", proxy.name))?;
         }
+        let scale = first_value / proxy.values[join_idx];
+        let mut spliced = self.clone();
+        spliced.dates = proxy.dates[..join_idx]
+            .iter()
+            .chain(self.dates.iter())
+            .copied()
+            .collect();
+        spliced.values = proxy.values[..join_idx]
+            .iter()
+            .map(|v| v * scale)
+            .chain(self.values.iter().copied())
+            .collect();
+        spliced.splices.push(Splice {
+            proxy: proxy.name.clone(),
+            proxy_origin: proxy.origin.clone(),
+            start: proxy.dates[0],
+            join: *join,
+            scale,
+        });
+        Ok(spliced)
     }
 
-    pub fn from_tuple(name: String, (dates, values): (Vec<Date>, Vec<f64>)) -> Self {
-        Self::new(name, dates, values)
+    /// Random walk with the expected return and the volatility of the chart that ends at the first
+    /// month of the chart
+    fn calibrated_proxy(&self, n_months: usize, seed: u64) -> BlcResult<Chart> {
+        let join = *self
+            .dates
+            .first()
+            .ok_or_else(|| blcerr!("cannot calibrate to empty chart {}", self.name))?;
+        let (expected_yearly_return, vola) = calibrate_random_walk(&self.values)?;
+        let values =
+            random_walk_from_seed(seed, expected_yearly_return, true, vola, 1, n_months, &[])?;
+        let mut start = join;
+        for _ in 0..n_months {
+            start = start.prev_month()?;
+        }
+        let dates = (0..(n_months + 1))
+            .map(|i| date_after_nmonths(start, i))
+            .collect();
+        let mut proxy = Chart::new(format!("{} calibrated", self.name), dates, values);
+        proxy.origin = Origin::Simulated {
+            parameters: format!(
+                "expected yearly return {expected_yearly_return:0.2}% and volatility \
+                 {vola:0.4} calibrated to {}",
+                self.name
+            ),
+            seed: Some(seed),
+        };
+        Ok(proxy)
+    }
+
+    /// Metadata of the chart's values for tooltips
+    pub fn provenance(&self) -> String {
+        let mut lines = vec![self.origin.to_string()];
+        if !self.currency.trim().is_empty() {
+            lines.push(format!("currency {}", self.currency.trim()));
+        }
+        if let Some(index_type) = self.index_type {
+            lines.push(index_type.to_string());
+        }
+        if let Some(resampling) = &self.resampling {
+            lines.push(resampling.to_string());
+        }
+        lines.extend(self.splices.iter().map(|s| s.to_string()));
+        lines.join("\n")
     }
 
     pub fn values_between_dates(
@@ -224,18 +625,41 @@ impl Chart {
         start_date: Date,
         end_date: Date,
         initial_balance: Option<f64>,
+    ) -> BlcResult<Vec<[f64; 2]>> {
+        self.values_between_dates_deflated(start_date, end_date, initial_balance, None)
+    }
+    /// If a price index is given, values are converted to the purchasing power of `start_date`
+    fn values_between_dates_deflated(
+        &self,
+        start_date: Date,
+        end_date: Date,
+        initial_balance: Option<f64>,
+        price_index: Option<&[f64]>,
     ) -> BlcResult<Vec<[f64; 2]>> {
         let sliced_values = self.sliced_values(start_date, end_date)?;
+        // charts that start after `start_date` are shifted to their first date
+        let offset = n_months_before(&self.dates, start_date)?;
+        let deflated;
+        let sliced_values = if let Some(price_index) = price_index {
+            let scale = price_index[0] / price_index[offset];
+            deflated = deflate(sliced_values, &price_index[offset..])
+                .iter()
+                .map(|v| v * scale)
+                .collect::<Vec<_>>();
+            &deflated
+        } else {
+            sliced_values
+        };
         Ok(if let Some(initial_balance) = initial_balance {
             adapt_pricedev_to_initial_balance(initial_balance, sliced_values)
                 .enumerate()
-                .map(|(i, v)| [i as f64, v])
+                .map(|(i, v)| [(i + offset) as f64, v])
                 .collect::<Vec<_>>()
         } else {
             sliced_values
                 .iter()
                 .enumerate()
-                .map(|(i, v)| [i as f64, *v])
+                .map(|(i, v)| [(i + offset) as f64, *v])
                 .collect::<Vec<_>>()
         })
     }
@@ -244,8 +668,10 @@ impl Chart {
         start_date: Date,
         end_date: Date,
         initial_balance: Option<f64>,
+        price_index: Option<&[f64]>,
     ) -> BlcResult<Line<'_>> {
-        let vals = self.values_between_dates(start_date, end_date, initial_balance)?;
+        let vals =
+            self.values_between_dates_deflated(start_date, end_date, initial_balance, price_index)?;
         Ok(Line::new(self.name.clone(), vals))
     }
 
@@ -253,12 +679,77 @@ impl Chart {
         slice_by_date(&self.dates, start_date, end_date, &self.values)
     }
 
-    fn sliced_dates(&self, start_date: Date, end_date: Date) -> BlcResult<&[Date]> {
-        slice_by_date(&self.dates, start_date, end_date, &self.dates)
+    /// Returns the currency the chart needs to be converted from if any. Without base currency
+    /// nothing is converted.
+    fn currency_to_convert(&self, base_currency: &str) -> Option<&str> {
+        let currency = self.currency.trim();
+        if self.hedged
+            || currency.is_empty()
+            || base_currency.trim().is_empty()
+            || same_currency(currency, base_currency)
+        {
+            None
+        } else {
+            Some(currency)
+        }
     }
 }
 
-type ComputeData<'a> = Vec<&'a [f64]>;
+fn same_currency(c1: &str, c2: &str) -> bool {
+    c1.trim().eq_ignore_ascii_case(c2.trim())
+}
+
+type ComputeData<'a> = Vec<Cow<'a, [f64]>>;
+
+fn as_slices(data: &[impl AsRef<[f64]>]) -> Vec<&[f64]> {
+    data.iter().map(|pd| pd.as_ref()).collect()
+}
+
+/// Pairwise statistic of the monthly returns of the price developments
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatrixKind {
+    #[default]
+    Correlation,
+    Covariance,
+}
+impl Display for MatrixKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MatrixKind::Correlation => "correlation",
+            MatrixKind::Covariance => "covariance [%²]",
+        })
+    }
+}
+
+/// Correlations and covariances of the monthly returns of the persisted charts
+#[derive(Clone, Debug)]
+pub struct ReturnMatrices {
+    pub names: Vec<String>,
+    pub correlation: Vec<Vec<f64>>,
+    /// of returns in percent
+    pub covariance: Vec<Vec<f64>>,
+    /// number of monthly returns the matrices are based on
+    pub n_months: usize,
+}
+impl ReturnMatrices {
+    pub fn matrix(&self, kind: MatrixKind) -> &[Vec<f64>] {
+        match kind {
+            MatrixKind::Correlation => &self.correlation,
+            MatrixKind::Covariance => &self.covariance,
+        }
+    }
+
+    pub fn to_csv(&self, kind: MatrixKind) -> String {
+        matrix_to_csv(&self.names, self.matrix(kind))
+    }
+}
+
+/// Exchange rate series, i.e., the value of one unit of `currency` in the base currency
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct FxRate {
+    pub currency: String,
+    pub chart: Chart,
+}
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct TmpChart {
@@ -275,10 +766,86 @@ pub struct Charts {
     total_payments_over_month: Option<Chart>,
     pub plot_balance: bool,
     pub user_start_end: MonthSliderPair,
+    /// consumer price index to compute real values
+    #[serde(default)]
+    inflation: Option<Chart>,
+    /// if true, charts and results are shown in purchasing power of the start date
+    #[serde(default)]
+    pub real_values: bool,
+    #[serde(default)]
+    total_balance_over_month_real: Option<Chart>,
+    #[serde(default)]
+    total_payments_over_month_real: Option<Chart>,
+    /// cumulative dividends that are paid out instead of reinvested
+    #[serde(default)]
+    total_payouts_over_month: Option<Chart>,
+    #[serde(default)]
+    total_payouts_over_month_real: Option<Chart>,
+    /// deterministic asset used as risk-free rate and as savings account baseline
+    #[serde(default)]
+    risk_free: Option<Chart>,
+    /// portfolio value if all payments went into the risk-free asset
+    #[serde(default)]
+    savings_baseline: Option<Chart>,
+    #[serde(default)]
+    savings_baseline_real: Option<Chart>,
+    #[serde(default)]
+    sharpe_ratio: Option<f64>,
+    /// yearly fee in percent of the assets under management
+    #[serde(default)]
+    pub advisory_fee: f64,
+    /// sum of expense ratios and advisory fees paid over the timeline
+    #[serde(default)]
+    total_fees: Option<f64>,
+    /// currency of the portfolio, charts without currency are assumed to be in this currency
+    #[serde(default)]
+    pub base_currency: String,
+    #[serde(default)]
+    fx_rates: Vec<FxRate>,
+    /// loan to hold the portfolio at a leverage, the portfolio value is the net equity
+    #[serde(default)]
+    pub margin: Option<Margin>,
+    /// if true, the timeline starts with the first chart and charts are phased in at their
+    /// inception, otherwise the timeline is the intersection of all charts
+    #[serde(default)]
+    pub union_timeline: bool,
+    #[serde(default)]
+    pub objective: Objective,
+    /// constraints of the optimizer per persisted chart
+    #[serde(default)]
+    weight_bounds: Vec<WeightBounds>,
+    /// cleared whenever the inputs of the optimizer change
+    #[serde(skip)]
+    proposed_fractions: Option<BlcResult<Vec<f64>>>,
 }
 impl Charts {
+    /// Inflation and risk-free charts only restrict the timeline if there are price developments,
+    /// exchange rates only if they are needed for conversion
+    fn auxiliary_for_timeline(&self) -> impl Iterator<Item = &Chart> + Clone {
+        let needed_fx_rates = self
+            .fx_rates
+            .iter()
+            .filter(|fx| {
+                self.persisted.iter().any(|c| {
+                    c.currency_to_convert(&self.base_currency)
+                        .is_some_and(|cur| same_currency(cur, &fx.currency))
+                })
+            })
+            .map(|fx| &fx.chart);
+        let dividend_yields = self
+            .persisted
+            .iter()
+            .filter_map(|c| c.dividends.yield_series.as_deref());
+        self.inflation
+            .iter()
+            .chain(self.risk_free.iter())
+            .filter(|_| !self.persisted.is_empty())
+            .chain(needed_fx_rates)
+            .chain(dividend_yields)
+    }
+
     pub fn update_start_end_sliders(&mut self) {
-        let start_end = start_end_date(self.persisted_and_tmp_iter());
+        let start_end = self.full_start_end_date(true);
         if let Ok((start, end)) = start_end {
             let start_slider = MonthSlider::new(start, end, SliderState::First);
             let end_slider = MonthSlider::new(start, end, SliderState::Last);
@@ -300,16 +867,19 @@ impl Charts {
         start.n_month_until(end)
     }
 
-    pub fn start_end_date(&self, with_tmp: bool) -> BlcResult<(Date, Date)> {
-        let (start, end) = if let Some(tmp) = &self.tmp {
-            if with_tmp {
-                start_end_date(self.persisted.iter().chain(iter::once(&tmp.chart)))?
-            } else {
-                start_end_date(self.persisted.iter())?
-            }
+    /// Timeline without the restriction by the user
+    fn full_start_end_date(&self, with_tmp: bool) -> BlcResult<(Date, Date)> {
+        let tmp = self.tmp.as_ref().filter(|_| with_tmp).map(|tmp| &tmp.chart);
+        let charts = self.persisted.iter().chain(tmp);
+        if self.union_timeline {
+            union_start_end_date(charts, self.auxiliary_for_timeline())
         } else {
-            start_end_date(self.persisted.iter())?
-        };
+            start_end_date(charts.chain(self.auxiliary_for_timeline()))
+        }
+    }
+
+    pub fn start_end_date(&self, with_tmp: bool) -> BlcResult<(Date, Date)> {
+        let (start, end) = self.full_start_end_date(with_tmp)?;
         let start = if let Some(user_start) = self.user_start_end.selected_start_date() {
             user_start
         } else {
@@ -333,11 +903,415 @@ impl Charts {
         Ok(fill_between(start, end))
     }
 
+    fn shows_real_values(&self) -> bool {
+        self.real_values && self.inflation.is_some()
+    }
     pub fn total_balance_over_month(&self) -> Option<&Chart> {
-        self.total_balance_over_month.as_ref()
+        if self.shows_real_values() {
+            self.total_balance_over_month_real.as_ref()
+        } else {
+            self.total_balance_over_month.as_ref()
+        }
     }
     pub fn total_payments_over_month(&self) -> Option<&Chart> {
-        self.total_payments_over_month.as_ref()
+        if self.shows_real_values() {
+            self.total_payments_over_month_real.as_ref()
+        } else {
+            self.total_payments_over_month.as_ref()
+        }
+    }
+
+    /// Cumulative dividend payouts, `None` if nothing is paid out
+    pub fn total_payouts_over_month(&self) -> Option<&Chart> {
+        if self.shows_real_values() {
+            self.total_payouts_over_month_real.as_ref()
+        } else {
+            self.total_payouts_over_month.as_ref()
+        }
+        .filter(|c| c.values.last().is_some_and(|v| *v != 0.0))
+    }
+
+    pub fn savings_baseline(&self) -> Option<&Chart> {
+        if self.shows_real_values() {
+            self.savings_baseline_real.as_ref()
+        } else {
+            self.savings_baseline.as_ref()
+        }
+    }
+    pub fn sharpe_ratio(&self) -> Option<f64> {
+        self.sharpe_ratio
+    }
+
+    pub fn total_fees(&self) -> Option<f64> {
+        self.total_fees
+    }
+
+    /// Monthly returns of a persisted chart as csv. Without index the returns of the portfolio
+    /// value are exported, corrected for the payments.
+    pub fn returns_csv(&self, idx: Option<usize>, kind: ReturnKind) -> BlcResult<String> {
+        match idx {
+            Some(idx) => {
+                let chart = self
+                    .persisted
+                    .get(idx)
+                    .ok_or_else(|| blcerr!("there is no chart {idx}"))?;
+                let returns = levels_to_returns(&chart.values, kind);
+                Ok(returns_to_csv(
+                    &chart.name,
+                    chart.dates.get(1..).unwrap_or_default(),
+                    &returns,
+                    kind,
+                ))
+            }
+            None => {
+                let (Some(balance), Some(payments)) = (
+                    self.total_balance_over_month(),
+                    self.total_payments_over_month(),
+                ) else {
+                    return Err(blcerr!("compute the portfolio balance first"));
+                };
+                let payments = match self.total_payouts_over_month() {
+                    Some(payouts) => net_payments(&payments.values, &payouts.values),
+                    None => payments.values.clone(),
+                };
+                let returns = portfolio_returns(&balance.values, &payments)
+                    .into_iter()
+                    .map(|r| kind.convert_simple(r))
+                    .collect::<Vec<_>>();
+                Ok(returns_to_csv(
+                    "portfolio",
+                    balance.dates.get(1..).unwrap_or_default(),
+                    &returns,
+                    kind,
+                ))
+            }
+        }
+    }
+
+    fn expense_ratios(&self) -> Vec<f64> {
+        self.persisted.iter().map(|c| c.expense_ratio).collect()
+    }
+
+    fn fees<'a>(&self, expense_ratios: &'a [f64]) -> Fees<'a> {
+        Fees {
+            expense_ratios,
+            advisory_fee: self.advisory_fee,
+        }
+    }
+
+    pub fn inflation(&self) -> Option<&Chart> {
+        self.inflation.as_ref()
+    }
+
+    /// Uses the temporary chart as consumer price index
+    pub fn persist_tmp_as_inflation(&mut self) {
+        if let Some(tmp) = mem::take(&mut self.tmp) {
+            if !tmp.chart.dates.is_empty() {
+                self.inflation = Some(tmp.chart);
+                self.update_start_end_sliders();
+            }
+        }
+    }
+
+    pub fn remove_inflation(&mut self) {
+        self.inflation = None;
+        self.total_balance_over_month_real = None;
+        self.total_payments_over_month_real = None;
+        self.total_payouts_over_month_real = None;
+        self.update_start_end_sliders();
+    }
+
+    /// Uses the temporary chart as risk-free rate and savings account baseline
+    pub fn persist_tmp_as_risk_free(&mut self) {
+        if let Some(tmp) = mem::take(&mut self.tmp) {
+            if !tmp.chart.dates.is_empty() {
+                self.risk_free = Some(tmp.chart);
+                self.update_start_end_sliders();
+            }
+        }
+    }
+
+    pub fn remove_risk_free(&mut self) {
+        self.risk_free = None;
+        self.savings_baseline = None;
+        self.savings_baseline_real = None;
+        self.update_start_end_sliders();
+    }
+
+    /// Uses the temporary chart as exchange rate of the given currency, i.e., the value of one
+    /// unit of `currency` in the base currency. An existing rate of the currency is replaced.
+    pub fn persist_tmp_as_fx_rate(&mut self, currency: &str) -> BlcResult<()> {
+        let currency = currency.trim().to_uppercase();
+        if currency.is_empty() {
+            Err(blcerr!("currency of the exchange rate is missing"))?;
+        }
+        if let Some(tmp) = mem::take(&mut self.tmp) {
+            if !tmp.chart.dates.is_empty() {
+                self.fx_rates
+                    .retain(|fx| !same_currency(&fx.currency, &currency));
+                self.fx_rates.push(FxRate {
+                    currency,
+                    chart: tmp.chart,
+                });
+                self.update_derived()?;
+                self.update_start_end_sliders();
+            }
+        }
+        Ok(())
+    }
+
+    fn fx_rate(&self, currency: &str) -> BlcResult<&Chart> {
+        self.fx_rates
+            .iter()
+            .find(|fx| same_currency(&fx.currency, currency))
+            .map(|fx| &fx.chart)
+            .ok_or_else(|| {
+                blcerr!(
+                    "no exchange rate from {currency} to base currency {}",
+                    self.base_currency
+                )
+            })
+    }
+
+    /// Shows currency tags and hedging of the price developments and the exchange rates and
+    /// returns true if a recomputation is needed
+    pub fn currency_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut recompute = false;
+        let mut remove_idx = None;
+        egui::CollapsingHeader::new("Currencies").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Base currency");
+                recompute |= ui
+                    .add(egui::TextEdit::singleline(&mut self.base_currency).desired_width(50.0))
+                    .lost_focus();
+            });
+            egui::Grid::new("grid-chart-currencies").show(ui, |ui| {
+                for chart in self.persisted.iter_mut() {
+                    ui.label(&chart.name);
+                    recompute |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut chart.currency)
+                                .hint_text("base")
+                                .desired_width(50.0),
+                        )
+                        .lost_focus();
+                    recompute |= ui.checkbox(&mut chart.hedged, "hedged").changed();
+                    if chart.hedged {
+                        recompute |= ui
+                            .add(
+                                egui::DragValue::new(&mut chart.hedging_cost)
+                                    .speed(0.01)
+                                    .range(0.0..=10.0)
+                                    .prefix("cost ")
+                                    .suffix(" %"),
+                            )
+                            .changed();
+                    }
+                    ui.end_row();
+                }
+            });
+            if !self.fx_rates.is_empty() {
+                egui::Grid::new("grid-fx-rates").show(ui, |ui| {
+                    for (idx, fx) in self.fx_rates.iter().enumerate() {
+                        ui.label(format!("1 {} in {}", fx.currency, self.base_currency));
+                        ui.label(fx.chart.name());
+                        if ui.button("x").clicked() {
+                            remove_idx = Some(idx);
+                        }
+                        ui.end_row();
+                    }
+                });
+            }
+        });
+        if let Some(idx) = remove_idx {
+            self.fx_rates.remove(idx);
+            recompute = true;
+        }
+        if recompute {
+            self.update_start_end_sliders();
+        }
+        recompute
+    }
+
+    /// Uses the temporary chart as series of yearly dividend yields in percent of the persisted
+    /// chart with index `chart_idx`
+    pub fn persist_tmp_as_dividend_yield(&mut self, chart_idx: usize) -> BlcResult<()> {
+        if chart_idx >= self.persisted.len() {
+            Err(blcerr!("no price development with index {chart_idx}"))?;
+        }
+        if let Some(tmp) = mem::take(&mut self.tmp) {
+            if !tmp.chart.dates.is_empty() {
+                self.persisted[chart_idx].dividends.yield_series = Some(Box::new(tmp.chart));
+                self.update_derived()?;
+                self.update_start_end_sliders();
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows dividend yields, modes, and taxes of the price developments and returns true if a
+    /// recomputation is needed
+    pub fn dividends_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut recompute = false;
+        let mut update_timeline = false;
+        egui::CollapsingHeader::new("Dividends").show(ui, |ui| {
+            egui::Grid::new("grid-chart-dividends").show(ui, |ui| {
+                for (idx, chart) in self.persisted.iter_mut().enumerate() {
+                    let dividends = &mut chart.dividends;
+                    ui.label(&chart.name);
+                    if let Some(series) = &dividends.yield_series {
+                        ui.label(format!("yield from {}", series.name()));
+                        if ui.button("x").clicked() {
+                            dividends.yield_series = None;
+                            update_timeline = true;
+                        }
+                    } else {
+                        recompute |= ui
+                            .add(
+                                egui::DragValue::new(&mut dividends.yearly_yield)
+                                    .speed(0.01)
+                                    .range(0.0..=20.0)
+                                    .prefix("yield ")
+                                    .suffix(" %"),
+                            )
+                            .changed();
+                        ui.label("");
+                    }
+                    egui::ComboBox::from_id_salt(format!("dividend-mode-{idx}"))
+                        .selected_text(dividends.mode.to_string())
+                        .show_ui(ui, |ui| {
+                            for mode in [DividendMode::Reinvest, DividendMode::Payout] {
+                                recompute |= ui
+                                    .selectable_value(&mut dividends.mode, mode, mode.to_string())
+                                    .changed();
+                            }
+                        });
+                    recompute |= ui
+                        .add(
+                            egui::DragValue::new(&mut dividends.tax_rate)
+                                .speed(0.1)
+                                .range(0.0..=100.0)
+                                .prefix("tax ")
+                                .suffix(" %"),
+                        )
+                        .changed();
+                    ui.end_row();
+                }
+            });
+            ui.label("Payouts are withdrawals and reduce the total payments.");
+        });
+        if update_timeline {
+            self.update_start_end_sliders();
+        }
+        recompute || update_timeline
+    }
+
+    /// Shows the loan to hold the portfolio at a leverage and returns true if a recomputation is
+    /// needed
+    pub fn margin_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut recompute = false;
+        egui::CollapsingHeader::new("Portfolio leverage").show(ui, |ui| {
+            let mut is_leveraged = self.margin.is_some();
+            if ui
+                .checkbox(&mut is_leveraged, "Finance a leverage with a loan")
+                .changed()
+            {
+                self.margin = is_leveraged.then(|| Margin::new(1.5, 5.0, 0.25).unwrap());
+                recompute = true;
+            }
+            if let Some(margin) = &mut self.margin {
+                ui.horizontal(|ui| {
+                    recompute |= ui
+                        .add(
+                            egui::DragValue::new(&mut margin.leverage)
+                                .speed(0.01)
+                                .range(1.0..=4.0)
+                                .prefix("leverage ")
+                                .suffix("x"),
+                        )
+                        .changed();
+                    recompute |= ui
+                        .add(
+                            egui::DragValue::new(&mut margin.interest)
+                                .speed(0.01)
+                                .range(0.0..=20.0)
+                                .prefix("interest ")
+                                .suffix(" %"),
+                        )
+                        .changed();
+                    // the equity after a margin call needs to be above the maintenance ratio
+                    let max_maintenance = 0.99 / margin.leverage;
+                    margin.maintenance = margin.maintenance.min(max_maintenance);
+                    recompute |= ui
+                        .add(
+                            egui::DragValue::new(&mut margin.maintenance)
+                                .speed(0.001)
+                                .range(0.0..=max_maintenance)
+                                .prefix("maintenance ")
+                                .custom_formatter(|x, _| format!("{:0.1}%", x * 100.0))
+                                .custom_parser(|s| {
+                                    s.trim_end_matches('%')
+                                        .trim()
+                                        .parse::<f64>()
+                                        .ok()
+                                        .map(|x| x / 100.0)
+                                }),
+                        )
+                        .changed();
+                });
+                ui.label(
+                    "The loan accrues interest monthly. If the equity falls below the maintenance \
+                     ratio of the assets, assets are sold to restore the leverage. Rebalancing \
+                     restores the leverage as well. The portfolio value is the net equity.",
+                );
+            }
+        });
+        recompute
+    }
+
+    /// Shows the inflation and the risk-free series if there are any and returns true if one
+    /// of them was removed
+    pub fn auxiliary_charts_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut remove_inflation = false;
+        let mut remove_risk_free = false;
+        if self.inflation.is_some() || self.risk_free.is_some() {
+            egui::Grid::new("grid-auxiliary-charts").show(ui, |ui| {
+                if let Some(inflation) = &self.inflation {
+                    ui.label("Inflation (consumer price index)");
+                    ui.label(inflation.name());
+                    remove_inflation = ui.button("x").clicked();
+                    ui.end_row();
+                }
+                if let Some(risk_free) = &self.risk_free {
+                    ui.label("Risk-free rate and savings account");
+                    ui.label(risk_free.name());
+                    remove_risk_free = ui.button("x").clicked();
+                    ui.end_row();
+                }
+            });
+        }
+        if remove_inflation {
+            self.remove_inflation();
+        }
+        if remove_risk_free {
+            self.remove_risk_free();
+        }
+        remove_inflation || remove_risk_free
+    }
+
+    fn payments_with_inflation(
+        &self,
+        monthly_payments: &MonthlyPayments,
+        start_date: Date,
+        end_date: Date,
+    ) -> BlcResult<MonthlyPayments> {
+        Ok(if let Some(inflation) = &self.inflation {
+            monthly_payments
+                .clone()
+                .with_price_index(inflation.sliced_values(start_date, end_date)?.to_vec())
+        } else {
+            monthly_payments.clone()
+        })
     }
 
     pub fn add_tmp(&mut self, chart: Option<TmpChart>) {
@@ -350,8 +1324,115 @@ impl Charts {
         }
     }
 
-    pub fn move_tmp(&mut self) -> Option<TmpChart> {
-        mem::take(&mut self.tmp)
+    pub fn take_tmp(&mut self) -> Option<TmpChart> {
+        self.tmp.take()
+    }
+
+    /// Replaces the persisted chart `idx` by its backwards extension with `proxy`
+    pub fn splice(&mut self, idx: usize, proxy: SpliceProxy) -> BlcResult<()> {
+        let chart = self
+            .persisted
+            .get(idx)
+            .ok_or_else(|| blcerr!("there is no chart {idx}"))?;
+        if chart.is_derived() {
+            Err(blcerr!(
+                "{} is derived from other charts and cannot be extended",
+                chart.name
+            ))?;
+        }
+        let spliced = match proxy {
+            SpliceProxy::Chart(proxy_idx) if proxy_idx == idx => {
+                Err(blcerr!("a chart cannot be its own proxy"))
+            }
+            SpliceProxy::Chart(proxy_idx) => self
+                .persisted
+                .get(proxy_idx)
+                .ok_or_else(|| blcerr!("there is no chart {proxy_idx}"))
+                .and_then(|proxy| chart.spliced(proxy)),
+            SpliceProxy::Calibrated { n_months, seed } => chart
+                .calibrated_proxy(n_months, seed)
+                .and_then(|proxy| chart.spliced(&proxy)),
+        }?;
+        self.persisted[idx] = spliced;
+        self.update_derived()?;
+        self.update_start_end_sliders();
+        Ok(())
+    }
+
+    /// Adds a chart that is computed from `expression` over the persisted charts. Charts are
+    /// referenced by their [`expression_identifier`].
+    pub fn add_derived(&mut self, name: &str, expression: &str) -> BlcResult<()> {
+        let name = if name.trim().is_empty() {
+            expression.trim().to_string()
+        } else {
+            name.trim().to_string()
+        };
+        let chart = derived_chart(
+            self.adapt_name(name),
+            expression,
+            self.persisted.iter(),
+            |c, start, end| Ok(self.converted_values(c, start, end)?.into_owned()),
+        )?;
+        self.push_persisted(chart);
+        self.update_start_end_sliders();
+        Ok(())
+    }
+
+    /// Adds a daily-reset leveraged or inverse product on the persisted chart `idx`
+    pub fn add_leveraged(
+        &mut self,
+        idx: usize,
+        leverage: f64,
+        borrowing_cost: f64,
+        ter: f64,
+    ) -> BlcResult<()> {
+        let underlying = self
+            .persisted
+            .get(idx)
+            .ok_or_else(|| blcerr!("there is no chart {idx}"))?;
+        let name = self.adapt_name(format!("{leverage}x {}", underlying.name));
+        let mut chart = leveraged_chart(name, underlying, leverage, borrowing_cost)?;
+        chart.expense_ratio = ter;
+        self.push_persisted(chart);
+        self.update_start_end_sliders();
+        Ok(())
+    }
+
+    /// Recomputes all derived charts. A derived chart can reference charts that are not derived
+    /// and derived charts before it. Needs to be called whenever a persisted chart, its currency,
+    /// or its dividends change.
+    pub fn update_derived(&mut self) -> BlcResult<()> {
+        self.proposed_fractions = None;
+        for idx in 0..self.persisted.len() {
+            let chart = &self.persisted[idx];
+            let mut inputs = self
+                .persisted
+                .iter()
+                .enumerate()
+                .filter(|(i, c)| *i < idx || !c.is_derived())
+                .map(|(_, c)| c);
+            let derived = match &chart.origin {
+                Origin::Derived { expression } => {
+                    derived_chart(chart.name.clone(), expression, inputs, |c, start, end| {
+                        Ok(self.converted_values(c, start, end)?.into_owned())
+                    })?
+                }
+                Origin::Leveraged {
+                    underlying,
+                    leverage,
+                    borrowing_cost,
+                } => {
+                    let underlying = inputs
+                        .find(|c| &c.name == underlying)
+                        .ok_or_else(|| blcerr!("underlying {underlying} not found"))?;
+                    leveraged_chart(chart.name.clone(), underlying, *leverage, *borrowing_cost)?
+                }
+                _ => continue,
+            };
+            self.persisted[idx].dates = derived.dates;
+            self.persisted[idx].values = derived.values;
+        }
+        Ok(())
     }
 
     fn adapt_name(&self, name: String) -> String {
@@ -363,25 +1444,47 @@ impl Charts {
         }
     }
 
+    fn push_persisted(&mut self, chart: Chart) {
+        self.proposed_fractions = None;
+        self.persisted.push(chart);
+        self.fractions = add_fraction(mem::take(&mut self.fractions));
+        self.fractions_fixed.push(false);
+        self.weight_bounds
+            .resize(self.persisted.len(), WeightBounds::default());
+    }
+
     pub fn persist_tmp(&mut self) {
         if let Some(tmp) = &self.tmp {
             if !tmp.chart.dates.is_empty() {
-                let mut c = mem::take(&mut self.tmp).unwrap();
-                let c = Chart::new(
-                    self.adapt_name(mem::take(&mut c.chart.name)),
-                    c.chart.dates,
-                    c.chart.values,
-                );
-                self.persisted.push(c);
-                self.fractions = add_fraction(mem::take(&mut self.fractions));
-                self.fractions_fixed.push(false);
+                let c = mem::take(&mut self.tmp).unwrap().chart;
+                self.persist(c);
             }
         }
     }
 
+    /// Adds a chart for the balance computation without showing it as temporary chart first
+    pub fn persist(&mut self, mut chart: Chart) {
+        if !chart.dates.is_empty() {
+            chart.name = self.adapt_name(mem::take(&mut chart.name));
+            self.push_persisted(chart);
+        }
+    }
+
+    /// Removes the chart and all derived charts that depend on it
     pub fn remove(&mut self, idx: usize) {
+        self.proposed_fractions = None;
+        let name = self.persisted[idx].name.clone();
+        let dependents = (idx + 1..self.persisted.len())
+            .filter(|i| self.persisted[*i].references(&name))
+            .collect::<Vec<_>>();
+        for dependent in dependents.into_iter().rev() {
+            self.remove(dependent);
+        }
         self.persisted.remove(idx);
         self.fractions_fixed.remove(idx);
+        if idx < self.weight_bounds.len() {
+            self.weight_bounds.remove(idx);
+        }
         let fr_removed = self.fractions.remove(idx);
         let new_fractions = redestribute_fractions(mem::take(&mut self.fractions), fr_removed);
         self.fractions = new_fractions;
@@ -393,6 +1496,206 @@ impl Charts {
         }
     }
 
+    /// Bounds of the optimizer, locked fractions are fixed
+    fn optimizer_bounds(&self) -> Vec<WeightBounds> {
+        (0..self.persisted.len())
+            .map(|idx| {
+                if self.fractions_fixed.get(idx).copied().unwrap_or(false) {
+                    WeightBounds::fixed(self.fractions[idx])
+                } else {
+                    self.weight_bounds.get(idx).copied().unwrap_or_default()
+                }
+            })
+            .collect()
+    }
+
+    /// Fractions according to the objective of the optimizer over the selected timeline
+    pub fn propose_fractions(&self) -> BlcResult<Vec<f64>> {
+        self.propose_fractions_for(self.objective)
+    }
+
+    /// Monthly returns of the price developments, months before the inception of a chart are
+    /// skipped
+    fn complete_monthly_returns(
+        &self,
+        start_date: Date,
+        end_date: Date,
+    ) -> BlcResult<Vec<Vec<f64>>> {
+        let compute_data = self.gather_compute_data(start_date, end_date)?;
+        let returns = compute_data
+            .iter()
+            .map(|pd| monthly_returns(pd))
+            .collect::<Vec<_>>();
+        let n_months = returns.iter().map(|r| r.len()).min().unwrap_or(0);
+        let complete_months = (0..n_months)
+            .filter(|i| returns.iter().all(|r| r[*i].is_finite()))
+            .collect::<Vec<_>>();
+        Ok(returns
+            .iter()
+            .map(|r| complete_months.iter().map(|i| r[*i]).collect())
+            .collect())
+    }
+
+    fn propose_fractions_for(&self, objective: Objective) -> BlcResult<Vec<f64>> {
+        let (start_date, end_date) = self.start_end_date(false)?;
+        let returns = self.complete_monthly_returns(start_date, end_date)?;
+        let risk_free = self
+            .risk_free
+            .as_ref()
+            .map(|rf| rf.sliced_values(start_date, end_date))
+            .transpose()?
+            .map(|rf| mean(&monthly_returns(rf)))
+            .unwrap_or(0.0);
+        optimize_fractions(
+            &as_slices(&returns),
+            risk_free,
+            objective,
+            &self.optimizer_bounds(),
+            &self.fractions,
+        )
+    }
+
+    /// Correlations and covariances of the monthly returns over the selected timeline
+    pub fn return_matrices(&self) -> BlcResult<ReturnMatrices> {
+        let (start_date, end_date) = self.start_end_date(false)?;
+        let returns_perc = self
+            .complete_monthly_returns(start_date, end_date)?
+            .into_iter()
+            .map(|r| r.into_iter().map(|x| x * 100.0).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let n_months = returns_perc.iter().map(|r| r.len()).min().unwrap_or(0);
+        if n_months < 2 {
+            Err(blcerr!(
+                "correlations need at least 2 months in which all charts have returns"
+            ))?;
+        }
+        let covariance = covariance_matrix(&as_slices(&returns_perc));
+        Ok(ReturnMatrices {
+            names: self.persisted.iter().map(|c| c.name.clone()).collect(),
+            correlation: correlation_matrix(&covariance),
+            covariance,
+            n_months,
+        })
+    }
+
+    /// Correlation of the monthly returns of two persisted charts over windows of `window`
+    /// months, dated with the last month of each window
+    pub fn rolling_correlation(
+        &self,
+        idx1: usize,
+        idx2: usize,
+        window: usize,
+    ) -> BlcResult<(Vec<Date>, Vec<f64>)> {
+        let n_charts = self.persisted.len();
+        if idx1 >= n_charts || idx2 >= n_charts {
+            Err(blcerr!("there are only {n_charts} charts"))?;
+        }
+        let (start_date, end_date) = self.start_end_date(false)?;
+        let compute_data = self.gather_compute_data(start_date, end_date)?;
+        let correlations = rolling_correlation(
+            &monthly_returns(&compute_data[idx1]),
+            &monthly_returns(&compute_data[idx2]),
+            window,
+        );
+        if correlations.is_empty() {
+            Err(blcerr!(
+                "the window needs at least 2 and at most {} months",
+                start_date.n_month_until(end_date)?
+            ))?;
+        }
+        let dates = fill_between(start_date, end_date)
+            .into_iter()
+            .skip(window)
+            .collect();
+        Ok((dates, correlations))
+    }
+
+    /// Shows the optimizer and returns true if proposed fractions have been applied
+    pub fn optimizer_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut applied = false;
+        let mut inputs_changed = false;
+        self.weight_bounds
+            .resize(self.persisted.len(), WeightBounds::default());
+        egui::CollapsingHeader::new("Optimize fractions").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Objective");
+                egui::ComboBox::from_id_salt("optimizer-objective")
+                    .selected_text(self.objective.to_string())
+                    .show_ui(ui, |ui| {
+                        for objective in [
+                            Objective::MinVariance,
+                            Objective::MaxSharpe,
+                            Objective::RiskParity,
+                            Objective::EqualWeight,
+                        ] {
+                            inputs_changed |= ui
+                                .selectable_value(
+                                    &mut self.objective,
+                                    objective,
+                                    objective.to_string(),
+                                )
+                                .changed();
+                        }
+                    });
+            });
+            let proposed = match &self.proposed_fractions {
+                Some(Ok(proposed)) if proposed.len() == self.persisted.len() => {
+                    Some(proposed.clone())
+                }
+                _ => None,
+            };
+            egui::Grid::new("grid-optimizer-bounds").show(ui, |ui| {
+                for (idx, bounds) in self.weight_bounds.iter_mut().enumerate() {
+                    ui.label(self.persisted[idx].name());
+                    if self.fractions_fixed[idx] {
+                        ui.label(format!("fixed at {:0.2}", self.fractions[idx]));
+                        ui.label("");
+                    } else {
+                        inputs_changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut bounds.min)
+                                    .speed(0.01)
+                                    .range(0.0..=bounds.max)
+                                    .prefix("min "),
+                            )
+                            .changed();
+                        inputs_changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut bounds.max)
+                                    .speed(0.01)
+                                    .range(bounds.min..=1.0)
+                                    .prefix("max "),
+                            )
+                            .changed();
+                    }
+                    if let Some(proposed) = &proposed {
+                        ui.label(format!("proposed {:0.3}", proposed[idx]));
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Propose").clicked() {
+                    self.proposed_fractions = Some(self.propose_fractions());
+                }
+                if let Some(proposed) = proposed {
+                    if ui.button("Apply").clicked() {
+                        self.fractions = proposed;
+                        self.proposed_fractions = None;
+                        applied = true;
+                    }
+                }
+            });
+            if let Some(Err(e)) = &self.proposed_fractions {
+                ui.label(format!("{e}"));
+            }
+        });
+        if inputs_changed {
+            self.proposed_fractions = None;
+        }
+        applied
+    }
+
     pub fn fraction_sliders(&mut self, ui: &mut Ui) -> bool {
         let chart_inds = 0..(self.persisted.len());
         let mut remove_idx = None;
@@ -402,7 +1705,11 @@ impl Charts {
             .show(ui, |ui| {
                 egui::Grid::new("grid-persistend-charts").show(ui, |ui| {
                     for idx in chart_inds {
-                        ui.label(self.persisted[idx].name());
+                        let name_label = ui.label(self.persisted[idx].name());
+                        let provenance = self.persisted[idx].provenance();
+                        if !provenance.is_empty() {
+                            name_label.on_hover_text(provenance);
+                        }
                         if self.fractions.len() > 1 {
                             let slider =
                                 ui.add(egui::Slider::new(&mut self.fractions[idx], 0.0..=1.0));
@@ -428,10 +1735,22 @@ impl Charts {
                                 recompute = true;
                             }
 
-                            if self.fractions.len() > 2 {
-                                ui.checkbox(&mut self.fractions_fixed[idx], "fix");
+                            if self.fractions.len() > 2
+                                && ui.checkbox(&mut self.fractions_fixed[idx], "fix").changed()
+                            {
+                                self.proposed_fractions = None;
                             }
                         }
+                        let ter = ui.add(
+                            egui::DragValue::new(&mut self.persisted[idx].expense_ratio)
+                                .speed(0.01)
+                                .range(0.0..=10.0)
+                                .prefix("TER ")
+                                .suffix(" %"),
+                        );
+                        if ter.changed() {
+                            recompute = true;
+                        }
                         if ui.button("x").clicked() {
                             remove_idx = Some(idx);
                             recompute = true;
@@ -442,31 +1761,107 @@ impl Charts {
                         self.remove(idx);
                     }
                 });
+                ui.horizontal(|ui| {
+                    ui.label("Yearly advisory fee");
+                    let fee = ui.add(
+                        egui::DragValue::new(&mut self.advisory_fee)
+                            .speed(0.01)
+                            .range(0.0..=10.0)
+                            .suffix(" %"),
+                    );
+                    if fee.changed() {
+                        recompute = true;
+                    }
+                });
             });
         recompute
     }
 
+    /// Values of `c` in the base currency with reinvested dividends between both dates
+    fn converted_values<'a>(
+        &'a self,
+        c: &'a Chart,
+        start_date: Date,
+        end_date: Date,
+    ) -> BlcResult<Cow<'a, [f64]>> {
+        let values = Cow::Borrowed(c.sliced_values(start_date, end_date)?);
+        let values = if c.dividends.is_active() && c.dividends.mode == DividendMode::Reinvest {
+            let net_yields = c.dividends.net_monthly_yields(start_date, end_date)?;
+            Cow::Owned(with_reinvested_dividends(&values, &net_yields))
+        } else {
+            values
+        };
+        Ok(if c.hedged {
+            Cow::Owned(with_hedging_cost(&values, c.hedging_cost))
+        } else if let Some(currency) = c.currency_to_convert(&self.base_currency) {
+            let fx_rates = self
+                .fx_rate(currency)?
+                .sliced_values(start_date, end_date)?;
+            Cow::Owned(convert_currency(&values, fx_rates))
+        } else {
+            values
+        })
+    }
+
+    /// Price developments in the base currency, `NaN` before the inception of a chart
     fn gather_compute_data(&self, start_date: Date, end_date: Date) -> BlcResult<ComputeData<'_>> {
         let price_devs = self
             .persisted
             .iter()
-            .map(|c| c.sliced_values(start_date, end_date))
+            .map(|c| {
+                let timeline_start = start_date;
+                let start_date = (start_date + n_months_before(&c.dates, start_date)?)?;
+                let values = self.converted_values(c, start_date, end_date)?;
+                nan_padded(&c.dates, timeline_start, values)
+            })
             .collect::<BlcResult<Vec<_>>>()?;
         Ok(price_devs)
     }
 
+    /// Net monthly dividend yields of all price developments, empty if there are no payouts and
+    /// zero before the inception of a chart such that they are aligned with the prices
+    fn dividend_payout_yields(&self, start_date: Date, end_date: Date) -> BlcResult<Vec<Vec<f64>>> {
+        self.persisted
+            .iter()
+            .map(|c| {
+                if c.dividends.is_active() && c.dividends.mode == DividendMode::Payout {
+                    let n_missing = n_months_before(&c.dates, start_date)?;
+                    let yields = c
+                        .dividends
+                        .net_monthly_yields((start_date + n_missing)?, end_date)?;
+                    Ok(iter::repeat(0.0).take(n_missing).chain(yields).collect())
+                } else {
+                    Ok(vec![])
+                }
+            })
+            .collect()
+    }
+
     pub fn find_bestrebalancetrigger(
         &self,
         initial_balance: f64,
         monthly_payments: &MonthlyPayments,
     ) -> BlcResult<BestRebalanceTrigger> {
         let (start_date, end_date) = self.start_end_date(false)?;
-        let price_devs = self.gather_compute_data(start_date, end_date)?;
+        let compute_data = self.gather_compute_data(start_date, end_date)?;
+        let price_devs = as_slices(&compute_data);
+        let monthly_payments =
+            self.payments_with_inflation(monthly_payments, start_date, end_date)?;
+        let expense_ratios = self.expense_ratios();
+        let payout_yields = self.dividend_payout_yields(start_date, end_date)?;
         best_rebalance_trigger(
             &price_devs,
             initial_balance,
-            Some(monthly_payments),
-            &self.fractions,
+            Some(&monthly_payments),
+            RebalanceData {
+                trigger: RebalanceTrigger::default(),
+                fractions: &self.fractions,
+                fees: self.fees(&expense_ratios),
+                dividend_payouts: DividendPayouts {
+                    net_monthly_yields: &as_slices(&payout_yields),
+                },
+                margin: self.margin,
+            },
             start_date,
         )
     }
@@ -476,22 +1871,240 @@ impl Charts {
         monthly_payments: &MonthlyPayments,
         rebalance_trigger: RebalanceTrigger,
     ) -> BlcResult<RebalanceStats> {
+        let (start_date, end_date) = self.start_end_date(false)?;
+        let expense_ratios = self.expense_ratios();
+        let payout_yields = self.dividend_payout_yields(start_date, end_date)?;
+        let payout_yields = as_slices(&payout_yields);
         let rebalance_data = RebalanceData {
             trigger: rebalance_trigger,
             fractions: &self.fractions,
+            fees: self.fees(&expense_ratios),
+            dividend_payouts: DividendPayouts {
+                net_monthly_yields: &payout_yields,
+            },
+            margin: self.margin,
         };
-        let (start_date, end_date) = self.start_end_date(false)?;
-        let price_devs = self.gather_compute_data(start_date, end_date)?;
+        let compute_data = self.gather_compute_data(start_date, end_date)?;
+        let price_devs = as_slices(&compute_data);
+        let monthly_payments =
+            self.payments_with_inflation(monthly_payments, start_date, end_date)?;
         rebalance_stats(
             &price_devs,
             initial_balance,
-            Some(monthly_payments),
+            Some(&monthly_payments),
             rebalance_data,
             start_date,
             10,
         )
     }
 
+    /// Solves for the free variable such that the portfolio reaches the target at the end of the
+    /// selected timeline. In case of [`GoalVariable::YearlyReturn`], the return of the price
+    /// development with index `chart_idx` is adapted.
+    pub fn solve_goal(
+        &self,
+        initial_balance: f64,
+        monthly_payments: &MonthlyPayments,
+        rebalance_trigger: RebalanceTrigger,
+        variable: GoalVariable,
+        chart_idx: usize,
+        target: GoalTarget,
+    ) -> BlcResult<f64> {
+        let (start_date, end_date) = self.start_end_date(false)?;
+        let n_months = start_date.n_month_until(end_date)?;
+        let compute_data = self.gather_compute_data(start_date, end_date)?;
+        let price_devs = as_slices(&compute_data);
+        let monthly_payments =
+            &self.payments_with_inflation(monthly_payments, start_date, end_date)?;
+        let expense_ratios = self.expense_ratios();
+        let payout_yields = self.dividend_payout_yields(start_date, end_date)?;
+        let payout_yields = as_slices(&payout_yields);
+        let excess =
+            |price_devs: &[&[f64]], initial_balance, monthly_payments: &MonthlyPayments| {
+                let (balance, total_payments) = compute_total_balance(
+                    price_devs,
+                    initial_balance,
+                    Some(monthly_payments),
+                    RebalanceData {
+                        trigger: rebalance_trigger,
+                        fractions: &self.fractions,
+                        fees: self.fees(&expense_ratios),
+                        dividend_payouts: DividendPayouts {
+                            net_monthly_yields: &payout_yields,
+                        },
+                        margin: self.margin,
+                    },
+                    start_date,
+                )?;
+                Ok(target.excess(balance, total_payments, n_months))
+            };
+        let upper = initial_balance.abs().max(1.0);
+        match variable {
+            GoalVariable::MonthlyPayment => bisection(
+                |x| {
+                    excess(
+                        &price_devs,
+                        initial_balance,
+                        &MonthlyPayments::from_constant(x),
+                    )
+                },
+                f64::NEG_INFINITY,
+                upper / 100.0,
+            ),
+            GoalVariable::InitialBalance => {
+                bisection(|x| excess(&price_devs, x, monthly_payments), 0.0, upper)
+            }
+            GoalVariable::YearlyReturn => {
+                if chart_idx >= price_devs.len() {
+                    Err(blcerr!("no price development with index {chart_idx}"))?;
+                }
+                bisection(
+                    |r| {
+                        let rescaled = with_yearly_return(price_devs[chart_idx], r);
+                        let mut price_devs = price_devs.clone();
+                        price_devs[chart_idx] = &rescaled;
+                        excess(&price_devs, initial_balance, monthly_payments)
+                    },
+                    -99.0,
+                    10.0,
+                )
+            }
+        }
+    }
+
+    /// Sweeps the fractions of two or three price developments and computes the risk and the
+    /// return of each mix with the rebalancing trigger and with buy-and-hold
+    pub fn weight_grid(
+        &self,
+        initial_balance: f64,
+        monthly_payments: &MonthlyPayments,
+        rebalance_trigger: RebalanceTrigger,
+    ) -> BlcResult<WeightGrid> {
+        let n_assets = self.persisted.len();
+        if !(2..=3).contains(&n_assets) {
+            Err(blcerr!(
+                "the weight grid needs 2 or 3 price developments, there are {n_assets}"
+            ))?;
+        }
+        let (start_date, end_date) = self.start_end_date(false)?;
+        let compute_data = self.gather_compute_data(start_date, end_date)?;
+        let price_devs = as_slices(&compute_data);
+        let monthly_payments =
+            self.payments_with_inflation(monthly_payments, start_date, end_date)?;
+        let expense_ratios = self.expense_ratios();
+        let payout_yields = self.dividend_payout_yields(start_date, end_date)?;
+        let payout_yields = as_slices(&payout_yields);
+        let risk_return = |fractions: &[f64], trigger| {
+            let balance_over_month = compute_balance_over_months(
+                &price_devs,
+                initial_balance,
+                Some(&monthly_payments),
+                RebalanceData {
+                    trigger,
+                    fractions,
+                    fees: self.fees(&expense_ratios),
+                    dividend_payouts: DividendPayouts {
+                        net_monthly_yields: &payout_yields,
+                    },
+                    margin: self.margin,
+                },
+                start_date,
+            );
+            let (balances, payments, _, payouts) = unzip_balance_iter(balance_over_month)?;
+            Ok(RiskReturn::from_returns(&portfolio_returns(
+                &balances,
+                &net_payments(&payments, &payouts),
+            )))
+        };
+        let mix = |fractions: Vec<f64>| -> BlcResult<Mix> {
+            Ok(Mix {
+                rebalanced: risk_return(&fractions, rebalance_trigger)?,
+                buy_and_hold: risk_return(&fractions, RebalanceTrigger::default())?,
+                fractions,
+            })
+        };
+        let mixes = weight_grid_fractions(n_assets, WEIGHT_GRID_STEPS)
+            .into_iter()
+            .map(mix)
+            .collect::<BlcResult<Vec<_>>>()?;
+        let picks = [
+            Objective::MinVariance,
+            Objective::MaxSharpe,
+            Objective::RiskParity,
+            Objective::EqualWeight,
+        ]
+        .into_iter()
+        .filter_map(|objective| {
+            let fractions = self.propose_fractions_for(objective).ok()?;
+            Some(mix(fractions).map(|m| (objective, m)))
+        })
+        .collect::<BlcResult<Vec<_>>>()?;
+        Ok(WeightGrid {
+            names: self.persisted.iter().map(|c| c.name.clone()).collect(),
+            mixes,
+            current: mix(self.fractions.clone())?,
+            picks,
+        })
+    }
+
+    /// Runs the plan over `n_months` from every start month of the selected timeline
+    pub fn rolling_windows(
+        &self,
+        initial_balance: f64,
+        monthly_payments: &MonthlyPayments,
+        rebalance_trigger: RebalanceTrigger,
+        n_months: usize,
+    ) -> BlcResult<RollingWindows> {
+        let (start_date, end_date) = self.start_end_date(false)?;
+        let n_months_timeline = start_date.n_month_until(end_date)?;
+        if n_months == 0 || n_months > n_months_timeline {
+            Err(blcerr!(
+                "the horizon needs to be between 1 and {n_months_timeline} months"
+            ))?;
+        }
+        let expense_ratios = self.expense_ratios();
+        let windows = (0..=(n_months_timeline - n_months))
+            .map(|offset| {
+                let start = (start_date + offset)?;
+                let end = (start + n_months)?;
+                let compute_data = self.gather_compute_data(start, end)?;
+                let price_devs = as_slices(&compute_data);
+                let monthly_payments =
+                    self.payments_with_inflation(monthly_payments, start, end)?;
+                let payout_yields = self.dividend_payout_yields(start, end)?;
+                let payout_yields = as_slices(&payout_yields);
+                let balance_over_month = compute_balance_over_months(
+                    &price_devs,
+                    initial_balance,
+                    Some(&monthly_payments),
+                    RebalanceData {
+                        trigger: rebalance_trigger,
+                        fractions: &self.fractions,
+                        fees: self.fees(&expense_ratios),
+                        dividend_payouts: DividendPayouts {
+                            net_monthly_yields: &payout_yields,
+                        },
+                        margin: self.margin,
+                    },
+                    start,
+                );
+                let (balances, payments, _, payouts) = unzip_balance_iter(balance_over_month)?;
+                let final_balance = balances[balances.len() - 1];
+                Ok(WindowOutcome {
+                    start,
+                    final_balance,
+                    irr: internal_rate_of_return(
+                        &net_payments(&payments, &payouts),
+                        final_balance,
+                    )?,
+                    balances,
+                    payments,
+                })
+            })
+            .collect::<BlcResult<Vec<_>>>()?;
+        Ok(RollingWindows { n_months, windows })
+    }
+
     pub fn compute_balance(
         &mut self,
         initial_balance: f64,
@@ -499,25 +2112,98 @@ impl Charts {
         rebalance_trigger: RebalanceTrigger,
     ) -> BlcResult<()> {
         let (start_date, end_date) = self.start_end_date(false)?;
-        let price_devs = self.gather_compute_data(start_date, end_date)?;
+        let compute_data = self.gather_compute_data(start_date, end_date)?;
+        let price_devs = as_slices(&compute_data);
+        let monthly_payments =
+            self.payments_with_inflation(monthly_payments, start_date, end_date)?;
+        let expense_ratios = self.expense_ratios();
+        let payout_yields = self.dividend_payout_yields(start_date, end_date)?;
+        let payout_yields = as_slices(&payout_yields);
         let balance_over_month = compute_balance_over_months(
             &price_devs,
             initial_balance,
-            Some(monthly_payments),
+            Some(&monthly_payments),
             RebalanceData {
                 trigger: rebalance_trigger,
                 fractions: &self.fractions,
+                fees: self.fees(&expense_ratios),
+                dividend_payouts: DividendPayouts {
+                    net_monthly_yields: &payout_yields,
+                },
+                margin: self.margin,
             },
             start_date,
         );
-        let (balances, payments) = unzip_balance_iter(balance_over_month)?;
-        let dates = self.persisted[0]
-            .sliced_dates(start_date, end_date)?
-            .to_vec();
+        let (balances, payments, fees, payouts) = unzip_balance_iter(balance_over_month)?;
+        self.total_fees = fees.last().copied();
+        let net_payments = net_payments(&payments, &payouts);
+        let dates = fill_between(start_date, end_date);
+        let risk_free = self
+            .risk_free
+            .as_ref()
+            .map(|rf| rf.sliced_values(start_date, end_date))
+            .transpose()?;
+        self.sharpe_ratio = sharpe_ratio(
+            &portfolio_returns(&balances, &net_payments),
+            &risk_free
+                .map(monthly_returns)
+                .unwrap_or_else(|| vec![0.0; balances.len()]),
+        );
+        // payments that depend on several price developments cannot be computed for the
+        // savings account, hence we skip the baseline in case of errors
+        let savings_balances = risk_free.and_then(|rf| {
+            let price_devs = [rf];
+            let balance_over_month = compute_balance_over_months(
+                &price_devs,
+                initial_balance,
+                Some(&monthly_payments),
+                RebalanceData {
+                    trigger: RebalanceTrigger::default(),
+                    fractions: &[1.0],
+                    fees: Fees::default(),
+                    dividend_payouts: DividendPayouts::default(),
+                    margin: None,
+                },
+                start_date,
+            );
+            unzip_balance_iter(balance_over_month)
+                .ok()
+                .map(|(balances, _, _, _)| balances)
+        });
+        self.savings_baseline = savings_balances
+            .as_ref()
+            .map(|b| Chart::new("savings account".to_string(), dates.clone(), b.clone()));
+        if let Some(inflation) = &self.inflation {
+            let price_index = inflation.sliced_values(start_date, end_date)?;
+            self.savings_baseline_real = savings_balances.map(|b| {
+                Chart::new(
+                    "savings account (real)".to_string(),
+                    dates.clone(),
+                    deflate(&b, price_index),
+                )
+            });
+            self.total_balance_over_month_real = Some(Chart::new(
+                "portfolio value (real)".to_string(),
+                dates.clone(),
+                deflate(&balances, price_index),
+            ));
+            self.total_payments_over_month_real = Some(Chart::new(
+                "total payments (real)".to_string(),
+                dates.clone(),
+                deflate_cumulative(&payments, price_index),
+            ));
+            self.total_payouts_over_month_real = Some(Chart::new(
+                "total dividend payouts (real)".to_string(),
+                dates.clone(),
+                deflate_cumulative(&payouts, price_index),
+            ));
+        }
         let b_chart = Chart::new("portfolio value".to_string(), dates.clone(), balances);
-        let p_chart = Chart::new("total payments".to_string(), dates, payments);
+        let p_chart = Chart::new("total payments".to_string(), dates.clone(), payments);
+        let po_chart = Chart::new("total dividend payouts".to_string(), dates, payouts);
         self.total_balance_over_month = Some(b_chart);
         self.total_payments_over_month = Some(p_chart);
+        self.total_payouts_over_month = Some(po_chart);
         Ok(())
     }
 
@@ -531,10 +2217,14 @@ impl Charts {
     pub fn plot(&self, ui: &mut Ui) -> BlcResult<()> {
         let charts_to_plot = if self.plot_balance {
             if let (Some(balances), Some(payments)) = (
-                &self.total_balance_over_month,
-                &self.total_payments_over_month,
+                self.total_balance_over_month(),
+                self.total_payments_over_month(),
             ) {
-                vec![balances, payments]
+                iter::once(balances)
+                    .chain(iter::once(payments))
+                    .chain(self.total_payouts_over_month())
+                    .chain(self.savings_baseline())
+                    .collect()
             } else {
                 vec![]
             }
@@ -554,6 +2244,15 @@ impl Charts {
         };
         let start_date = dates.first().copied();
         let end_date = dates.last().copied();
+        // balance charts are deflated already
+        let price_index = match (&self.inflation, start_date, end_date) {
+            (Some(inflation), Some(start), Some(end))
+                if self.shows_real_values() && !self.plot_balance =>
+            {
+                Some(inflation.sliced_values(start, end)?)
+            }
+            _ => None,
+        };
         let x_fmt_tbom = move |x: GridMark, _range: &RangeInclusive<f64>| {
             if x.value.fract().abs() < 1e-6 {
                 let i = x.value.round() as usize;
@@ -582,9 +2281,27 @@ impl Charts {
                                 } else {
                                     self.tmp.as_ref().map(|tmp| tmp.initial_balance)
                                 },
+                                price_index,
                             ) {
                                 plot_ui.line(line);
                             }
+                            // mark where proxies have been spliced in
+                            for splice in &c.splices {
+                                if let Some(x) = splice
+                                    .join
+                                    .n_month_until(end)
+                                    .ok()
+                                    .and_then(|_| start.n_month_until(splice.join).ok())
+                                {
+                                    plot_ui.vline(
+                                        VLine::new(
+                                            format!("{} spliced before {}", c.name, splice.join),
+                                            x as f64,
+                                        )
+                                        .style(LineStyle::dashed_loose()),
+                                    );
+                                }
+                            }
                         }
                     }
                 }
@@ -592,6 +2309,16 @@ impl Charts {
         Ok(())
     }
 }
+/// Row of a csv with dates as columns, months before the first value are left empty
+fn values_to_csv_row(name: &str, values: &[[f64; 2]]) -> String {
+    let n_missing = values.first().map_or(0, |[x, _]| *x as usize);
+    values
+        .iter()
+        .map(|[_, v]| v)
+        .fold(format!("{name}{}", ",".repeat(n_missing)), |v1, v2| {
+            format!("{v1},{v2}")
+        })
+}
 fn charts_to_string(charts: &Charts) -> BlcResult<String> {
     const WITH_TMP: bool = true;
     let dates = charts.dates(WITH_TMP)?;
@@ -607,13 +2334,7 @@ fn charts_to_string(charts: &Charts) -> BlcResult<String> {
                 end,
                 charts.tmp.as_ref().map(|tmp| tmp.initial_balance),
             ) {
-                Ok(tmp_values) => {
-                    let tmp_name = tmp.chart.name();
-                    tmp_values
-                        .iter()
-                        .map(|[_, v]| v)
-                        .fold(tmp_name.to_string(), |v1, v2| format!("{v1},{v2}"))
-                }
+                Ok(tmp_values) => values_to_csv_row(tmp.chart.name(), &tmp_values),
                 Err(_) => "".to_string(),
             }
         } else {
@@ -621,16 +2342,14 @@ fn charts_to_string(charts: &Charts) -> BlcResult<String> {
         };
 
         for c in &charts.persisted {
-            let cur_csv_str = c
-                .values_between_dates(
+            let cur_csv_str = values_to_csv_row(
+                c.name(),
+                &c.values_between_dates(
                     start,
                     end,
                     charts.tmp.as_ref().map(|tmp| tmp.initial_balance),
-                )
-                .unwrap()
-                .iter()
-                .map(|[_, v]| v)
-                .fold(c.name().to_string(), |v1, v2| format!("{v1},{v2}"));
+                )?,
+            );
 
             csv_str = if !csv_str.is_empty() {
                 format!("{csv_str}\n{cur_csv_str}")
@@ -655,7 +2374,13 @@ impl Display for Charts {
 }
 
 #[cfg(test)]
-use {crate::date::Interval, exmex::parse_val};
+use {super::frontier::RiskMeasure, crate::compute::PaymentVars, crate::date::Interval};
+
+/// `n` consecutive months beginning with `start`
+#[cfg(test)]
+fn monthly_dates(start: Date, n: usize) -> Vec<Date> {
+    (0..n).map(|i| date_after_nmonths(start, i)).collect()
+}
 
 #[test]
 fn test_add_fraction() {
@@ -738,11 +2463,354 @@ fn test_monthly_payments() {
     let start = Date::from_str("2000/01").unwrap();
     let end = Date::from_str("2000/12").unwrap();
 
+    let vars = PaymentVars {
+        initial_balance: 0.0,
+        month: 0,
+        date: Date::new(1999, 12).unwrap(),
+        values: &[],
+        debt: 0.0,
+        peak_balance: 0.0,
+        total_payments: 0.0,
+    };
+
     let expr = parse_val("100.0").unwrap();
     let mp = MonthlyPayments::from_single_payment(expr.clone());
-    assert!((100.0 - mp.compute(Date::new(1999, 12).unwrap(), &[]).unwrap()).abs() < 1e-8);
+    assert!((100.0 - mp.compute(&vars).unwrap()).abs() < 1e-8);
 
     let mp = MonthlyPayments::from_intervals(vec![expr], vec![Interval::new(start, end).unwrap()])
         .unwrap();
-    assert!((mp.compute(Date::new(1999, 12).unwrap(), &[]).unwrap()).abs() < 1e-8);
+    assert!((mp.compute(&vars).unwrap()).abs() < 1e-8);
+}
+
+#[test]
+fn test_chart_metadata() {
+    let old = r#"{"name": "world", "dates": [{"date": 202001}], "values": [1.0]}"#;
+    let chart: Chart = serde_json::from_str(old).unwrap();
+    assert_eq!(chart.origin, Origin::Unknown);
+    assert!(chart.index_type.is_none());
+    assert_eq!(chart.provenance(), "unknown origin");
+
+    let mut chart = Chart::new("sim".to_string(), chart.dates, chart.values);
+    chart.origin = Origin::Simulated {
+        parameters: "expected yearly return 5%".to_string(),
+        seed: Some(42),
+    };
+    chart.currency = "USD".to_string();
+    chart.index_type = Some(IndexType::NetReturn);
+    let chart: Chart = serde_json::from_str(&serde_json::to_string(&chart).unwrap()).unwrap();
+    assert_eq!(
+        chart.provenance(),
+        "simulated with expected yearly return 5%, seed 42\ncurrency USD\nnet total return index"
+    );
+}
+
+#[test]
+fn test_splice() {
+    let d2020 = Date::new(2020, 1).unwrap();
+    let d2021 = Date::new(2021, 1).unwrap();
+    let proxy = Chart::new("world".to_string(), monthly_dates(d2020, 24), vec![2.0; 24]);
+    let short = Chart::new("em".to_string(), monthly_dates(d2021, 12), vec![5.0; 12]);
+
+    let mut charts = Charts {
+        persisted: vec![short, proxy],
+        ..Charts::default()
+    };
+    charts.splice(0, SpliceProxy::Chart(0)).unwrap_err();
+    charts.splice(0, SpliceProxy::Chart(1)).unwrap();
+    let spliced = &charts.persisted[0];
+    assert_eq!(spliced.dates.len(), 24);
+    assert_eq!(spliced.dates[0], d2020);
+    assert_eq!(spliced.values, vec![5.0; 24]);
+    assert_eq!(spliced.splices[0].join, d2021);
+    assert!((spliced.splices[0].scale - 2.5).abs() < 1e-12);
+    assert!(spliced.provenance().contains("spliced with world"));
+    assert!(charts.persisted[1].spliced(spliced).is_err());
+
+    let values = (0..12).map(|i| 1.0 + 0.01 * i as f64).collect();
+    let short = Chart::new("em".to_string(), monthly_dates(d2021, 12), values);
+    charts.persisted = vec![short];
+    charts
+        .splice(
+            0,
+            SpliceProxy::Calibrated {
+                n_months: 6,
+                seed: 1,
+            },
+        )
+        .unwrap();
+    let spliced = &charts.persisted[0];
+    assert_eq!(spliced.dates[0], Date::new(2020, 7).unwrap());
+    assert_eq!(spliced.values.len(), 18);
+    assert_eq!(spliced.values[6], 1.0);
+    assert!(matches!(
+        spliced.splices[0].proxy_origin,
+        Origin::Simulated { seed: Some(1), .. }
+    ));
+}
+
+#[test]
+fn test_union_timeline() {
+    let d2020 = Date::new(2020, 1).unwrap();
+    let d2021 = Date::new(2021, 1).unwrap();
+    let world = Chart::new("world".to_string(), monthly_dates(d2020, 24), vec![1.0; 24]);
+    let mut em_values = vec![1.0; 12];
+    em_values[11] = 2.0;
+    let em = Chart::new("em".to_string(), monthly_dates(d2021, 12), em_values);
+    let mut charts = Charts {
+        persisted: vec![world, em],
+        fractions: vec![0.5, 0.5],
+        ..Charts::default()
+    };
+    assert_eq!(charts.start_end_date(false).unwrap().0, d2021);
+    charts.union_timeline = true;
+    let (start, end) = charts.start_end_date(false).unwrap();
+    assert_eq!((start, end), (d2020, Date::new(2021, 12).unwrap()));
+
+    let compute_data = charts.gather_compute_data(start, end).unwrap();
+    assert_eq!(compute_data[1].len(), 24);
+    assert!(compute_data[1][11].is_nan());
+    assert_eq!(compute_data[1][12], 1.0);
+
+    charts
+        .compute_balance(
+            100.0,
+            &MonthlyPayments::default(),
+            RebalanceTrigger::default(),
+        )
+        .unwrap();
+    let balance = charts.total_balance_over_month().unwrap();
+    assert_eq!(balance.dates.len(), 24);
+    assert!((balance.values[12] - 100.0).abs() < 1e-12);
+    assert!((balance.values[23] - 150.0).abs() < 1e-12);
+
+    let csv = charts_to_string(&charts).unwrap();
+    assert!(csv.contains(&format!("\nem{}1,", ",".repeat(13))));
+
+    let yields = (0..24).map(|i| i as f64 * 12.0).collect();
+    charts.persisted[1].dividends = Dividends {
+        yield_series: Some(Box::new(Chart::new(
+            "em dividends".to_string(),
+            monthly_dates(d2020, 24),
+            yields,
+        ))),
+        mode: DividendMode::Payout,
+        ..Dividends::default()
+    };
+    let payout_yields = charts.dividend_payout_yields(start, end).unwrap();
+    assert!(payout_yields[0].is_empty());
+    assert_eq!(payout_yields[1].len(), 24);
+    assert_eq!(payout_yields[1][11], 0.0);
+    assert!((payout_yields[1][12] - 0.12).abs() < 1e-12);
+}
+
+#[test]
+fn test_derived_charts() {
+    assert_eq!(expression_identifier("MSCI World"), "msci_world");
+    assert_eq!(expression_identifier("500 S&P"), "_500_s_p");
+
+    let d2020 = Date::new(2020, 1).unwrap();
+    let world = Chart::new(
+        "MSCI World".to_string(),
+        monthly_dates(d2020, 24),
+        vec![2.0; 24],
+    );
+    let d2021 = Date::new(2021, 1).unwrap();
+    let em = Chart::new("em".to_string(), monthly_dates(d2021, 12), vec![1.0; 12]);
+    let mut charts = Charts::default();
+    for chart in [world, em] {
+        charts.persist(chart);
+    }
+    charts.add_derived("", "0.5*msci_world + 0.5*em").unwrap();
+    assert!(charts.add_derived("drag", "mix * 0.5^month").is_err());
+    assert!(charts.add_derived("x", "sp500").is_err());
+    assert_eq!(charts.persisted.len(), 3);
+    assert_eq!(charts.fractions.len(), 3);
+    let mix = &charts.persisted[2];
+    assert_eq!(mix.name(), "0.5*msci_world + 0.5*em");
+    assert_eq!(mix.values, vec![1.5; 12]);
+    assert!(mix.provenance().starts_with("derived as"));
+    assert!(charts.splice(2, SpliceProxy::Chart(0)).is_err());
+
+    // the expression as name is no valid identifier
+    charts.persisted[2].name = "mix".to_string();
+    charts.add_derived("drag", "mix * 0.5^month").unwrap();
+    assert_eq!(charts.persisted[3].values[..2], [1.5, 0.75]);
+
+    // derived charts are recomputed when an input changes
+    charts.splice(1, SpliceProxy::Chart(0)).unwrap();
+    assert_eq!(charts.persisted[2].values.len(), 24);
+    assert_eq!(charts.persisted[3].values.len(), 24);
+
+    // derived charts are evaluated in the base currency with reinvested dividends
+    charts.base_currency = "EUR".to_string();
+    charts.persisted[1].currency = "USD".to_string();
+    charts.add_tmp(Some(TmpChart {
+        chart: Chart::new("usd".to_string(), monthly_dates(d2020, 24), vec![2.0; 24]),
+        initial_balance: 1.0,
+    }));
+    charts.persist_tmp_as_fx_rate("USD").unwrap();
+    assert_eq!(charts.persisted[2].values, vec![2.0; 24]);
+    charts.persisted[0].dividends.yearly_yield = 12.0;
+    charts.update_derived().unwrap();
+    assert!((charts.persisted[2].values[1] - 2.01).abs() < 1e-12);
+
+    // dependents are removed with their inputs
+    charts.remove(1);
+    assert_eq!(charts.persisted.len(), 1);
+    assert_eq!(charts.fractions.len(), 1);
+}
+
+#[test]
+fn test_leveraged_chart() {
+    let values = (0..24).map(|i| 1.01f64.powi(i)).collect::<Vec<_>>();
+    let mut world = Chart::new(
+        "world".to_string(),
+        monthly_dates(Date::new(2020, 1).unwrap(), 24),
+        values,
+    );
+    world.currency = "USD".to_string();
+    let em = Chart::new(
+        "em".to_string(),
+        monthly_dates(Date::new(2021, 1).unwrap(), 12),
+        vec![1.0; 12],
+    );
+    let mut charts = Charts::default();
+    for chart in [world, em] {
+        charts.persist(chart);
+    }
+    charts.add_leveraged(1, -1.0, 0.0, 0.9).unwrap();
+    charts.add_leveraged(0, 2.0, 0.0, 0.6).unwrap();
+    assert!(charts.add_leveraged(5, 2.0, 0.0, 0.6).is_err());
+    let lev = &charts.persisted[3];
+    assert_eq!(lev.name(), "2x world");
+    assert_eq!(lev.currency, "USD");
+    assert_eq!(lev.expense_ratio, 0.6);
+    assert!((lev.values[23] - 1.01f64.powi(46)).abs() < 1e-9);
+    assert!(lev.provenance().contains("2x leveraged world"));
+
+    charts.splice(1, SpliceProxy::Chart(0)).unwrap();
+    assert_eq!(charts.persisted[2].values.len(), 24);
+    charts.remove(1);
+    assert_eq!(charts.persisted.len(), 2);
+    assert_eq!(charts.persisted[1].name(), "2x world");
+}
+
+#[test]
+fn test_propose_fractions() {
+    let calm = [1.0, 1.1, 1.0, 1.1, 1.0];
+    let wild = [1.0, 1.0, 1.2, 1.2, 1.0];
+    let mut charts = Charts::default();
+    for (name, values) in [("calm", calm), ("wild", wild), ("calm2", calm)] {
+        charts.persist(Chart::new(
+            name.to_string(),
+            monthly_dates(Date::new(2020, 1).unwrap(), 5),
+            values.to_vec(),
+        ));
+    }
+    assert_eq!(charts.weight_bounds.len(), 3);
+    charts.objective = Objective::EqualWeight;
+    charts.fractions = vec![0.6, 0.2, 0.2];
+    charts.fractions_fixed[0] = true;
+    let proposed = charts.propose_fractions().unwrap();
+    assert!((proposed[0] - 0.6).abs() < 1e-9);
+    assert!((proposed[1] - 0.2).abs() < 1e-9);
+
+    charts.fractions_fixed[0] = false;
+    charts.weight_bounds[1].min = 0.3;
+    charts.objective = Objective::MinVariance;
+    let proposed = charts.propose_fractions().unwrap();
+    assert!((proposed.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    assert!(proposed[1] >= 0.3 - 1e-9);
+
+    // proposals for other charts cannot be applied
+    charts.proposed_fractions = Some(Ok(proposed));
+    charts.remove(1);
+    assert!(charts.proposed_fractions.is_none());
+    assert_eq!(charts.weight_bounds, vec![WeightBounds::default(); 2]);
+}
+
+#[test]
+fn test_weight_grid() {
+    let dates = monthly_dates(Date::new(2020, 1).unwrap(), 7);
+    let up_down = [1.0, 1.1, 0.99, 1.089, 0.9801, 1.07811, 0.970299];
+    let down_up = [1.0, 0.9, 0.99, 0.891, 0.9801, 0.88209, 0.970299];
+    let mut charts = Charts::default();
+    let payments = MonthlyPayments::from_constant(0.0);
+    let monthly = RebalanceTrigger {
+        interval: Some(1),
+        deviation: None,
+    };
+    for (name, values) in [("up", up_down), ("down", down_up)] {
+        assert!(charts.weight_grid(1.0, &payments, monthly).is_err());
+        charts.persist(Chart::new(name.to_string(), dates.clone(), values.to_vec()));
+    }
+    let grid = charts.weight_grid(1.0, &payments, monthly).unwrap();
+    assert_eq!(grid.mixes.len(), WEIGHT_GRID_STEPS + 1);
+    assert_eq!(grid.picks.len(), 4);
+    let pure = &grid.mixes[0];
+    assert_eq!(pure.fractions, vec![1.0, 0.0]);
+    assert!((pure.rebalanced.volatility - pure.buy_and_hold.volatility).abs() < 1e-10);
+    let current = &grid.current;
+    assert_eq!(current.fractions, vec![0.5, 0.5]);
+    // monthly rebalancing of opposite moves keeps the portfolio constant
+    assert!(current.rebalanced.volatility < 1e-10);
+    assert!(current.buy_and_hold.volatility > 0.1);
+    let (_, d_volatility) = grid.mean_shift(RiskMeasure::Volatility);
+    assert!(d_volatility < 0.0);
+}
+
+#[test]
+fn test_return_matrices() {
+    let dates = monthly_dates(Date::new(2020, 1).unwrap(), 5);
+    let mut charts = Charts::default();
+    for (name, values) in [
+        ("up", [1.0, 1.1, 0.99, 1.089, 0.9801]),
+        ("down", [1.0, 0.9, 0.99, 0.891, 0.9801]),
+        ("double", [1.0, 1.2, 0.96, 1.152, 0.9216]),
+    ] {
+        charts.persist(Chart::new(name.to_string(), dates.clone(), values.to_vec()));
+    }
+    let matrices = charts.return_matrices().unwrap();
+    assert_eq!(matrices.n_months, 4);
+    assert!((matrices.correlation[0][1] + 1.0).abs() < 1e-10);
+    assert!((matrices.correlation[0][2] - 1.0).abs() < 1e-10);
+    // returns of ±10% and ±20% in percent
+    assert!((matrices.covariance[0][2] - 800.0 / 3.0).abs() < 1e-8);
+    assert!(matrices
+        .to_csv(MatrixKind::Correlation)
+        .starts_with(",up,down,double\nup,1,"));
+
+    let (rolling_dates, correlations) = charts.rolling_correlation(0, 1, 3).unwrap();
+    assert_eq!(rolling_dates, dates[3..].to_vec());
+    assert!(correlations.iter().all(|c| (c + 1.0).abs() < 1e-10));
+    assert!(charts.rolling_correlation(0, 1, 5).is_err());
+    assert!(charts.rolling_correlation(0, 3, 3).is_err());
+}
+
+#[test]
+fn test_rolling_windows() {
+    let dates = monthly_dates(Date::new(2019, 11).unwrap(), 25);
+    let values = (0..25).map(|i| 1.01f64.powi(i)).collect::<Vec<_>>();
+    let mut charts = Charts::default();
+    charts.persist(Chart::new("growth".to_string(), dates.clone(), values));
+    let payments = MonthlyPayments::from_constant(10.0);
+    let trigger = RebalanceTrigger::default();
+    let rolling = charts
+        .rolling_windows(100.0, &payments, trigger, 12)
+        .unwrap();
+    assert_eq!(rolling.windows.len(), 13);
+    assert_eq!(rolling.windows[1].start, Date::new(2019, 12).unwrap());
+    assert_eq!(rolling.windows[12].start, dates[12]);
+    assert_eq!(rolling.windows[3].balances.len(), 13);
+    assert_eq!(rolling.windows[3].payments[12], 220.0);
+    for window in &rolling.windows {
+        assert!((window.irr - 100.0 * (1.01f64.powi(12) - 1.0)).abs() < 1e-6);
+        assert!((window.final_balance - rolling.windows[0].final_balance).abs() < 1e-8);
+    }
+    assert!(charts
+        .rolling_windows(100.0, &payments, trigger, 25)
+        .is_err());
+    assert!(charts
+        .rolling_windows(100.0, &payments, trigger, 0)
+        .is_err());
 }
@@ -0,0 +1,274 @@
+use crate::{blcerr, core_types::BlcResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Display;
+
+#[cfg(target_arch = "wasm32")]
+use std::collections::HashMap;
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    crate::core_types::to_blc,
+    std::{fs, path::PathBuf},
+};
+
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "dataset_cache";
+
+pub fn sha256_hex(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_now_secs() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn unix_now_secs() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM UTC`
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let (hour, minute) = ((secs % 86400) / 3600, (secs % 3600) / 60);
+    // civil from days, see https://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02} UTC")
+}
+
+/// Identifies the downloaded content a chart was created from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DatasetVersion {
+    pub location: String,
+    pub sha256: String,
+    /// seconds since the Unix epoch
+    pub downloaded: u64,
+}
+impl DatasetVersion {
+    pub fn new(location: &str, content: &str) -> Self {
+        DatasetVersion {
+            location: location.to_string(),
+            sha256: sha256_hex(content),
+            downloaded: unix_now_secs(),
+        }
+    }
+}
+impl Display for DatasetVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {}, sha256 {}",
+            self.location,
+            format_timestamp(self.downloaded),
+            &self.sha256[..self.sha256.len().min(12)]
+        )
+    }
+}
+
+/// Downloaded datasets for offline use. Natively, the datasets are stored in a directory, on the
+/// web in the eframe storage.
+#[derive(Default)]
+pub struct DatasetCache {
+    index: Vec<DatasetVersion>,
+    #[cfg(target_arch = "wasm32")]
+    contents: HashMap<String, String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    dir: Option<PathBuf>,
+}
+impl DatasetCache {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(_storage: Option<&dyn eframe::Storage>) -> Self {
+        let dir = eframe::storage_dir("Balance").map(|d| d.join("datasets"));
+        Self::from_dir(dir)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_dir(dir: Option<PathBuf>) -> Self {
+        let index = dir
+            .as_ref()
+            .and_then(|d| fs::read_to_string(d.join("index.json")).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        DatasetCache { index, dir }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        let stored: Option<(Vec<DatasetVersion>, HashMap<String, String>)> =
+            storage.and_then(|s| eframe::get_value(s, STORAGE_KEY));
+        let (index, contents) = stored.unwrap_or_default();
+        DatasetCache { index, contents }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, STORAGE_KEY, &(&self.index, &self.contents));
+    }
+
+    /// Entries are written immediately on native
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, _storage: &mut dyn eframe::Storage) {}
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn entry_path(&self, location: &str) -> BlcResult<PathBuf> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| blcerr!("no directory for the dataset cache"))?;
+        Ok(dir.join(format!("{}.csv", &sha256_hex(location)[..16])))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_content(&self, location: &str) -> BlcResult<String> {
+        fs::read_to_string(self.entry_path(location)?).map_err(to_blc)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read_content(&self, location: &str) -> BlcResult<String> {
+        self.contents
+            .get(location)
+            .cloned()
+            .ok_or_else(|| blcerr!("{location} is not cached"))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_content(&mut self, location: &str, content: &str) -> BlcResult<()> {
+        let path = self.entry_path(location)?;
+        if let Some(dir) = &self.dir {
+            fs::create_dir_all(dir).map_err(to_blc)?;
+        }
+        fs::write(path, content).map_err(to_blc)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn write_content(&mut self, location: &str, content: &str) -> BlcResult<()> {
+        self.contents
+            .insert(location.to_string(), content.to_string());
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_index(&self) -> BlcResult<()> {
+        if let Some(dir) = &self.dir {
+            let json = serde_json::to_string_pretty(&self.index).map_err(to_blc)?;
+            fs::write(dir.join("index.json"), json).map_err(to_blc)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn write_index(&self) -> BlcResult<()> {
+        Ok(())
+    }
+
+    pub fn version(&self, location: &str) -> Option<&DatasetVersion> {
+        self.index.iter().find(|v| v.location == location)
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Cached content of `location` if its checksum is still valid. Corrupted entries are
+    /// removed.
+    pub fn get(&mut self, location: &str) -> BlcResult<Option<(DatasetVersion, String)>> {
+        let Some(version) = self.version(location).cloned() else {
+            return Ok(None);
+        };
+        let content = self.read_content(location);
+        match content {
+            Ok(content) if sha256_hex(&content) == version.sha256 => Ok(Some((version, content))),
+            _ => {
+                self.remove(location)?;
+                Err(blcerr!(
+                    "cached version of {location} is corrupted and has been removed"
+                ))
+            }
+        }
+    }
+
+    /// Adds or replaces the content of `location`
+    pub fn insert(&mut self, location: &str, content: &str) -> BlcResult<DatasetVersion> {
+        let version = DatasetVersion::new(location, content);
+        self.write_content(location, content)?;
+        self.index.retain(|v| v.location != location);
+        self.index.push(version.clone());
+        self.write_index()?;
+        Ok(version)
+    }
+
+    pub fn remove(&mut self, location: &str) -> BlcResult<()> {
+        self.index.retain(|v| v.location != location);
+        #[cfg(target_arch = "wasm32")]
+        self.contents.remove(location);
+        self.write_index()
+    }
+
+    pub fn clear(&mut self) -> BlcResult<()> {
+        for location in self
+            .index
+            .iter()
+            .map(|v| v.location.clone())
+            .collect::<Vec<_>>()
+        {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Ok(path) = self.entry_path(&location) {
+                let _ = fs::remove_file(path);
+            }
+            self.remove(&location)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+    assert_eq!(format_timestamp(951_827_696), "2000-02-29 12:34 UTC");
+    assert_eq!(format_timestamp(1_704_067_199), "2023-12-31 23:59 UTC");
+}
+
+#[test]
+fn test_dataset_cache() {
+    let dir = std::env::temp_dir().join(format!("balance-cache-test-{}", std::process::id()));
+    let mut cache = DatasetCache::from_dir(Some(dir.clone()));
+    let location = "https://example.com/world.csv";
+    assert!(cache.get(location).unwrap().is_none());
+    let version = cache.insert(location, "date,value\n2020/01,1.0\n").unwrap();
+    assert_eq!(version.sha256, sha256_hex("date,value\n2020/01,1.0\n"));
+
+    let mut reloaded = DatasetCache::from_dir(Some(dir.clone()));
+    assert_eq!(reloaded.len(), 1);
+    let (cached_version, content) = reloaded.get(location).unwrap().unwrap();
+    assert_eq!(cached_version, version);
+    assert!(content.ends_with("1.0\n"));
+
+    fs::write(reloaded.entry_path(location).unwrap(), "tampered").unwrap();
+    assert!(reloaded.get(location).is_err());
+    assert_eq!(reloaded.len(), 0);
+
+    cache.insert(location, "date,value\n").unwrap();
+    cache.clear().unwrap();
+    assert!(DatasetCache::from_dir(Some(dir.clone()))
+        .get(location)
+        .unwrap()
+        .is_none());
+    let _ = fs::remove_dir_all(dir);
+}
@@ -0,0 +1,275 @@
+use crate::{
+    blcerr,
+    core_types::{to_blc, BlcResult},
+};
+use egui::Context;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+#[cfg(target_arch = "wasm32")]
+use {
+    wasm_bindgen::{prelude::*, JsCast},
+    wasm_bindgen_futures::JsFuture,
+    web_sys::HtmlInputElement,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// File name and content
+pub type LoadedFile = (String, BlcResult<String>);
+
+const EXTENSIONS: [&str; 3] = ["csv", "tsv", "txt"];
+
+fn bytes_to_string(bytes: &[u8]) -> BlcResult<String> {
+    String::from_utf8(bytes.to_vec()).map_err(to_blc)
+}
+
+/// Reads local files that are picked or dropped onto the window. On the web the browser file API
+/// is used, natively a simple file dialog.
+pub struct FileImport {
+    tx: Sender<LoadedFile>,
+    rx: Receiver<LoadedFile>,
+    #[cfg(not(target_arch = "wasm32"))]
+    dialog: Option<FileDialog>,
+}
+impl FileImport {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            tx,
+            rx,
+            #[cfg(not(target_arch = "wasm32"))]
+            dialog: None,
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn open(&mut self, ctx: &Context) -> BlcResult<()> {
+        open_file_picker(self.tx.clone(), ctx.clone()).map_err(to_blc)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(&mut self, _ctx: &Context) -> BlcResult<()> {
+        self.dialog = Some(FileDialog::new()?);
+        Ok(())
+    }
+
+    /// Shows the file dialog if it is open
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn show(&mut self, ctx: &Context) {
+        if let Some(dialog) = &mut self.dialog {
+            match dialog.show(ctx) {
+                DialogResult::Open(path) => {
+                    let _ = self.tx.send(read_file(path));
+                    self.dialog = None;
+                }
+                DialogResult::Cancel => {
+                    self.dialog = None;
+                }
+                DialogResult::None => (),
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn show(&mut self, _ctx: &Context) {}
+
+    /// Reads files that have been dropped onto the window
+    pub fn check_dropped(&self, ctx: &Context) {
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        for file in dropped_files {
+            let loaded = if let Some(bytes) = &file.bytes {
+                (file.name.clone(), bytes_to_string(bytes))
+            } else {
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(path) = file.path {
+                    read_file(path)
+                } else {
+                    (file.name, Err(blcerr!("dropped file without content")))
+                }
+                #[cfg(target_arch = "wasm32")]
+                (file.name, Err(blcerr!("dropped file without content")))
+            };
+            let _ = self.tx.send(loaded);
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<LoadedFile> {
+        self.rx.try_recv().ok()
+    }
+}
+impl Default for FileImport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// File name without extension
+pub fn file_stem(name: &str) -> &str {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => name,
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn open_file_picker(tx: Sender<LoadedFile>, ctx: Context) -> Result<(), JsValue> {
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| JsValue::from_str("no document"))?;
+    let input = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    input.set_type("file");
+    input.set_accept(
+        &EXTENSIONS
+            .iter()
+            .map(|ext| format!(".{ext}"))
+            .collect::<Vec<_>>()
+            .join(","),
+    );
+    let input_cloned = input.clone();
+    let onchange = Closure::once(move || {
+        if let Some(file) = input_cloned.files().and_then(|files| files.get(0)) {
+            let name = file.name();
+            wasm_bindgen_futures::spawn_local(async move {
+                let content = JsFuture::from(file.text())
+                    .await
+                    .map_err(to_blc)
+                    .and_then(|text| {
+                        text.as_string()
+                            .ok_or_else(|| blcerr!("content of {name} is not a text"))
+                    });
+                let _ = tx.send((name, content));
+                ctx.request_repaint();
+            });
+        }
+    });
+    input.set_onchange(Some(onchange.as_ref().unchecked_ref()));
+    onchange.forget();
+    input.click();
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_file(path: PathBuf) -> LoadedFile {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let content = fs::read(&path)
+        .map_err(|e| blcerr!("could not read {}, {e}", path.display()))
+        .and_then(|bytes| bytes_to_string(&bytes));
+    (name, content)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum DialogResult {
+    None,
+    Open(PathBuf),
+    Cancel,
+}
+
+/// Sub-directories and files with supported extensions
+#[cfg(not(target_arch = "wasm32"))]
+fn list_dir(dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut entries = fs::read_dir(dir)
+        .map(|rd| rd.flatten().map(|e| e.path()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    entries.sort();
+    let is_supported = |p: &PathBuf| {
+        p.extension()
+            .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+    };
+    let (dirs, files): (Vec<_>, Vec<_>) = entries.into_iter().partition(|p| p.is_dir());
+    (dirs, files.into_iter().filter(is_supported).collect())
+}
+
+/// Minimal file dialog that lists directories and files with supported extensions
+#[cfg(not(target_arch = "wasm32"))]
+struct FileDialog {
+    dir: PathBuf,
+    path: String,
+    /// listing of `dir` that is read once per directory instead of every frame
+    entries: (Vec<PathBuf>, Vec<PathBuf>),
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl FileDialog {
+    fn new() -> BlcResult<Self> {
+        let dir = std::env::current_dir().map_err(to_blc)?;
+        Ok(Self {
+            path: dir.display().to_string(),
+            entries: list_dir(&dir),
+            dir,
+        })
+    }
+
+    fn change_dir(&mut self, dir: PathBuf) {
+        self.path = dir.display().to_string();
+        self.entries = list_dir(&dir);
+        self.dir = dir;
+    }
+
+    fn show(&mut self, ctx: &Context) -> DialogResult {
+        let mut result = DialogResult::None;
+        let mut new_dir = None;
+        egui::Window::new("Open CSV or TSV file")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("up").clicked() {
+                        new_dir = self.dir.parent().map(|p| p.to_path_buf());
+                    }
+                    if ui.button("refresh").clicked() {
+                        new_dir = Some(self.dir.clone());
+                    }
+                    ui.label(self.dir.display().to_string());
+                });
+                ui.separator();
+                let (dirs, files) = &self.entries;
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for dir in dirs {
+                            let name = dir.file_name().unwrap_or_default().to_string_lossy();
+                            if ui.button(format!("{name}/")).clicked() {
+                                new_dir = Some(dir.clone());
+                            }
+                        }
+                        for file in files {
+                            let name = file.file_name().unwrap_or_default().to_string_lossy();
+                            if ui.button(name.to_string()).clicked() {
+                                self.path = file.display().to_string();
+                            }
+                        }
+                    });
+                ui.separator();
+                ui.text_edit_singleline(&mut self.path);
+                ui.horizontal(|ui| {
+                    if ui.button("Open").clicked() {
+                        result = DialogResult::Open(PathBuf::from(&self.path));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        result = DialogResult::Cancel;
+                    }
+                });
+            });
+        if let Some(dir) = new_dir {
+            self.change_dir(dir);
+        }
+        result
+    }
+}
+
+#[test]
+fn test_file_stem() {
+    assert_eq!(file_stem("msciworld.csv"), "msciworld");
+    assert_eq!(file_stem("/data/prices.2020.tsv"), "prices.2020");
+    assert_eq!(file_stem("C:\\data\\prices.csv"), "prices");
+    assert_eq!(file_stem("prices"), "prices");
+    assert_eq!(file_stem(".csv"), ".csv");
+}
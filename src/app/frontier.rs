@@ -0,0 +1,244 @@
+use crate::{compute::RiskReturn, optimize::Objective};
+use egui::{Color32, Ui};
+use egui_plot::{Legend, Line, LineStyle, MarkerShape, Plot, Points};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Number of steps between 0 and 1 of the fraction of each asset in the weight grid
+pub const WEIGHT_GRID_STEPS: usize = 20;
+
+const REBALANCED_COLOR: Color32 = Color32::from_rgb(70, 130, 220);
+const BUY_AND_HOLD_COLOR: Color32 = Color32::from_rgb(230, 140, 40);
+
+/// Risk on the x-axis of the frontier plot
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RiskMeasure {
+    #[default]
+    Volatility,
+    MaxDrawdown,
+}
+impl RiskMeasure {
+    fn of(self, risk_return: &RiskReturn) -> f64 {
+        match self {
+            RiskMeasure::Volatility => risk_return.volatility,
+            RiskMeasure::MaxDrawdown => risk_return.max_drawdown,
+        }
+    }
+}
+impl Display for RiskMeasure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RiskMeasure::Volatility => "volatility",
+            RiskMeasure::MaxDrawdown => "maximum drawdown",
+        })
+    }
+}
+
+/// All fractions of `n_assets` that are multiples of `1 / n_steps` and sum up to 1
+pub fn weight_grid_fractions(n_assets: usize, n_steps: usize) -> Vec<Vec<f64>> {
+    fn split(n_assets: usize, remaining: usize) -> Vec<Vec<usize>> {
+        if n_assets == 1 {
+            vec![vec![remaining]]
+        } else {
+            (0..=remaining)
+                .rev()
+                .flat_map(|first| {
+                    split(n_assets - 1, remaining - first)
+                        .into_iter()
+                        .map(move |mut rest| {
+                            rest.insert(0, first);
+                            rest
+                        })
+                })
+                .collect()
+        }
+    }
+    split(n_assets, n_steps)
+        .into_iter()
+        .map(|steps| {
+            steps
+                .into_iter()
+                .map(|s| s as f64 / n_steps as f64)
+                .collect()
+        })
+        .collect()
+}
+
+/// Points that have a higher return than all points with less risk, sorted by risk
+fn upper_envelope(mut points: Vec<[f64; 2]>) -> Vec<[f64; 2]> {
+    points.retain(|[x, y]| x.is_finite() && y.is_finite());
+    points.sort_by(|[x1, y1], [x2, y2]| x1.total_cmp(x2).then(y2.total_cmp(y1)));
+    let mut best = f64::NEG_INFINITY;
+    points
+        .into_iter()
+        .filter(|[_, y]| {
+            let is_better = *y > best;
+            best = best.max(*y);
+            is_better
+        })
+        .collect()
+}
+
+/// Portfolio with given fractions, once with the rebalancing trigger and once without
+#[derive(Clone, Debug)]
+pub struct Mix {
+    pub fractions: Vec<f64>,
+    pub rebalanced: RiskReturn,
+    pub buy_and_hold: RiskReturn,
+}
+
+/// Risks and returns of mixes of the price developments
+#[derive(Clone, Debug)]
+pub struct WeightGrid {
+    pub names: Vec<String>,
+    pub mixes: Vec<Mix>,
+    pub current: Mix,
+    /// proposals of the optimizer, objectives that fail are skipped
+    pub picks: Vec<(Objective, Mix)>,
+}
+impl WeightGrid {
+    /// Mean difference of return and risk between rebalancing and buy-and-hold across the
+    /// grid in percentage points
+    pub fn mean_shift(&self, risk: RiskMeasure) -> (f64, f64) {
+        let n = self.mixes.len() as f64;
+        let (d_return, d_risk) = self.mixes.iter().fold((0.0, 0.0), |(dy, dx), m| {
+            (
+                dy + m.rebalanced.yearly_return - m.buy_and_hold.yearly_return,
+                dx + risk.of(&m.rebalanced) - risk.of(&m.buy_and_hold),
+            )
+        });
+        (d_return / n, d_risk / n)
+    }
+
+    fn format_fractions(&self, fractions: &[f64]) -> String {
+        self.names
+            .iter()
+            .zip(fractions)
+            .map(|(name, f)| format!("{name} {:0.0}%", f * 100.0))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Plots the annualized return against the risk of all mixes and lists the highlighted mixes
+    pub fn show(&self, ui: &mut Ui, risk: RiskMeasure) {
+        let points = |rr: fn(&Mix) -> &RiskReturn| {
+            self.mixes
+                .iter()
+                .map(|m| [risk.of(rr(m)), rr(m).yearly_return])
+                .collect::<Vec<_>>()
+        };
+        let rebalanced = points(|m| &m.rebalanced);
+        let buy_and_hold = points(|m| &m.buy_and_hold);
+        let to_point = |rr: &RiskReturn| [risk.of(rr), rr.yearly_return];
+        let pick_shapes = [
+            MarkerShape::Square,
+            MarkerShape::Up,
+            MarkerShape::Cross,
+            MarkerShape::Asterisk,
+        ];
+        Plot::new("risk vs return")
+            .legend(Legend::default())
+            .height(ui.available_height().clamp(200.0, 400.0))
+            .x_axis_label(format!("{risk} [%]"))
+            .y_axis_label("yearly return [%]")
+            .show(ui, |plot_ui| {
+                plot_ui.line(
+                    Line::new(
+                        "buy-and-hold frontier",
+                        upper_envelope(buy_and_hold.clone()),
+                    )
+                    .color(BUY_AND_HOLD_COLOR)
+                    .style(LineStyle::dashed_loose()),
+                );
+                plot_ui.line(
+                    Line::new("rebalanced frontier", upper_envelope(rebalanced.clone()))
+                        .color(REBALANCED_COLOR),
+                );
+                plot_ui.points(
+                    Points::new("buy-and-hold mixes", buy_and_hold)
+                        .color(BUY_AND_HOLD_COLOR)
+                        .radius(2.0),
+                );
+                plot_ui.points(
+                    Points::new("rebalanced mixes", rebalanced)
+                        .color(REBALANCED_COLOR)
+                        .radius(2.0),
+                );
+                plot_ui.points(
+                    Points::new(
+                        "current mix, buy-and-hold",
+                        vec![to_point(&self.current.buy_and_hold)],
+                    )
+                    .shape(MarkerShape::Diamond)
+                    .filled(false)
+                    .radius(6.0),
+                );
+                plot_ui.points(
+                    Points::new("current mix", vec![to_point(&self.current.rebalanced)])
+                        .shape(MarkerShape::Diamond)
+                        .radius(6.0),
+                );
+                for ((objective, mix), shape) in self.picks.iter().zip(pick_shapes) {
+                    plot_ui.points(
+                        Points::new(objective.to_string(), vec![to_point(&mix.rebalanced)])
+                            .shape(shape)
+                            .radius(5.0),
+                    );
+                }
+            });
+        let (d_return, d_risk) = self.mean_shift(risk);
+        ui.label(format!(
+            "On average across the mixes, the rebalancing trigger changes the yearly return by \
+             {d_return:+0.2} and the {risk} by {d_risk:+0.2} percentage points compared with \
+             buy-and-hold. Returns are time-weighted, i.e., independent of the payments."
+        ));
+        egui::Grid::new("grid-frontier-mixes").show(ui, |ui| {
+            ui.label("mix");
+            ui.label("fractions");
+            ui.label("yearly return [%]");
+            ui.label("volatility [%]");
+            ui.label("max drawdown [%]");
+            ui.end_row();
+            for (name, mix) in std::iter::once(("current".to_string(), &self.current)).chain(
+                self.picks
+                    .iter()
+                    .map(|(objective, mix)| (objective.to_string(), mix)),
+            ) {
+                ui.label(name);
+                ui.label(self.format_fractions(&mix.fractions));
+                let RiskReturn {
+                    yearly_return,
+                    volatility,
+                    max_drawdown,
+                } = mix.rebalanced;
+                ui.label(format!("{yearly_return:0.2}"));
+                ui.label(format!("{volatility:0.2}"));
+                ui.label(format!("{max_drawdown:0.2}"));
+                ui.end_row();
+            }
+        });
+    }
+}
+
+#[test]
+fn test_weight_grid_fractions() {
+    let fractions = weight_grid_fractions(2, 4);
+    assert_eq!(fractions.len(), 5);
+    assert_eq!(fractions[0], vec![1.0, 0.0]);
+    assert_eq!(fractions[3], vec![0.25, 0.75]);
+    let fractions = weight_grid_fractions(3, WEIGHT_GRID_STEPS);
+    assert_eq!(fractions.len(), 231);
+    for f in &fractions {
+        assert!((f.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    let envelope = upper_envelope(vec![
+        [2.0, 3.0],
+        [1.0, 1.0],
+        [3.0, 2.0],
+        [1.0, 2.0],
+        [4.0, 5.0],
+        [f64::NAN, 9.0],
+    ]);
+    assert_eq!(envelope, vec![[1.0, 2.0], [2.0, 3.0], [4.0, 5.0]]);
+}
@@ -1,24 +1,36 @@
 use crate::blcerr;
+use crate::catalog::{is_remote, Catalog};
 use crate::compute::{
-    random_walk, yearly_return, BestRebalanceTrigger, RebalanceStats, RebalanceStatsSummary,
-    RebalanceTrigger,
+    random_walk_from_seed, solve_for_success_probability, unix_to_now_nanos, yearly_return,
+    BestRebalanceTrigger, GoalVariable, RebalanceStats, RebalanceStatsSummary, RebalanceTrigger,
+    ReturnKind, PAYMENT_VARIABLES,
 };
 use crate::container_util::remove_indices;
 use crate::core_types::{to_blc, BlcResult};
 use crate::date::date_after_nmonths;
 use crate::io::{
-    read_csv_from_str, sessionid_from_link, sessionid_to_link, ResponsePayload, URL_READ_SHARELINK,
+    import_csv, series_to_csv, sessionid_from_link, sessionid_to_link, CsvImportOptions, Delimiter,
+    GapRepair, Header, ImportedSeries, Resampling, ResponsePayload, ValueKind, URL_READ_SHARELINK,
     URL_WRITE_SHARELINK,
 };
-use charts::{Chart, Charts, TmpChart};
-use egui::{Context, OutputCommand, Response, RichText, Ui};
+use charts::{expression_identifier, Chart, Charts, MatrixKind, Origin, SpliceProxy, TmpChart};
+use dataset_cache::{format_timestamp, DatasetCache, DatasetVersion};
+use egui::{Color32, Context, OutputCommand, Response, RichText, Ui};
+use egui_plot::{GridMark, HLine, Line, Plot};
+use file_import::{file_stem, FileImport};
+use frontier::{RiskMeasure, WeightGrid};
 use month_slider::{MonthSlider, MonthSliderPair, SliderState};
+use rolling::{Outcome, RollingView, RollingWindows};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::iter;
 use std::mem;
 mod charts;
+mod dataset_cache;
+mod file_import;
+mod frontier;
 mod month_slider;
+mod rolling;
 mod ui_mut_itemlist;
 mod ui_state_types;
 
@@ -29,8 +41,10 @@ use {
 };
 
 use self::ui_state_types::{
-    FinalBalance, ParsedSimInput, PaymentData, RestMethod, RestRequest, RestRequestState, SimInput,
-    VolaAmount,
+    CashInput, CorrelationInput, CsvImportInput, DerivedChartInput, FinalBalance, GoalSeekInput,
+    LeverageInput, ParsedCashInput, ParsedGoalSeek, ParsedSimInput, PaymentData, RateOverride,
+    RestMethod, RestRequest, RestRequestState, ReturnsExportInput, RollingInput, SimInput,
+    SpliceInput, VolaAmount,
 };
 
 #[cfg(target_arch = "wasm32")]
@@ -40,8 +54,7 @@ use {
     web_sys::{window, Blob, HtmlElement, Url},
 };
 
-// const BASE_URL_WWW: &str = "http://localhost:8000/data";
-const BASE_URL_WWW: &str = "https://www.bertiqwerty.com/data";
+const N_MONTE_CARLO_PATHS: usize = 200;
 
 #[cfg(target_arch = "wasm32")]
 fn download_str(s: &str, tmp_filename: &str) -> Result<(), JsValue> {
@@ -68,24 +81,159 @@ macro_rules! recompute {
         $self.best_rebalance_trigger = None;
         $self.recompute_balance();
         $self.recompute_rebalance_stats(false);
+        $self.outdate_analyses();
     };
 }
 
-fn export_csv(charts: &Charts) -> BlcResult<()> {
-    let tmp_filename = "charts.csv";
+fn gap_repair_ui(ui: &mut Ui, gap_repair: &mut GapRepair, id: &str) {
+    ui.label("Missing months");
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(gap_repair.to_string())
+        .show_ui(ui, |ui| {
+            for repair in [
+                GapRepair::Reject,
+                GapRepair::ForwardFill,
+                GapRepair::LinearInterpolation,
+                GapRepair::LogInterpolation,
+                GapRepair::LongestGapFreeRange,
+            ] {
+                ui.selectable_value(gap_repair, repair, repair.to_string());
+            }
+        });
+}
+
+fn csv_import_options_ui(ui: &mut Ui, options: &mut CsvImportOptions) {
+    ui.horizontal(|ui| {
+        ui.label("Delimiter");
+        egui::ComboBox::from_id_salt("csv-delimiter")
+            .selected_text(options.delimiter.to_string())
+            .show_ui(ui, |ui| {
+                for delimiter in [
+                    Delimiter::Detect,
+                    Delimiter::Comma,
+                    Delimiter::Semicolon,
+                    Delimiter::Tab,
+                ] {
+                    ui.selectable_value(&mut options.delimiter, delimiter, delimiter.to_string());
+                }
+            });
+        ui.label("Header");
+        egui::ComboBox::from_id_salt("csv-header")
+            .selected_text(options.header.to_string())
+            .show_ui(ui, |ui| {
+                for header in [Header::Detect, Header::Yes, Header::No] {
+                    ui.selectable_value(&mut options.header, header, header.to_string());
+                }
+            });
+        ui.checkbox(&mut options.decimal_comma, "Decimal comma");
+    });
+    ui.horizontal(|ui| {
+        gap_repair_ui(ui, &mut options.gap_repair, "gap-repair-import");
+        ui.label("Daily or weekly data");
+        egui::ComboBox::from_id_salt("csv-resampling")
+            .selected_text(options.resampling.to_string())
+            .show_ui(ui, |ui| {
+                for resampling in [Resampling::MonthEnd, Resampling::MonthlyAverage] {
+                    ui.selectable_value(
+                        &mut options.resampling,
+                        resampling,
+                        resampling.to_string(),
+                    );
+                }
+            });
+    });
+    ui.horizontal(|ui| {
+        ui.label("Values are");
+        egui::ComboBox::from_id_salt("csv-value-kind")
+            .selected_text(options.value_kind.to_string())
+            .show_ui(ui, |ui| {
+                for value_kind in [
+                    ValueKind::Levels,
+                    ValueKind::SimpleReturns,
+                    ValueKind::LogReturns,
+                ] {
+                    ui.selectable_value(
+                        &mut options.value_kind,
+                        value_kind,
+                        value_kind.to_string(),
+                    );
+                }
+            });
+        if options.value_kind != ValueKind::Levels {
+            ui.label("Start level");
+            ui.add(egui::DragValue::new(&mut options.base_level).range(1e-6..=f64::MAX));
+        }
+    });
+}
+
+fn returns_export_ui(ui: &mut Ui, export: &mut ReturnsExportInput, charts: &Charts) {
+    let chart_name = |idx: Option<usize>| match idx {
+        Some(idx) => charts
+            .persisted
+            .get(idx)
+            .map(|c| c.name().to_string())
+            .unwrap_or_default(),
+        None => "portfolio".to_string(),
+    };
+    let kind_name = |kind: ReturnKind| match kind {
+        ReturnKind::Simple => "returns in %",
+        ReturnKind::Log => "log returns",
+    };
+    if export
+        .chart
+        .is_some_and(|idx| idx >= charts.persisted.len())
+    {
+        export.chart = None;
+    }
+    egui::ComboBox::from_id_salt("returns-export-chart")
+        .selected_text(chart_name(export.chart))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut export.chart, None, chart_name(None));
+            for idx in 0..charts.persisted.len() {
+                ui.selectable_value(&mut export.chart, Some(idx), chart_name(Some(idx)));
+            }
+        });
+    egui::ComboBox::from_id_salt("returns-export-kind")
+        .selected_text(kind_name(export.kind))
+        .show_ui(ui, |ui| {
+            for kind in [ReturnKind::Simple, ReturnKind::Log] {
+                ui.selectable_value(&mut export.kind, kind, kind_name(kind));
+            }
+        });
+}
+
+/// In the browser everything is fetched, relative URLs refer to the server of the app
+fn is_fetched(location: &str) -> bool {
+    cfg!(target_arch = "wasm32") || is_remote(location)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_local(path: &str) -> BlcResult<String> {
+    std::fs::read_to_string(path).map_err(|e| blcerr!("could not read {path}, {e}"))
+}
 
-    let s = charts.to_string();
+#[cfg(target_arch = "wasm32")]
+fn read_local(path: &str) -> BlcResult<String> {
+    Err(blcerr!(
+        "local files like {path} cannot be read in the browser"
+    ))
+}
 
+fn export_str(s: &str, filename: &str) -> BlcResult<()> {
     #[cfg(target_arch = "wasm32")]
-    download_str(&s, tmp_filename).map_err(to_blc)?;
+    download_str(s, filename).map_err(to_blc)?;
     #[cfg(not(target_arch = "wasm32"))]
     {
-        let mut tmp_file = File::create(tmp_filename).map_err(to_blc).unwrap();
+        let mut tmp_file = File::create(filename).map_err(to_blc)?;
         write!(tmp_file, "{s}").map_err(to_blc)?;
     }
     Ok(())
 }
 
+fn export_csv(charts: &Charts) -> BlcResult<()> {
+    export_str(&charts.to_string(), "charts.csv")
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
@@ -93,6 +241,48 @@ extern "C" {
     fn log(s: &str);
 }
 
+/// Red for positive and blue for negative values, the more opaque the closer to `max_abs`
+fn heat_color(x: f64, max_abs: f64) -> Color32 {
+    let alpha = ((x.abs() / max_abs).clamp(0.0, 1.0) * 180.0) as u8;
+    if x >= 0.0 {
+        Color32::from_rgba_unmultiplied(220, 60, 60, alpha)
+    } else {
+        Color32::from_rgba_unmultiplied(60, 100, 220, alpha)
+    }
+}
+
+fn heatmap_ui(ui: &mut Ui, names: &[String], matrix: &[Vec<f64>]) {
+    let max_abs = matrix
+        .iter()
+        .flatten()
+        .map(|x| x.abs())
+        .filter(|x| x.is_finite())
+        .fold(0.0, f64::max);
+    egui::Grid::new("grid-heatmap").show(ui, |ui| {
+        ui.label("");
+        for name in names {
+            ui.label(name);
+        }
+        ui.end_row();
+        for (name, row) in names.iter().zip(matrix) {
+            ui.label(name);
+            for x in row {
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(64.0, 22.0), egui::Sense::hover());
+                ui.painter().rect_filled(rect, 2.0, heat_color(*x, max_abs));
+                ui.painter().text(
+                    rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    format!("{x:0.2}"),
+                    egui::TextStyle::Body.resolve(ui.style()),
+                    ui.visuals().text_color(),
+                );
+            }
+            ui.end_row();
+        }
+    });
+}
+
 fn heading2(ui: &mut Ui, s: &str) -> Response {
     ui.heading(RichText::new(s).strong().size(18.0))
 }
@@ -144,6 +334,8 @@ pub struct BalanceApp<'a> {
     #[serde(skip)]
     load_request: RestRequest<'a>,
     #[serde(skip)]
+    catalog_request: RestRequest<'a>,
+    #[serde(skip)]
     session_id_to_be_loaded: String,
     status_msg: Option<String>,
     sim: SimInput,
@@ -152,7 +344,52 @@ pub struct BalanceApp<'a> {
     rebalance_stats: Option<BlcResult<RebalanceStats>>,
     rebalance_stats_summary: Option<BlcResult<RebalanceStatsSummary>>,
     best_rebalance_trigger: Option<BestRebalanceTrigger>,
+    /// risk and return of mixes of the price developments
+    #[serde(skip)]
+    frontier: Option<BlcResult<WeightGrid>>,
+    frontier_risk: RiskMeasure,
+    /// outcomes of the plan for every start month
+    #[serde(skip)]
+    rolling_windows: Option<BlcResult<RollingWindows>>,
+    /// analyses that have been hidden and are shown again without recomputation unless the
+    /// inputs have changed in between
+    #[serde(skip)]
+    hidden_analyses: (
+        Option<BlcResult<WeightGrid>>,
+        Option<BlcResult<RollingWindows>>,
+    ),
+    /// the shown analysis is recomputed on request since it needs many simulations
+    #[serde(skip)]
+    analyses_outdated: bool,
+    rolling: RollingInput,
     final_balance: Option<FinalBalance>,
+    goal_seek: GoalSeekInput,
+    goal_seek_result: Option<(GoalVariable, f64)>,
+    cash: CashInput,
+    fx_currency: String,
+    dividend_chart_idx: usize,
+    csv_import: CsvImportInput,
+    returns_export: ReturnsExportInput,
+    splice: SpliceInput,
+    derived_chart: DerivedChartInput,
+    leverage: LeverageInput,
+    correlation: CorrelationInput,
+    /// imported series of multi-column imports that are added or skipped one by one
+    #[serde(skip)]
+    pending_imports: Vec<Chart>,
+    #[serde(skip)]
+    file_import: FileImport,
+    /// URL or local path of a dataset catalog, the builtin catalog is used if empty. Local to this
+    /// device, loaded sessions and resets keep it.
+    catalog_location: String,
+    /// loaded from `catalog_location` at startup instead of being stored with the session
+    #[serde(skip)]
+    catalog: Catalog,
+    #[serde(skip)]
+    dataset_cache: DatasetCache,
+    /// location of the dataset that is currently downloaded
+    #[serde(skip)]
+    dataset_download_location: String,
 }
 
 impl BalanceApp<'_> {
@@ -160,23 +397,43 @@ impl BalanceApp<'_> {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        let app: Self = if let Some(storage) = cc.storage {
+        let mut app: Self = if let Some(storage) = cc.storage {
             eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
         } else {
             // This is also where you can customize the look and feel of egui using
             // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
             Self::default()
         };
+        app.dataset_cache = DatasetCache::load(cc.storage);
+        app.trigger_catalog_load(&cc.egui_ctx);
+        app.update_loaded_session();
         #[cfg(target_arch = "wasm32")]
-        {
-            let mut app = app;
-            app.get_session_fromurl();
-            app
-        }
-        #[cfg(not(target_arch = "wasm32"))]
+        app.get_session_fromurl();
         app
     }
 
+    /// Parses the inputs of a stored session, e.g., to migrate payment expressions of earlier
+    /// versions
+    fn update_loaded_session(&mut self) {
+        if let Err(e) = self.payment.parse() {
+            self.status_msg = Some(format!("{e}"));
+        }
+    }
+
+    /// Replaces the session, e.g., on reset or load, but keeps state that is local to this
+    /// device such as downloaded datasets and the catalog
+    fn replace_session(&mut self, session: Self) {
+        let dataset_cache = mem::take(&mut self.dataset_cache);
+        let file_import = mem::take(&mut self.file_import);
+        let catalog_location = mem::take(&mut self.catalog_location);
+        let catalog = mem::take(&mut self.catalog);
+        *self = session;
+        self.dataset_cache = dataset_cache;
+        self.file_import = file_import;
+        self.catalog_location = catalog_location;
+        self.catalog = catalog;
+    }
+
     #[cfg(target_arch = "wasm32")]
     fn get_session_fromurl(&mut self) {
         let link_with_sessionid = get_current_url();
@@ -191,25 +448,327 @@ impl BalanceApp<'_> {
         if let Some(status) = status {
             self.status_msg = Some(status);
         }
-        if let RestRequestState::Done((name, d)) = &self.download_historic_csv.state {
-            let tmp = match d {
-                Ok(resp) => {
-                    let (dates, values) = read_csv_from_str(resp.text().unwrap()).unwrap();
-                    self.charts.plot_balance = false;
-                    Some(TmpChart {
-                        chart: Chart::from_tuple(name.to_string(), (dates, values)),
-                        initial_balance: self.payment.initial_balance.1,
+        if let RestRequestState::Done(_) = &self.download_historic_csv.state {
+            if let RestRequestState::Done((name, d)) =
+                mem::take(&mut self.download_historic_csv.state)
+            {
+                let text = d
+                    .map_err(|e| blcerr!("{e}"))
+                    .and_then(|resp| {
+                        if resp.ok {
+                            Ok(resp)
+                        } else {
+                            Err(blcerr!("status {} {}", resp.status, resp.status_text))
+                        }
                     })
+                    .and_then(|resp| {
+                        resp.text()
+                            .map(|text| text.to_string())
+                            .ok_or_else(|| blcerr!("response from {} is not a text", resp.url))
+                    });
+                let location = mem::take(&mut self.dataset_download_location);
+                self.import_downloaded_dataset(&name, &location, text);
+            }
+        }
+    }
+    /// Caches successful downloads and falls back to the cache otherwise
+    fn import_downloaded_dataset(&mut self, name: &str, location: &str, text: BlcResult<String>) {
+        match text {
+            Ok(text) => {
+                let previous = self.dataset_cache.version(location).cloned();
+                let (version, cache_status) = match self.dataset_cache.insert(location, &text) {
+                    Ok(version) => (version, None),
+                    Err(e) => (
+                        DatasetVersion::new(location, &text),
+                        Some(format!("{name}: could not be cached, {e}")),
+                    ),
+                };
+                let changed_status = previous
+                    .filter(|previous| previous.sha256 != version.sha256)
+                    .map(|previous| {
+                        format!(
+                            "{name} has changed since the download at {}",
+                            format_timestamp(previous.downloaded)
+                        )
+                    });
+                self.import_dataset(name, Ok(text), Origin::Downloaded(version));
+                if self.status_msg.is_none() {
+                    self.status_msg = cache_status.or(changed_status);
                 }
-                Err(e) => {
-                    let status = e.to_string();
-                    self.status_msg = Some(status);
-                    self.charts.move_tmp()
+            }
+            Err(e) => match self.dataset_cache.get(location) {
+                Ok(Some((version, content))) => {
+                    let offline_status = format!(
+                        "{name}: download failed with {e}, using the version downloaded at {}",
+                        format_timestamp(version.downloaded)
+                    );
+                    self.import_dataset(name, Ok(content), Origin::Downloaded(version));
+                    if self.status_msg.is_none() {
+                        self.status_msg = Some(offline_status);
+                    }
+                }
+                _ => self.import_dataset(name, Err(e), Origin::Unknown),
+            },
+        }
+    }
+    fn import_dataset(&mut self, name: &str, text: BlcResult<String>, origin: Origin) {
+        // datasets of catalogs contain prices in the default format, only the handling of gaps is
+        // taken from the import options of the user
+        let options = CsvImportOptions {
+            gap_repair: self.csv_import.options.gap_repair,
+            ..CsvImportOptions::default()
+        };
+        let dataset = self.catalog.datasets.iter().find(|d| d.name == name);
+        let metadata = dataset.map(|d| (d.currency.clone(), d.index_type));
+        match text.and_then(|text| import_csv(&text, &options)) {
+            Ok(imported) => {
+                let mut charts = self.imported_charts(imported, Some(name), origin);
+                if let Some((currency, index_type)) = metadata {
+                    for chart in charts.iter_mut() {
+                        chart.currency = currency.clone();
+                        chart.index_type = index_type;
+                    }
+                }
+                self.add_imported_charts(charts);
+            }
+            Err(e) => {
+                self.status_msg = Some(format!("{name}: {e}"));
+            }
+        };
+    }
+    /// Uses the cached version of the dataset if there is one unless `refresh` is true
+    fn download_dataset(&mut self, idx: usize, refresh: bool, ctx: &Context) {
+        let Some(dataset) = self.catalog.datasets.get(idx) else {
+            return;
+        };
+        let location = self.catalog.resolve(dataset);
+        let name = dataset.name.clone();
+        self.charts.plot_balance = false;
+        self.rebalance_stats = None;
+        if is_fetched(&location) {
+            if !refresh {
+                match self.dataset_cache.get(&location) {
+                    Ok(Some((version, content))) => {
+                        self.import_dataset(&name, Ok(content), Origin::Downloaded(version));
+                        return;
+                    }
+                    Ok(None) => (),
+                    Err(e) => {
+                        self.status_msg = Some(format!("{e}"));
+                    }
                 }
+            }
+            self.dataset_download_location = location.clone();
+            self.download_historic_csv
+                .trigger(&location, name, RestMethod::Get, Some(ctx.clone()));
+        } else {
+            let origin = Origin::Imported {
+                filename: Some(location.clone()),
             };
-            self.charts.add_tmp(tmp);
-            self.download_historic_csv.state = RestRequestState::None;
-            self.status_msg = None;
+            self.import_dataset(&name, read_local(&location), origin);
+        }
+    }
+    /// Loads the catalog from the configured location or resets it to the builtin catalog
+    fn trigger_catalog_load(&mut self, ctx: &Context) {
+        let location = self.catalog_location.trim().to_string();
+        if location.is_empty() {
+            self.catalog = Catalog::default();
+        } else if is_fetched(&location) {
+            self.catalog_request.trigger(
+                &location,
+                location.clone(),
+                RestMethod::Get,
+                Some(ctx.clone()),
+            );
+        } else {
+            let catalog =
+                read_local(&location).and_then(|json| Catalog::from_json(&json, &location));
+            self.set_catalog(catalog);
+        }
+    }
+    fn check_catalog_download(&mut self) {
+        let (status, state) = self.catalog_request.check();
+        self.catalog_request.state = state;
+        if let Some(status) = status {
+            self.status_msg = Some(status);
+        }
+        if let RestRequestState::Done(_) = &self.catalog_request.state {
+            if let RestRequestState::Done((location, d)) =
+                mem::take(&mut self.catalog_request.state)
+            {
+                let catalog = d.map_err(|e| blcerr!("{e}")).and_then(|resp| {
+                    let json = resp
+                        .text()
+                        .ok_or_else(|| blcerr!("response from {} is not a text", resp.url))?;
+                    Catalog::from_json(json, &location)
+                });
+                self.set_catalog(catalog);
+            }
+        }
+    }
+    fn set_catalog(&mut self, catalog: BlcResult<Catalog>) {
+        match catalog {
+            Ok(catalog) => {
+                self.status_msg = None;
+                self.catalog = catalog;
+            }
+            Err(e) => {
+                self.status_msg = Some(format!("could not load catalog, {e}"));
+            }
+        }
+    }
+    fn add_imported(&mut self, imported: Vec<ImportedSeries>, name: Option<&str>, origin: Origin) {
+        let charts = self.imported_charts(imported, name, origin);
+        self.add_imported_charts(charts);
+    }
+    /// Charts of imported series, single series are named `name` if given
+    fn imported_charts(
+        &mut self,
+        imported: Vec<ImportedSeries>,
+        name: Option<&str>,
+        origin: Origin,
+    ) -> Vec<Chart> {
+        let gap_repair = self.csv_import.options.gap_repair;
+        let summaries = imported
+            .iter()
+            .filter(|series| !series.summary.is_empty())
+            .map(|series| {
+                format!(
+                    "{} repaired with {gap_repair}: {}",
+                    series.name, series.summary
+                )
+            })
+            .collect::<Vec<_>>();
+        self.status_msg = if summaries.is_empty() {
+            None
+        } else {
+            Some(summaries.join("\n"))
+        };
+        let n_series = imported.len();
+        imported
+            .into_iter()
+            .map(|series| {
+                let series_name = match name {
+                    Some(name) if n_series == 1 => name.to_string(),
+                    Some(name) => format!("{name} {}", series.name),
+                    None => series.name,
+                };
+                let mut chart = Chart::new(series_name, series.dates, series.values);
+                chart.resampling = series.resampling;
+                chart.origin = origin.clone();
+                chart
+            })
+            .collect()
+    }
+    /// A single chart becomes the temporary chart unless other imports are listed already,
+    /// otherwise the charts are appended to the list of imports to be added or skipped. An
+    /// imported temporary chart that has not been added is moved to the list instead of being
+    /// replaced.
+    fn add_imported_charts(&mut self, mut charts: Vec<Chart>) {
+        if charts.len() == 1 && self.pending_imports.is_empty() {
+            if let Some(tmp) = self.charts.take_tmp() {
+                if matches!(tmp.chart.origin, Origin::Imported { .. }) {
+                    self.pending_imports.push(tmp.chart);
+                }
+            }
+            self.show_as_tmp(charts.remove(0));
+        } else {
+            self.pending_imports.extend(charts);
+        }
+    }
+    fn show_as_tmp(&mut self, chart: Chart) {
+        self.charts.add_tmp(Some(TmpChart {
+            chart,
+            initial_balance: self.payment.initial_balance.1,
+        }));
+        self.charts.plot_balance = false;
+        self.rebalance_stats = None;
+    }
+    fn check_file_import(&mut self, ctx: &Context) {
+        self.file_import.check_dropped(ctx);
+        self.file_import.show(ctx);
+        if let Some((name, content)) = self.file_import.try_recv() {
+            match content.and_then(|content| import_csv(&content, &self.csv_import.options)) {
+                Ok(imported) => {
+                    let origin = Origin::Imported {
+                        filename: Some(name.clone()),
+                    };
+                    self.add_imported(imported, Some(file_stem(&name)), origin)
+                }
+                Err(e) => {
+                    self.status_msg = Some(format!("{name}: {e}"));
+                }
+            }
+        }
+    }
+    /// Lists the series of a multi-column import to add or skip them
+    fn ui_pending_imports(&mut self, ui: &mut Ui) {
+        if self.pending_imports.is_empty() {
+            return;
+        }
+        ui.label("Imported price developments");
+        let mut to_show = None;
+        let mut to_add = None;
+        let mut to_skip = None;
+        egui::Grid::new("grid-pending-imports").show(ui, |ui| {
+            for (idx, chart) in self.pending_imports.iter().enumerate() {
+                ui.label(chart.name());
+                ui.label(format!("{} months", chart.values().len()));
+                if ui.button("Show").clicked() {
+                    to_show = Some(idx);
+                }
+                if ui.button("Add").clicked() {
+                    to_add = Some(idx);
+                }
+                if ui.button("Skip").clicked() {
+                    to_skip = Some(idx);
+                }
+                ui.end_row();
+            }
+        });
+        let (add_all, skip_all) = ui
+            .horizontal(|ui| {
+                (
+                    ui.button("Add all").clicked(),
+                    ui.button("Skip all").clicked(),
+                )
+            })
+            .inner;
+        if let Some(idx) = to_show {
+            // the temporary chart is not lost but takes the place in the list
+            let chart = self.pending_imports.remove(idx);
+            if let Some(tmp) = self.charts.take_tmp() {
+                self.pending_imports.insert(idx, tmp.chart);
+            }
+            self.show_as_tmp(chart);
+        }
+        if let Some(idx) = to_skip {
+            self.pending_imports.remove(idx);
+        }
+        if skip_all {
+            self.pending_imports.clear();
+        }
+        let added = if add_all {
+            mem::take(&mut self.pending_imports)
+        } else if let Some(idx) = to_add {
+            vec![self.pending_imports.remove(idx)]
+        } else {
+            vec![]
+        };
+        if !added.is_empty() {
+            for chart in added {
+                self.charts.persist(chart);
+            }
+            self.best_rebalance_trigger = None;
+            self.recompute_balance();
+        }
+    }
+    fn run_csv_import(&mut self) {
+        match import_csv(&self.csv_import.text, &self.csv_import.options) {
+            Ok(imported) => self.add_imported(imported, None, Origin::Imported { filename: None }),
+            Err(e) => {
+                self.status_msg = Some(format!("{e}"));
+            }
         }
     }
     fn trigger_sharelink(&mut self, ctx: &Context) {
@@ -288,8 +847,8 @@ impl BalanceApp<'_> {
                     if resp.status == 200 {
                         let json_str = resp.text().unwrap();
                         let v: ResponsePayload<Self> = serde_json::from_str(json_str).unwrap();
-                        let new_balance = v.json_data;
-                        *self = new_balance;
+                        self.replace_session(v.json_data);
+                        self.update_loaded_session();
                     } else {
                         let json_str = resp.text().unwrap();
                         let v: Value = serde_json::from_str(json_str).unwrap();
@@ -309,6 +868,17 @@ impl BalanceApp<'_> {
             };
         }
     }
+    /// Recomputes derived charts and the balance after inputs of derived charts have changed
+    fn update_derived(&mut self) {
+        match self.charts.update_derived() {
+            Ok(_) => {
+                recompute!(self);
+            }
+            Err(e) => {
+                self.status_msg = Some(format!("{e}"));
+            }
+        }
+    }
     fn recompute_balance(&mut self) {
         if let Err(e) = self.payment.parse() {
             self.status_msg = Some(format!("{e}"));
@@ -333,31 +903,45 @@ impl BalanceApp<'_> {
             } else {
                 self.status_msg = None;
                 self.charts.plot_balance = true;
-                match (
-                    self.charts.total_balance_over_month(),
-                    self.charts.total_payments_over_month(),
-                    self.charts.n_months_persisted(),
-                ) {
-                    (Some(tbom), Some(tp), Ok(n_months)) => {
-                        let final_balance = FinalBalance::from_chart(tbom, tp, n_months);
-                        match final_balance {
-                            Ok(final_balance) => {
-                                self.final_balance = Some(final_balance);
-                            }
-                            Err(e) => {
-                                self.status_msg = Some(e.to_string());
-                            }
-                        }
+                self.update_final_balance();
+            }
+        }
+    }
+    fn update_final_balance(&mut self) {
+        match (
+            self.charts.total_balance_over_month(),
+            self.charts.total_payments_over_month(),
+            self.charts.n_months_persisted(),
+        ) {
+            (Some(tbom), Some(tp), Ok(n_months)) => {
+                let final_balance = FinalBalance::from_chart(
+                    tbom,
+                    tp,
+                    self.charts.total_payouts_over_month(),
+                    n_months,
+                );
+                match final_balance {
+                    Ok(mut final_balance) => {
+                        final_balance.sharpe_ratio = self.charts.sharpe_ratio();
+                        final_balance.total_fees = self.charts.total_fees();
+                        final_balance.savings_final_balance = self
+                            .charts
+                            .savings_baseline()
+                            .and_then(|c| c.values().last().copied());
+                        self.final_balance = Some(final_balance);
                     }
-                    (_, _, Err(e)) => {
+                    Err(e) => {
                         self.status_msg = Some(e.to_string());
-                        self.final_balance = None;
-                    }
-                    _ => {
-                        self.final_balance = None;
                     }
                 }
             }
+            (_, _, Err(e)) => {
+                self.status_msg = Some(e.to_string());
+                self.final_balance = None;
+            }
+            _ => {
+                self.final_balance = None;
+            }
         }
     }
     fn recompute_rebalance_stats(&mut self, always: bool) {
@@ -387,6 +971,133 @@ impl BalanceApp<'_> {
             }
         }
     }
+    fn recompute_frontier(&mut self) {
+        if let Some(frontier) = self.hidden_analyses.0.take() {
+            self.frontier = Some(frontier);
+        } else {
+            let PaymentData {
+                initial_balance: (_, initial_balance),
+                monthly_payments,
+                rebalance_interval: (_, interval),
+                rebalance_deviation: (_, deviation),
+            } = &self.payment;
+            self.frontier = Some(self.charts.weight_grid(
+                *initial_balance,
+                &monthly_payments.payments,
+                RebalanceTrigger {
+                    interval: *interval,
+                    deviation: *deviation,
+                },
+            ));
+        }
+    }
+    fn recompute_rolling_windows(&mut self) {
+        if let Some(rolling_windows) = self.hidden_analyses.1.take() {
+            self.rolling_windows = Some(rolling_windows);
+        } else {
+            let PaymentData {
+                initial_balance: (_, initial_balance),
+                monthly_payments,
+                rebalance_interval: (_, interval),
+                rebalance_deviation: (_, deviation),
+            } = &self.payment;
+            self.rolling_windows = Some(self.charts.rolling_windows(
+                *initial_balance,
+                &monthly_payments.payments,
+                RebalanceTrigger {
+                    interval: *interval,
+                    deviation: *deviation,
+                },
+                self.rolling.n_years * 12,
+            ));
+        }
+    }
+    /// Analyses that replace the plot
+    fn shows_analysis(&self) -> bool {
+        self.frontier.is_some() || self.rolling_windows.is_some()
+    }
+    fn clear_analyses(&mut self) {
+        let frontier = self.frontier.take();
+        let rolling_windows = self.rolling_windows.take();
+        if !self.analyses_outdated {
+            self.hidden_analyses = (
+                frontier.or(self.hidden_analyses.0.take()),
+                rolling_windows.or(self.hidden_analyses.1.take()),
+            );
+        }
+        self.analyses_outdated = false;
+    }
+    /// Called when the inputs of the analyses change
+    fn outdate_analyses(&mut self) {
+        self.hidden_analyses = (None, None);
+        self.analyses_outdated = self.shows_analysis();
+    }
+    /// Recomputes the shown analysis if requested
+    fn outdated_analysis_ui(&mut self, ui: &mut Ui) {
+        if self.analyses_outdated {
+            ui.horizontal(|ui| {
+                ui.label("The inputs have changed.");
+                if ui.button("Recompute").clicked() {
+                    self.analyses_outdated = false;
+                    if self.frontier.is_some() {
+                        self.frontier = None;
+                        self.recompute_frontier();
+                    }
+                    if self.rolling_windows.is_some() {
+                        self.rolling_windows = None;
+                        self.recompute_rolling_windows();
+                    }
+                }
+            });
+        }
+    }
+    fn run_cash_generation(&mut self) {
+        match self.cash.parse() {
+            Ok(ParsedCashInput {
+                rates,
+                start_month,
+                n_months,
+            }) => match rates.price_dev(start_month, n_months) {
+                Ok(values) => {
+                    let mut chart = Chart::new(
+                        if self.cash.name.is_empty() {
+                            format!("cash_{}_{}", self.cash.rate, self.cash.n_months)
+                        } else {
+                            self.cash.name.clone()
+                        },
+                        (0..(n_months + 1))
+                            .map(|i| date_after_nmonths(start_month, i))
+                            .collect::<Vec<_>>(),
+                        values,
+                    );
+                    chart.origin = Origin::Simulated {
+                        parameters: format!(
+                            "yearly rate {}% from {start_month} for {n_months} months{}",
+                            self.cash.rate,
+                            if self.cash.rate_overrides.iter().next().is_none() {
+                                ""
+                            } else {
+                                " with rate overrides"
+                            }
+                        ),
+                        seed: None,
+                    };
+                    self.charts.add_tmp(Some(TmpChart {
+                        chart,
+                        initial_balance: self.payment.initial_balance.1,
+                    }));
+                    self.status_msg = None;
+                    self.charts.plot_balance = false;
+                }
+                Err(e) => {
+                    self.status_msg = Some(format!("{e}"));
+                }
+            },
+            Err(e) => {
+                self.status_msg = Some(format!("{e}"));
+            }
+        }
+    }
     fn run_simulation(&mut self) {
         self.rebalance_stats = None;
         match self.sim.parse() {
@@ -411,16 +1122,21 @@ impl BalanceApp<'_> {
                     .map(|(idx, _)| idx)
                     .collect::<Vec<_>>();
                 let crashes = remove_indices(crashes, &to_be_del);
-                match random_walk(
-                    expected_yearly_return,
-                    is_eyr_markovian,
-                    vola,
-                    vola_window,
-                    n_months,
-                    &crashes,
-                ) {
-                    Ok(values) => {
-                        let chart = Chart::new(
+                let walk = unix_to_now_nanos().and_then(|seed| {
+                    random_walk_from_seed(
+                        seed,
+                        expected_yearly_return,
+                        is_eyr_markovian,
+                        vola,
+                        vola_window,
+                        n_months,
+                        &crashes,
+                    )
+                    .map(|values| (seed, values))
+                });
+                match walk {
+                    Ok((seed, values)) => {
+                        let mut chart = Chart::new(
                             if self.sim.name.is_empty() {
                                 format!(
                                     "{}_{}_{}_{}",
@@ -441,6 +1157,18 @@ impl BalanceApp<'_> {
                                 .collect::<Vec<_>>(),
                             values,
                         );
+                        chart.origin = Origin::Simulated {
+                            parameters: format!(
+                                "expected yearly return {}%{}, volatility {}, {} months from \
+                                 {start_date}, {} crashes",
+                                self.sim.expected_yearly_return,
+                                if is_eyr_markovian { " (Markovian)" } else { "" },
+                                self.sim.vola,
+                                n_months,
+                                crashes.len()
+                            ),
+                            seed: Some(seed),
+                        };
                         self.charts.add_tmp(Some(TmpChart {
                             chart,
                             initial_balance: self.payment.initial_balance.1,
@@ -453,144 +1181,819 @@ impl BalanceApp<'_> {
                     }
                 };
             }
-            Err(e) => {
-                self.status_msg = Some(format!("{e}"));
+            Err(e) => {
+                self.status_msg = Some(format!("{e}"));
+            }
+        };
+    }
+    fn run_goal_seek(&mut self) {
+        self.goal_seek_result = None;
+        let parsed = self.payment.parse().and_then(|_| self.goal_seek.parse());
+        let ParsedGoalSeek {
+            target,
+            variable,
+            chart_idx,
+            success_probability,
+        } = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                self.status_msg = Some(format!("{e}"));
+                return;
+            }
+        };
+        let PaymentData {
+            initial_balance: (_, initial_balance),
+            monthly_payments,
+            rebalance_interval: (_, interval),
+            rebalance_deviation: (_, deviation),
+        } = &self.payment;
+        let solution = if let Some(probability) = success_probability {
+            self.sim.parse().and_then(|sim| {
+                let seed = unix_to_now_nanos()?;
+                let price_devs = (0..N_MONTE_CARLO_PATHS as u64)
+                    .map(|i| {
+                        random_walk_from_seed(
+                            seed.wrapping_add(2 * i),
+                            sim.expected_yearly_return,
+                            sim.is_eyr_markovian,
+                            sim.vola,
+                            sim.vola_window,
+                            sim.n_months,
+                            &sim.crashes,
+                        )
+                    })
+                    .collect::<BlcResult<Vec<_>>>()?;
+                solve_for_success_probability(
+                    &price_devs,
+                    *initial_balance,
+                    &monthly_payments.payments,
+                    sim.start_month,
+                    variable,
+                    target,
+                    probability,
+                )
+            })
+        } else {
+            self.charts.solve_goal(
+                *initial_balance,
+                &monthly_payments.payments,
+                RebalanceTrigger {
+                    interval: *interval,
+                    deviation: *deviation,
+                },
+                variable,
+                chart_idx,
+                target,
+            )
+        };
+        match solution {
+            Ok(x) => {
+                self.goal_seek_result = Some((variable, x));
+                self.status_msg = None;
+            }
+            Err(e) => {
+                self.status_msg = Some(format!("goal seeking failed; {e}"));
+            }
+        }
+    }
+    fn ui_goal_seek(&mut self, ui: &mut Ui) {
+        egui::CollapsingHeader::new("Goal seeking").show(ui, |ui| {
+            egui::Grid::new("goal-seeking-inputs")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Target");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(
+                            &mut self.goal_seek.target_is_yearly_return,
+                            false,
+                            "final balance",
+                        );
+                        ui.radio_value(
+                            &mut self.goal_seek.target_is_yearly_return,
+                            true,
+                            "yearly return [%]",
+                        );
+                    });
+                    ui.end_row();
+                    ui.label("Target value");
+                    ui.text_edit_singleline(&mut self.goal_seek.target);
+                    ui.end_row();
+                    ui.label("Solve for");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(
+                            &mut self.goal_seek.variable,
+                            GoalVariable::MonthlyPayment,
+                            "monthly payment",
+                        );
+                        ui.radio_value(
+                            &mut self.goal_seek.variable,
+                            GoalVariable::InitialBalance,
+                            "initial capital",
+                        );
+                        ui.radio_value(
+                            &mut self.goal_seek.variable,
+                            GoalVariable::YearlyReturn,
+                            "expected yearly return of",
+                        );
+                        if self.goal_seek.chart_idx >= self.charts.persisted.len() {
+                            self.goal_seek.chart_idx = 0;
+                        }
+                        let selected_name = self
+                            .charts
+                            .persisted
+                            .get(self.goal_seek.chart_idx)
+                            .map(|c| c.name().to_string())
+                            .unwrap_or_default();
+                        egui::ComboBox::from_id_salt("goal-seeking-chart")
+                            .selected_text(selected_name)
+                            .show_ui(ui, |ui| {
+                                for (idx, c) in self.charts.persisted.iter().enumerate() {
+                                    ui.selectable_value(
+                                        &mut self.goal_seek.chart_idx,
+                                        idx,
+                                        c.name(),
+                                    );
+                                }
+                            });
+                    });
+                    ui.end_row();
+                    ui.label("Success probability [%]");
+                    ui.text_edit_singleline(&mut self.goal_seek.success_probability);
+                    ui.end_row();
+                });
+            ui.label(
+                "The target refers to the end of the timeline. If a success probability is given, \
+                 we run a Monte Carlo simulation with the settings from the simulation of price \
+                 developments instead of using the added price developments.",
+            );
+            if ui.button("Solve").clicked() {
+                self.run_goal_seek();
+            }
+        });
+    }
+    fn ui_add_price_dev(&mut self, ui: &mut Ui, ctx: &egui::Context) {
+        egui::CollapsingHeader::new("Simulate price development").show(ui, |ui| {
+            egui::Grid::new("simulate-inputs")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Expected yearly return [%]");
+                    ui.text_edit_singleline(&mut self.sim.expected_yearly_return);
+                    ui.end_row();
+                    ui.label("#Months");
+                    ui.text_edit_singleline(&mut self.sim.n_months);
+                    ui.end_row();
+                    ui.label("Start date");
+                    self.sim.start_month_slider.month_slider(ui);
+                });
+            ui.horizontal(|ui| {
+                ui.label("Vola");
+                ui.radio_value(
+                    &mut self.sim.vola.amount,
+                    VolaAmount::No,
+                    format!("{}", VolaAmount::No),
+                );
+                ui.radio_value(
+                    &mut self.sim.vola.amount,
+                    VolaAmount::Lo,
+                    format!("{}", VolaAmount::Lo),
+                );
+                ui.radio_value(
+                    &mut self.sim.vola.amount,
+                    VolaAmount::Mi,
+                    format!("{}", VolaAmount::Mi),
+                );
+                ui.radio_value(
+                    &mut self.sim.vola.amount,
+                    VolaAmount::Hi,
+                    format!("{}", VolaAmount::Hi),
+                );
+            });
+            let add_crash = || {
+                let start_end = self.charts.start_end_date(true);
+                match start_end {
+                    Ok(se) => {
+                        let (start, end) = se;
+                        Ok(MonthSlider::new(start, end, SliderState::First))
+                    }
+                    Err(_) => {
+                        if let (Some(start), Ok(n_month)) = (
+                            self.sim.start_month_slider.selected_date(),
+                            self.sim.n_months.parse::<usize>(),
+                        ) {
+                            let end = start + n_month;
+                            end.map(|end| MonthSlider::new(start, end, SliderState::First))
+                        } else {
+                            let err = blcerr!(
+                                "couldn't parse n_month, what integer>0 is {}",
+                                self.sim.n_months
+                            );
+                            self.status_msg = Some(err.msg.to_string());
+                            Err(err)
+                        }
+                    }
+                }
+            };
+            egui::CollapsingHeader::new("Advanced").show(ui, |ui| {
+                egui::Grid::new("simulate-advanced")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Name (auto-generated if empty)");
+                        ui.text_edit_singleline(&mut self.sim.name);
+                        ui.end_row();
+                        ui.label("Return independent of previous returns");
+                        ui.checkbox(&mut self.sim.is_eyr_markovian, "");
+                        ui.end_row();
+                        ui.label("Times of similar volatility");
+                        ui.checkbox(&mut self.sim.vola.smoothing, "");
+                        ui.end_row();
+                        let show_crash = |i, month_slider: &mut MonthSlider, ui: &mut Ui| {
+                            ui.label(format!("Crash {}", i + 1));
+                            month_slider.month_slider(ui);
+                        };
+                        self.sim
+                            .crashes
+                            .show(ui, show_crash, add_crash, "Add crash");
+                    })
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Run simulation").clicked() {
+                    self.run_simulation()
+                }
+            });
+        });
+        egui::CollapsingHeader::new("Cash or fixed interest").show(ui, |ui| {
+            egui::Grid::new("cash-inputs")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Yearly interest rate [%]");
+                    ui.text_edit_singleline(&mut self.cash.rate);
+                    ui.end_row();
+                    ui.label("#Months");
+                    ui.text_edit_singleline(&mut self.cash.n_months);
+                    ui.end_row();
+                    ui.label("Start date");
+                    self.cash.start_month_slider.month_slider(ui);
+                    ui.end_row();
+                    ui.label("Name (auto-generated if empty)");
+                    ui.text_edit_singleline(&mut self.cash.name);
+                    ui.end_row();
+                });
+            egui::CollapsingHeader::new("Rates for date ranges").show(ui, |ui| {
+                egui::Grid::new("cash-rate-overrides")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        let start_month = self.cash.start_month_slider.selected_date();
+                        let n_months = self.cash.n_months.parse::<usize>();
+                        let add_override = || {
+                            if let (Some(start), Ok(n_months)) = (start_month, &n_months) {
+                                let end = (start + *n_months)?;
+                                Ok(RateOverride {
+                                    rate: "0.0".to_string(),
+                                    interval: MonthSliderPair::new(
+                                        MonthSlider::new(start, end, SliderState::First),
+                                        MonthSlider::new(start, end, SliderState::Last),
+                                    ),
+                                })
+                            } else {
+                                Err(blcerr!("start date and #months needed"))
+                            }
+                        };
+                        let show_override = |i, ro: &mut RateOverride, ui: &mut Ui| {
+                            ui.label(format!("Rate {} [%]", i + 1));
+                            ui.text_edit_singleline(&mut ro.rate);
+                            ui.end_row();
+                            ui.label("");
+                            ro.interval.start_slider(ui);
+                            ui.end_row();
+                            ui.label("");
+                            ro.interval.end_slider(ui);
+                        };
+                        self.cash.rate_overrides.show(
+                            ui,
+                            show_override,
+                            add_override,
+                            "Add rate for date range",
+                        );
+                    });
+                ui.label(
+                    "Rates can be expressions of month, years_elapsed, calendar_year, and \
+                     calendar_month. Later date ranges override earlier ones.",
+                );
+            });
+            if ui.button("Create price development").clicked() {
+                self.run_cash_generation();
+            }
+        });
+        egui::CollapsingHeader::new("Use historical data as price development").show(ui, |ui| {
+            let mut clicked = None;
+            ui.horizontal_wrapped(|ui| {
+                for (idx, dataset) in self.catalog.datasets.iter().enumerate() {
+                    let mut details = dataset.details(&self.catalog);
+                    if let Some(version) =
+                        self.dataset_cache.version(&self.catalog.resolve(dataset))
+                    {
+                        details = format!(
+                            "{details}\ncached at {}, right-click to refresh",
+                            format_timestamp(version.downloaded)
+                        );
+                    }
+                    let button = ui.button(&dataset.name);
+                    let button = if details.is_empty() {
+                        button
+                    } else {
+                        button.on_hover_text(details.trim_start())
+                    };
+                    if button.clicked() {
+                        clicked = Some((idx, false));
+                    }
+                    button.context_menu(|ui| {
+                        if ui.button("Refresh").clicked() {
+                            clicked = Some((idx, true));
+                            ui.close();
+                        }
+                    });
+                }
+            });
+            if let Some((idx, refresh)) = clicked {
+                self.download_dataset(idx, refresh, ctx);
+            }
+            if self.dataset_cache.len() > 0 {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} datasets cached for offline use",
+                        self.dataset_cache.len()
+                    ));
+                    if ui.button("Clear cache").clicked() {
+                        if let Err(e) = self.dataset_cache.clear() {
+                            self.status_msg = Some(format!("{e}"));
+                        }
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                gap_repair_ui(
+                    ui,
+                    &mut self.csv_import.options.gap_repair,
+                    "gap-repair-download",
+                );
+            });
+            if !self.catalog.sources.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label("data from");
+                    for source in &self.catalog.sources {
+                        if source.url.is_empty() {
+                            ui.label(&source.name);
+                        } else {
+                            ui.hyperlink_to(&source.name, &source.url);
+                        }
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label("Catalog");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.catalog_location)
+                        .hint_text("URL or path of a catalog.json, empty for the default"),
+                );
+                if ui.button("Load catalog").clicked() {
+                    self.trigger_catalog_load(ctx);
+                }
+            });
+        });
+        egui::CollapsingHeader::new("Import CSV or TSV").show(ui, |ui| {
+            csv_import_options_ui(ui, &mut self.csv_import.options);
+            ui.label(
+                "Dates in the first column, e.g., 2020/03, 2020-03, 03/2020, 2020-03-31, or \
+                 31.03.2020, one price development per further column.",
+            );
+            ui.add(
+                egui::TextEdit::multiline(&mut self.csv_import.text)
+                    .hint_text("date,world,em\n2020-01,100.0,50.0\n2020-02,101.5,49.0")
+                    .desired_rows(4)
+                    .desired_width(f32::INFINITY),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Import").clicked() {
+                    self.run_csv_import();
+                }
+                if ui.button("Open file...").clicked() {
+                    if let Err(e) = self.file_import.open(ctx) {
+                        self.status_msg = Some(format!("{e}"));
+                    }
+                }
+                ui.label("or drop files onto the window");
+            });
+        });
+        self.ui_pending_imports(ui);
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Add price development for balance computation")
+                .clicked()
+            {
+                self.best_rebalance_trigger = None;
+                self.charts.persist_tmp();
+                self.recompute_balance();
+            }
+            if ui
+                .button("Use as inflation (consumer price index)")
+                .clicked()
+            {
+                self.charts.persist_tmp_as_inflation();
+                recompute!(self);
+            }
+            if ui
+                .button("Use as risk-free rate and savings account")
+                .clicked()
+            {
+                self.charts.persist_tmp_as_risk_free();
+                recompute!(self);
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Use as exchange rate, value of 1").clicked() {
+                match self.charts.persist_tmp_as_fx_rate(&self.fx_currency) {
+                    Ok(_) => {
+                        recompute!(self);
+                    }
+                    Err(e) => {
+                        self.status_msg = Some(format!("{e}"));
+                    }
+                }
+            }
+            ui.add(
+                egui::TextEdit::singleline(&mut self.fx_currency)
+                    .hint_text("USD")
+                    .desired_width(50.0),
+            );
+            ui.label(if self.charts.base_currency.is_empty() {
+                "in base currency".to_string()
+            } else {
+                format!("in {}", self.charts.base_currency)
+            });
+        });
+        if !self.charts.persisted.is_empty() {
+            ui.horizontal(|ui| {
+                if ui.button("Use as yearly dividend yield [%] of").clicked() {
+                    match self
+                        .charts
+                        .persist_tmp_as_dividend_yield(self.dividend_chart_idx)
+                    {
+                        Ok(_) => {
+                            recompute!(self);
+                        }
+                        Err(e) => {
+                            self.status_msg = Some(format!("{e}"));
+                        }
+                    }
+                }
+                let names = self
+                    .charts
+                    .persisted
+                    .iter()
+                    .map(|c| c.name().to_string())
+                    .collect::<Vec<_>>();
+                egui::ComboBox::from_id_salt("dividend-chart")
+                    .selected_text(
+                        names
+                            .get(self.dividend_chart_idx)
+                            .cloned()
+                            .unwrap_or_default(),
+                    )
+                    .show_ui(ui, |ui| {
+                        for (idx, name) in names.iter().enumerate() {
+                            ui.selectable_value(&mut self.dividend_chart_idx, idx, name);
+                        }
+                    });
+            });
+        }
+        ui.separator();
+        if !self.charts.persisted.is_empty() && self.charts.fraction_sliders(ui) {
+            recompute!(self);
+        }
+        if self.charts.persisted.len() > 1 && self.charts.optimizer_ui(ui) {
+            recompute!(self);
+        }
+        if self.charts.persisted.len() > 1 {
+            self.ui_correlations(ui);
+        }
+        if self.charts.auxiliary_charts_ui(ui) {
+            recompute!(self);
+        }
+        if !self.charts.persisted.is_empty() && self.charts.currency_ui(ui) {
+            self.update_derived();
+        }
+        if !self.charts.persisted.is_empty() && self.charts.dividends_ui(ui) {
+            self.update_derived();
+        }
+        if !self.charts.persisted.is_empty() && self.charts.margin_ui(ui) {
+            recompute!(self);
+        }
+        if !self.charts.persisted.is_empty() && self.ui_splice(ui) {
+            recompute!(self);
+        }
+        if !self.charts.persisted.is_empty() && self.ui_derive(ui) {
+            recompute!(self);
+        }
+        if !self.charts.persisted.is_empty() && self.ui_leverage(ui) {
+            recompute!(self);
+        }
+    }
+    fn ui_correlations(&mut self, ui: &mut Ui) {
+        egui::CollapsingHeader::new("Correlations").show(ui, |ui| {
+            let input = &mut self.correlation;
+            match self.charts.return_matrices() {
+                Ok(matrices) => {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("correlation-matrix-kind")
+                            .selected_text(input.kind.to_string())
+                            .show_ui(ui, |ui| {
+                                for kind in [MatrixKind::Correlation, MatrixKind::Covariance] {
+                                    ui.selectable_value(&mut input.kind, kind, kind.to_string());
+                                }
+                            });
+                        if ui.button("Download as csv").clicked() {
+                            let filename = match input.kind {
+                                MatrixKind::Correlation => "correlations.csv",
+                                MatrixKind::Covariance => "covariances.csv",
+                            };
+                            if let Err(e) = export_str(&matrices.to_csv(input.kind), filename) {
+                                self.status_msg = Some(format!("{e}"));
+                            }
+                        }
+                    });
+                    heatmap_ui(ui, &matrices.names, matrices.matrix(input.kind));
+                    ui.label(format!(
+                        "Monthly returns of {} months in which all charts have values",
+                        matrices.n_months
+                    ));
+                }
+                Err(e) => {
+                    ui.label(format!("{e}"));
+                }
+            }
+            ui.separator();
+            let n_charts = self.charts.persisted.len();
+            if input.pair.0 >= n_charts || input.pair.1 >= n_charts {
+                input.pair = (0, 1);
             }
-        };
-    }
-    fn ui_add_price_dev(&mut self, ui: &mut Ui, ctx: &egui::Context) {
-        egui::CollapsingHeader::new("Simulate price development").show(ui, |ui| {
-            egui::Grid::new("simulate-inputs")
-                .num_columns(2)
-                .show(ui, |ui| {
-                    ui.label("Expected yearly return [%]");
-                    ui.text_edit_singleline(&mut self.sim.expected_yearly_return);
-                    ui.end_row();
-                    ui.label("#Months");
-                    ui.text_edit_singleline(&mut self.sim.n_months);
-                    ui.end_row();
-                    ui.label("Start date");
-                    self.sim.start_month_slider.month_slider(ui);
-                });
             ui.horizontal(|ui| {
-                ui.label("Vola");
-                ui.radio_value(
-                    &mut self.sim.vola.amount,
-                    VolaAmount::No,
-                    format!("{}", VolaAmount::No),
-                );
-                ui.radio_value(
-                    &mut self.sim.vola.amount,
-                    VolaAmount::Lo,
-                    format!("{}", VolaAmount::Lo),
-                );
-                ui.radio_value(
-                    &mut self.sim.vola.amount,
-                    VolaAmount::Mi,
-                    format!("{}", VolaAmount::Mi),
-                );
-                ui.radio_value(
-                    &mut self.sim.vola.amount,
-                    VolaAmount::Hi,
-                    format!("{}", VolaAmount::Hi),
+                ui.label("Rolling correlation of");
+                for (idx, salt) in [
+                    (&mut input.pair.0, "rolling-correlation-first"),
+                    (&mut input.pair.1, "rolling-correlation-second"),
+                ] {
+                    egui::ComboBox::from_id_salt(salt)
+                        .selected_text(self.charts.persisted[*idx].name())
+                        .show_ui(ui, |ui| {
+                            for (i, chart) in self.charts.persisted.iter().enumerate() {
+                                ui.selectable_value(idx, i, chart.name());
+                            }
+                        });
+                }
+                ui.add(
+                    egui::DragValue::new(&mut input.window)
+                        .range(2..=600)
+                        .prefix("window ")
+                        .suffix(" months"),
                 );
             });
-            let add_crash = || {
-                let start_end = self.charts.start_end_date(true);
-                match start_end {
-                    Ok(se) => {
-                        let (start, end) = se;
-                        Ok(MonthSlider::new(start, end, SliderState::First))
+            let (i, j) = input.pair;
+            match self.charts.rolling_correlation(i, j, input.window) {
+                Ok((dates, correlations)) => {
+                    let name = format!(
+                        "{} vs {}",
+                        self.charts.persisted[i].name(),
+                        self.charts.persisted[j].name()
+                    );
+                    // correlations before the inception of a chart are NaN
+                    let (dates, correlations): (Vec<_>, Vec<_>) = dates
+                        .into_iter()
+                        .zip(correlations)
+                        .filter(|(_, c)| c.is_finite())
+                        .unzip();
+                    if ui.button("Download rolling correlation as csv").clicked() {
+                        let csv = series_to_csv(&name, &dates, &correlations);
+                        if let Err(e) = export_str(&csv, "rolling_correlation.csv") {
+                            self.status_msg = Some(format!("{e}"));
+                        }
                     }
-                    Err(_) => {
-                        if let (Some(start), Ok(n_month)) = (
-                            self.sim.start_month_slider.selected_date(),
-                            self.sim.n_months.parse::<usize>(),
-                        ) {
-                            let end = start + n_month;
-                            end.map(|end| MonthSlider::new(start, end, SliderState::First))
+                    let points = correlations
+                        .iter()
+                        .enumerate()
+                        .map(|(x, c)| [x as f64, *c])
+                        .collect::<Vec<_>>();
+                    let x_fmt = move |x: GridMark, _range: &std::ops::RangeInclusive<f64>| {
+                        let i = x.value.round();
+                        if (x.value - i).abs() < 1e-6 && i >= 0.0 && (i as usize) < dates.len() {
+                            dates[i as usize].to_string()
                         } else {
-                            let err = blcerr!(
-                                "couldn't parse n_month, what integer>0 is {}",
-                                self.sim.n_months
-                            );
-                            self.status_msg = Some(err.msg.to_string());
-                            Err(err)
+                            String::new()
                         }
-                    }
+                    };
+                    Plot::new("rolling correlation")
+                        .height(200.0)
+                        .include_y(-1.0)
+                        .include_y(1.0)
+                        .x_axis_formatter(x_fmt)
+                        .show(ui, |plot_ui| {
+                            plot_ui.hline(HLine::new("zero", 0.0));
+                            plot_ui.line(Line::new(name, points));
+                        });
                 }
-            };
-            egui::CollapsingHeader::new("Advanced").show(ui, |ui| {
-                egui::Grid::new("simulate-advanced")
-                    .num_columns(2)
-                    .show(ui, |ui| {
-                        ui.label("Name (auto-generated if empty)");
-                        ui.text_edit_singleline(&mut self.sim.name);
-                        ui.end_row();
-                        ui.label("Return independent of previous returns");
-                        ui.checkbox(&mut self.sim.is_eyr_markovian, "");
-                        ui.end_row();
-                        ui.label("Times of similar volatility");
-                        ui.checkbox(&mut self.sim.vola.smoothing, "");
-                        ui.end_row();
-                        let show_crash = |i, month_slider: &mut MonthSlider, ui: &mut Ui| {
-                            ui.label(format!("Crash {}", i + 1));
-                            month_slider.month_slider(ui);
-                        };
-                        self.sim
-                            .crashes
-                            .show(ui, show_crash, add_crash, "Add crash");
-                    })
-            });
+                Err(e) => {
+                    ui.label(format!("{e}"));
+                }
+            }
+        });
+    }
 
+    fn ui_leverage(&mut self, ui: &mut Ui) -> bool {
+        let mut added = false;
+        let names = self
+            .charts
+            .persisted
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect::<Vec<_>>();
+        egui::CollapsingHeader::new("Leveraged and inverse products").show(ui, |ui| {
             ui.horizontal(|ui| {
-                if ui.button("Run simulation").clicked() {
-                    self.run_simulation()
-                }
+                ui.add(
+                    egui::DragValue::new(&mut self.leverage.leverage)
+                        .speed(0.1)
+                        .range(-3.0..=5.0)
+                        .suffix("x"),
+                );
+                ui.label("on");
+                egui::ComboBox::from_id_salt("leverage-underlying")
+                    .selected_text(
+                        names
+                            .get(self.leverage.underlying)
+                            .cloned()
+                            .unwrap_or_default(),
+                    )
+                    .show_ui(ui, |ui| {
+                        for (idx, name) in names.iter().enumerate() {
+                            ui.selectable_value(&mut self.leverage.underlying, idx, name);
+                        }
+                    });
             });
-        });
-        egui::CollapsingHeader::new("Use historical data as price development").show(ui, |ui| {
             ui.horizontal(|ui| {
-                let mut dl_button = |name, filename| {
-                    if ui.button(name).clicked() {
-                        let url = format!("{BASE_URL_WWW}/{filename}");
-                        self.download_historic_csv.trigger(
-                            &url,
-                            name,
-                            RestMethod::Get,
-                            Some(ctx.clone()),
-                        );
-                        self.charts.plot_balance = false;
-                        self.rebalance_stats = None;
+                ui.add(
+                    egui::DragValue::new(&mut self.leverage.borrowing_cost)
+                        .speed(0.01)
+                        .range(0.0..=20.0)
+                        .prefix("borrowing cost ")
+                        .suffix(" %"),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.leverage.ter)
+                        .speed(0.01)
+                        .range(0.0..=10.0)
+                        .prefix("TER ")
+                        .suffix(" %"),
+                );
+            });
+            ui.label(
+                "The leverage is reset daily, approximated from monthly data with a correction \
+                 for the volatility drag. Inverse products earn the borrowing cost on their cash.",
+            );
+            if ui.button("Add leveraged product").clicked() {
+                let LeverageInput {
+                    underlying,
+                    leverage,
+                    borrowing_cost,
+                    ter,
+                } = self.leverage;
+                match self
+                    .charts
+                    .add_leveraged(underlying, leverage, borrowing_cost, ter)
+                {
+                    Ok(()) => {
+                        self.status_msg = None;
+                        added = true;
                     }
-                };
-                dl_button("MSCI ACWI", "msciacwi.csv");
-                dl_button("MSCI World", "msciworld.csv");
-                dl_button("MSCI EM", "msciem.csv");
-                dl_button("MSCI Europe", "mscieurope.csv");
-                dl_button("S&P 500", "sandp500.csv");
+                    Err(e) => {
+                        self.status_msg = Some(format!("{e}"));
+                    }
+                }
+            }
+        });
+        added
+    }
+    fn ui_derive(&mut self, ui: &mut Ui) -> bool {
+        let mut derived = false;
+        egui::CollapsingHeader::new("Derive charts from expressions").show(ui, |ui| {
+            egui::Grid::new("derive-chart").show(ui, |ui| {
+                ui.label("Name");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.derived_chart.name)
+                        .hint_text("expression"),
+                );
+                ui.end_row();
+                ui.label("Expression");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.derived_chart.expression)
+                        .hint_text("0.6*world + 0.4*em"),
+                );
+                ui.end_row();
             });
+            let identifiers = self
+                .charts
+                .persisted
+                .iter()
+                .map(|c| expression_identifier(c.name()))
+                .collect::<Vec<_>>();
+            ui.label(format!(
+                "Charts are referenced as {}. Further variables are month, years_elapsed, \
+                 calendar_year, and calendar_month. Derived charts are recomputed when their \
+                 inputs change.",
+                identifiers.join(", ")
+            ));
+            if ui.button("Add derived chart").clicked() {
+                match self
+                    .charts
+                    .add_derived(&self.derived_chart.name, &self.derived_chart.expression)
+                {
+                    Ok(()) => {
+                        self.status_msg = None;
+                        self.derived_chart.name.clear();
+                        derived = true;
+                    }
+                    Err(e) => {
+                        self.status_msg = Some(format!("{e}"));
+                    }
+                }
+            }
+        });
+        derived
+    }
+    fn ui_splice(&mut self, ui: &mut Ui) -> bool {
+        let mut spliced = false;
+        let names = self
+            .charts
+            .persisted
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect::<Vec<_>>();
+        let proxy_name = |proxy: Option<usize>| match proxy {
+            Some(idx) => names.get(idx).cloned().unwrap_or_default(),
+            None => "calibrated random walk".to_string(),
+        };
+        egui::CollapsingHeader::new("Extend short histories with a proxy").show(ui, |ui| {
             ui.horizontal(|ui| {
-                ui.label("data from");
-                ui.hyperlink_to(
-                    "Backtest by Curvo",
-                    "https://curvo.eu/backtest/faq#is-it-free",
-                );
+                ui.label("Extend");
+                egui::ComboBox::from_id_salt("splice-target")
+                    .selected_text(names.get(self.splice.target).cloned().unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for (idx, name) in names.iter().enumerate() {
+                            ui.selectable_value(&mut self.splice.target, idx, name);
+                        }
+                    });
+                ui.label("backwards with");
+                egui::ComboBox::from_id_salt("splice-proxy")
+                    .selected_text(proxy_name(self.splice.proxy))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.splice.proxy, None, proxy_name(None));
+                        for idx in (0..names.len()).filter(|idx| *idx != self.splice.target) {
+                            ui.selectable_value(
+                                &mut self.splice.proxy,
+                                Some(idx),
+                                proxy_name(Some(idx)),
+                            );
+                        }
+                    });
+                if self.splice.proxy.is_none() {
+                    ui.label("over");
+                    ui.add(egui::DragValue::new(&mut self.splice.n_months).range(1..=1200));
+                    ui.label("months");
+                }
             });
+            ui.label(
+                "The proxy is scaled to match at the first month of the extended chart. A \
+                 calibrated random walk has the mean return and volatility of the extended chart.",
+            );
+            if ui.button("Splice").clicked() {
+                let proxy = match self.splice.proxy {
+                    Some(idx) => Ok(SpliceProxy::Chart(idx)),
+                    None => unix_to_now_nanos().map(|seed| SpliceProxy::Calibrated {
+                        n_months: self.splice.n_months,
+                        seed,
+                    }),
+                };
+                match proxy.and_then(|proxy| self.charts.splice(self.splice.target, proxy)) {
+                    Ok(()) => {
+                        self.status_msg = None;
+                        spliced = true;
+                    }
+                    Err(e) => {
+                        self.status_msg = Some(format!("{e}"));
+                    }
+                }
+            }
         });
-
-        if ui
-            .button("Add price development for balance computation")
-            .clicked()
-        {
-            self.best_rebalance_trigger = None;
-            self.charts.persist_tmp();
-            self.recompute_balance();
-        }
-        ui.separator();
-        if !self.charts.persisted.is_empty() && self.charts.fraction_sliders(ui) {
-            recompute!(self);
-        }
+        spliced
     }
     fn ui_set_investment(&mut self, ui: &mut Ui) {
         ui.label("Initial capital");
@@ -678,6 +2081,48 @@ impl BalanceApp<'_> {
                         }
                     }
                 });
+            if ui
+                .checkbox(
+                    &mut self.payment.monthly_payments.inflation_indexed,
+                    "Index payments to inflation",
+                )
+                .changed()
+            {
+                recompute!(self);
+            }
+            egui::CollapsingHeader::new("Constants").show(ui, |ui| {
+                egui::Grid::new("monthly-payments-constants")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        let mut changed = false;
+                        let show_constant =
+                            |_, (name, value): &mut (String, String), ui: &mut Ui| {
+                                changed |= ui.text_edit_singleline(name).changed();
+                                changed |= ui.text_edit_singleline(value).changed();
+                            };
+                        let removed = self.payment.monthly_payments.constants.show(
+                            ui,
+                            show_constant,
+                            || Ok((String::new(), "0.0".to_string())),
+                            "Add constant",
+                        );
+                        if changed || removed.is_some() {
+                            recompute!(self);
+                        }
+                    });
+            });
+            egui::CollapsingHeader::new("Available variables").show(ui, |ui| {
+                egui::Grid::new("monthly-payments-variables")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (name, description) in PAYMENT_VARIABLES {
+                            ui.label(RichText::new(name).code());
+                            ui.label(description);
+                            ui.end_row();
+                        }
+                    });
+                ui.label("Constants can be used by their names, e.g., salary * 0.1.");
+            });
         });
         egui::CollapsingHeader::new("Rebalancing strategy").show(ui, |ui| {
             egui::Grid::new("rebalancing-strategy-inputs").show(ui, |ui| {
@@ -711,7 +2156,22 @@ impl BalanceApp<'_> {
                     recompute!(self);
                 }
             });
+            if ui
+                .checkbox(
+                    &mut self.charts.union_timeline,
+                    "Run over the union of the chart histories",
+                )
+                .on_hover_text(
+                    "Charts that start later are phased in by rebalancing, until then their \
+                     fractions are distributed among the available charts",
+                )
+                .changed()
+            {
+                self.charts.update_start_end_sliders();
+                recompute!(self);
+            }
         });
+        self.ui_goal_seek(ui);
         ui.separator();
     }
     fn ui_show_results(&mut self, ui: &mut Ui, ctx: &Context) {
@@ -721,6 +2181,7 @@ impl BalanceApp<'_> {
                     final_balance,
                     yearly_return_perc,
                     total_payments,
+                    ..
                 } = final_balance;
                 ui.label("Final balance");
                 ui.label(RichText::new(format_num(*final_balance)).strong());
@@ -738,13 +2199,59 @@ impl BalanceApp<'_> {
                 ui.label("Yearly return [%]");
                 ui.label("-");
             }
+            if let Some(FinalBalance {
+                sharpe_ratio,
+                savings_final_balance,
+                total_fees,
+                total_payouts,
+                ..
+            }) = &self.final_balance
+            {
+                ui.end_row();
+                ui.label("Sharpe ratio");
+                ui.label(
+                    RichText::new(
+                        sharpe_ratio
+                            .map(|sr| format!("{sr:0.2}"))
+                            .unwrap_or_else(|| "-".to_string()),
+                    )
+                    .strong(),
+                );
+                if let Some(total_fees) = total_fees {
+                    ui.label("Total fees");
+                    ui.label(RichText::new(format_num(*total_fees)).strong());
+                }
+                if let Some(total_payouts) = total_payouts {
+                    ui.label("Total dividend payouts");
+                    ui.label(RichText::new(format_num(*total_payouts)).strong());
+                }
+                if let Some(savings_final_balance) = savings_final_balance {
+                    ui.label("Savings account final balance");
+                    ui.label(RichText::new(format_num(*savings_final_balance)).strong());
+                }
+            }
+            if let Some((variable, x)) = &self.goal_seek_result {
+                ui.end_row();
+                ui.label(match variable {
+                    GoalVariable::MonthlyPayment if *x < 0.0 => "Possible monthly withdrawal",
+                    GoalVariable::MonthlyPayment => "Required monthly payment",
+                    GoalVariable::InitialBalance => "Required initial capital",
+                    GoalVariable::YearlyReturn => "Required yearly return [%]",
+                });
+                let x = match variable {
+                    GoalVariable::MonthlyPayment => x.abs(),
+                    _ => *x,
+                };
+                ui.label(RichText::new(format_num(x)).strong());
+            }
         });
         ui.horizontal(|ui| {
             if ui
                 .selectable_label(
                     self.charts.plot_balance
                         && self.rebalance_stats.is_none()
-                        && self.best_rebalance_trigger.is_none(),
+                        && self.best_rebalance_trigger.is_none()
+                        && !self.shows_analysis(),
                     "Balance plot",
                 )
                 .clicked()
@@ -752,11 +2259,13 @@ impl BalanceApp<'_> {
                 self.charts.plot_balance = true;
                 self.rebalance_stats = None;
                 self.best_rebalance_trigger = None;
+                self.clear_analyses();
             } else if ui
                 .selectable_label(
                     !self.charts.plot_balance
                         && self.rebalance_stats.is_none()
-                        && self.best_rebalance_trigger.is_none(),
+                        && self.best_rebalance_trigger.is_none()
+                        && !self.shows_analysis(),
                     "Charts plot",
                 )
                 .clicked()
@@ -764,22 +2273,27 @@ impl BalanceApp<'_> {
                 self.charts.plot_balance = false;
                 self.rebalance_stats = None;
                 self.best_rebalance_trigger = None;
+                self.clear_analyses();
             } else if ui
                 .selectable_label(
-                    self.rebalance_stats.is_some() && self.best_rebalance_trigger.is_none(),
+                    self.rebalance_stats.is_some()
+                        && self.best_rebalance_trigger.is_none()
+                        && !self.shows_analysis(),
                     "Rebalance statistics",
                 )
                 .clicked()
             {
                 self.best_rebalance_trigger = None;
+                self.clear_analyses();
                 self.recompute_rebalance_stats(true);
             } else if ui
                 .selectable_label(
-                    self.best_rebalance_trigger.is_some(),
+                    self.best_rebalance_trigger.is_some() && !self.shows_analysis(),
                     "Best rebalance strategy",
                 )
                 .clicked()
             {
+                self.clear_analyses();
                 let PaymentData {
                     initial_balance: (_, initial_balance),
                     monthly_payments,
@@ -796,9 +2310,121 @@ impl BalanceApp<'_> {
                         None
                     }
                 };
+            } else if ui
+                .selectable_label(self.frontier.is_some(), "Efficient frontier")
+                .clicked()
+            {
+                self.clear_analyses();
+                self.recompute_frontier();
+            } else {
+                for (view, label) in [
+                    (RollingView::Outcomes, "Rolling windows"),
+                    (RollingView::Cohorts, "Cohorts"),
+                ] {
+                    if ui
+                        .selectable_label(
+                            self.rolling_windows.is_some() && self.rolling.view == view,
+                            label,
+                        )
+                        .clicked()
+                    {
+                        self.clear_analyses();
+                        self.rolling.view = view;
+                        self.recompute_rolling_windows();
+                    }
+                }
             }
         });
-        if let Some(best_trigger) = &self.best_rebalance_trigger {
+        if self.charts.inflation().is_some()
+            && ui
+                .checkbox(
+                    &mut self.charts.real_values,
+                    "Adjust for inflation (purchasing power at the start)",
+                )
+                .changed()
+        {
+            self.update_final_balance();
+        }
+        self.outdated_analysis_ui(ui);
+        if let Some(frontier) = &self.frontier {
+            match frontier {
+                Ok(frontier) => {
+                    ui.horizontal(|ui| {
+                        ui.label("Risk");
+                        egui::ComboBox::from_id_salt("frontier-risk")
+                            .selected_text(self.frontier_risk.to_string())
+                            .show_ui(ui, |ui| {
+                                for risk in [RiskMeasure::Volatility, RiskMeasure::MaxDrawdown] {
+                                    ui.selectable_value(
+                                        &mut self.frontier_risk,
+                                        risk,
+                                        risk.to_string(),
+                                    );
+                                }
+                            });
+                    });
+                    frontier.show(ui, self.frontier_risk);
+                }
+                Err(e) => {
+                    ui.label(format!("{e}"));
+                }
+            }
+        } else if let Some(rolling_windows) = &self.rolling_windows {
+            let mut horizon_changed = false;
+            ui.horizontal(|ui| {
+                let horizon = ui.add(
+                    egui::DragValue::new(&mut self.rolling.n_years)
+                        .range(1..=100)
+                        .prefix("horizon ")
+                        .suffix(" years"),
+                );
+                // each horizon needs a simulation per start month, hence no recomputation while
+                // dragging
+                horizon_changed =
+                    horizon.drag_stopped() || (horizon.changed() && !horizon.dragged());
+                match self.rolling.view {
+                    RollingView::Outcomes => {
+                        egui::ComboBox::from_id_salt("rolling-outcome")
+                            .selected_text(self.rolling.outcome.to_string())
+                            .show_ui(ui, |ui| {
+                                for outcome in [Outcome::FinalBalance, Outcome::Irr] {
+                                    ui.selectable_value(
+                                        &mut self.rolling.outcome,
+                                        outcome,
+                                        outcome.to_string(),
+                                    );
+                                }
+                            });
+                    }
+                    RollingView::Cohorts => {
+                        ui.checkbox(
+                            &mut self.rolling.per_payments,
+                            "Divide by the payments made so far",
+                        );
+                    }
+                }
+            });
+            let shown = rolling_windows
+                .as_ref()
+                .map_err(|e| e.clone())
+                .and_then(|r| {
+                    match self.rolling.view {
+                        RollingView::Outcomes => r.show(ui, self.rolling.outcome)?,
+                        RollingView::Cohorts => {
+                            r.show_cohorts(ui, self.rolling.per_payments, &mut self.rolling.cohorts)
+                        }
+                    }
+                    Ok(())
+                });
+            if let Err(e) = shown {
+                ui.label(format!("{e}"));
+            }
+            if horizon_changed {
+                self.rolling_windows = None;
+                self.analyses_outdated = false;
+                self.recompute_rolling_windows();
+            }
+        } else if let Some(best_trigger) = &self.best_rebalance_trigger {
             egui::Grid::new("best-balance").show(ui, |ui| {
                 ui.label("(best) balance");
                 ui.label("(best) yearly return");
@@ -911,10 +2537,22 @@ impl BalanceApp<'_> {
                 log("download csv");
                 export_csv(&self.charts).unwrap();
             }
+            ui.horizontal(|ui| {
+                returns_export_ui(ui, &mut self.returns_export, &self.charts);
+                if ui.button("Download returns as csv").clicked() {
+                    let exported = self
+                        .charts
+                        .returns_csv(self.returns_export.chart, self.returns_export.kind)
+                        .and_then(|s| export_str(&s, "returns.csv"));
+                    if let Err(e) = exported {
+                        self.status_msg = Some(format!("{e}"));
+                    }
+                }
+            });
         });
         ui.horizontal(|ui| {
             if ui.button("Reset").clicked() {
-                *self = Self::default();
+                self.replace_session(Self::default());
             }
             ui.label("-");
             ui.label("Code on");
@@ -930,12 +2568,15 @@ impl eframe::App for BalanceApp<'_> {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, eframe::APP_KEY, self);
+        self.dataset_cache.save(storage);
     }
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.check_csv_download();
+        self.check_catalog_download();
         self.check_load();
+        self.check_file_import(ctx);
 
         #[cfg(not(target_arch = "wasm32"))] // no File->Quit on web pages!
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -943,7 +2584,7 @@ impl eframe::App for BalanceApp<'_> {
             egui::MenuBar::new().ui(ui, |ui| {
                 ui.menu_button("File", |ui| {
                     if ui.button("Reset").clicked() {
-                        *self = Self::default();
+                        self.replace_session(Self::default());
                     }
                     if ui.button("Quit").clicked() {
                         ctx.send_viewport_cmd(ViewportCommand::Close);
@@ -980,6 +2621,96 @@ impl eframe::App for BalanceApp<'_> {
     }
 }
 
+#[cfg(test)]
+use crate::{date::Date, io::RepairSummary};
+
+#[test]
+fn test_add_imported() {
+    let series = |name: &str| ImportedSeries {
+        name: name.to_string(),
+        dates: vec![Date::new(2020, 1).unwrap(), Date::new(2020, 2).unwrap()],
+        values: vec![1.0, 2.0],
+        summary: RepairSummary::default(),
+        resampling: None,
+    };
+    let origin = Origin::Imported {
+        filename: Some("a.csv".to_string()),
+    };
+    let mut app = BalanceApp::default();
+    // files that are dropped at once arrive one after another
+    app.add_imported(vec![series("a")], Some("a"), origin.clone());
+    app.add_imported(vec![series("b")], Some("b"), origin.clone());
+    app.add_imported(vec![series("c"), series("d")], Some("cd"), origin);
+    let names = app
+        .pending_imports
+        .iter()
+        .map(|c| c.name())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["a", "cd c", "cd d"]);
+    assert_eq!(app.charts.take_tmp().unwrap().chart.name(), "b");
+}
+
+#[test]
+fn test_import_dataset() {
+    let mut app = BalanceApp::default();
+    app.csv_import.options.decimal_comma = true;
+    app.csv_import.options.delimiter = Delimiter::Semicolon;
+    app.csv_import.options.value_kind = ValueKind::SimpleReturns;
+    let csv = "date,value\n2020/01,1.5\n2020/02,2.5\n".to_string();
+    app.import_dataset("MSCI World", Ok(csv), Origin::Unknown);
+    assert_eq!(app.status_msg, None);
+    let chart = app.charts.take_tmp().unwrap().chart;
+    assert_eq!(chart.values(), &vec![1.5, 2.5]);
+}
+
+#[test]
+fn test_analyses_on_request() {
+    let mut app = BalanceApp {
+        frontier: Some(Err(blcerr!("cached"))),
+        ..BalanceApp::default()
+    };
+    let frontier_msg = |app: &BalanceApp<'_>| match &app.frontier {
+        Some(Err(e)) => format!("{e}"),
+        _ => String::new(),
+    };
+    // hidden analyses are shown again without recomputation
+    app.clear_analyses();
+    app.recompute_frontier();
+    assert!(frontier_msg(&app).contains("cached"));
+
+    // changed inputs do not trigger the simulations but hidden analyses are dropped
+    recompute!(app);
+    assert!(app.analyses_outdated);
+    assert!(frontier_msg(&app).contains("cached"));
+    app.clear_analyses();
+    assert!(!app.analyses_outdated);
+    app.recompute_frontier();
+    assert!(!frontier_msg(&app).contains("cached"));
+}
+
+#[test]
+fn test_replace_session() {
+    let dir = std::env::temp_dir().join(format!("balance-session-test-{}", std::process::id()));
+    let mut app = BalanceApp {
+        dataset_cache: DatasetCache::from_dir(Some(dir.clone())),
+        ..BalanceApp::default()
+    };
+    app.dataset_cache
+        .insert("https://example.com/world.csv", "date,value\n2020/01,1.0\n")
+        .unwrap();
+    app.fx_currency = "EUR".to_string();
+    app.catalog_location = "catalog.json".to_string();
+    let shared = BalanceApp {
+        catalog_location: "https://example.com/catalog.json".to_string(),
+        ..BalanceApp::default()
+    };
+    app.replace_session(shared);
+    assert_eq!(app.fx_currency, "");
+    assert_eq!(app.catalog_location, "catalog.json");
+    assert_eq!(app.dataset_cache.len(), 1);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
 #[test]
 fn test_1000() {
     assert_eq!(&space_sep_1000("1000".to_string()), "1000");
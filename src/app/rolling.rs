@@ -0,0 +1,340 @@
+use crate::{blcerr, core_types::BlcResult, date::Date};
+use egui::{Color32, Ui};
+use egui_plot::{Bar, BarChart, GridMark, HLine, Legend, Line, LineStyle, Plot};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, ops::RangeInclusive};
+
+pub const PERCENTILES: [f64; 5] = [5.0, 25.0, 50.0, 75.0, 95.0];
+const N_BINS: usize = 20;
+
+/// Result of the plan that starts in a given month
+#[derive(Clone, Debug)]
+pub struct WindowOutcome {
+    pub start: Date,
+    pub final_balance: f64,
+    /// yearly internal rate of return in percent
+    pub irr: f64,
+    /// portfolio value of each month
+    pub balances: Vec<f64>,
+    /// cumulative payments of each month
+    pub payments: Vec<f64>,
+}
+impl WindowOutcome {
+    /// Portfolio values, optionally divided by the payments made so far to compare plans whose
+    /// payments depend on the start, e.g., due to inflation
+    fn path(&self, per_payments: bool) -> Vec<f64> {
+        if per_payments {
+            self.balances
+                .iter()
+                .zip(self.payments.iter())
+                .map(|(b, p)| b / p)
+                .collect()
+        } else {
+            self.balances.clone()
+        }
+    }
+}
+
+/// Shows the distribution of an outcome or the paths of all start months
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RollingView {
+    #[default]
+    Outcomes,
+    Cohorts,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    #[default]
+    FinalBalance,
+    Irr,
+}
+impl Outcome {
+    fn of(self, window: &WindowOutcome) -> f64 {
+        match self {
+            Outcome::FinalBalance => window.final_balance,
+            Outcome::Irr => window.irr,
+        }
+    }
+}
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Outcome::FinalBalance => "final balance",
+            Outcome::Irr => "internal rate of return [%]",
+        })
+    }
+}
+
+/// Linear interpolation between the closest ranks of sorted values
+fn percentile(sorted: &[f64], perc: f64) -> f64 {
+    let rank = perc / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (rank - lower as f64) * (sorted[upper] - sorted[lower])
+}
+
+#[derive(Clone, Debug)]
+pub struct OutcomeSummary {
+    pub min: f64,
+    pub max: f64,
+    /// values at [`PERCENTILES`]
+    pub percentiles: Vec<f64>,
+    pub worst_start: Date,
+}
+
+/// Outcomes of the plan for every start month with a fixed horizon
+#[derive(Clone, Debug)]
+pub struct RollingWindows {
+    pub n_months: usize,
+    pub windows: Vec<WindowOutcome>,
+}
+impl RollingWindows {
+    pub fn summary(&self, outcome: Outcome) -> BlcResult<OutcomeSummary> {
+        let worst = self
+            .windows
+            .iter()
+            .min_by(|a, b| outcome.of(a).total_cmp(&outcome.of(b)))
+            .ok_or_else(|| blcerr!("there are no rolling windows"))?;
+        let mut sorted = self
+            .windows
+            .iter()
+            .map(|w| outcome.of(w))
+            .collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+        Ok(OutcomeSummary {
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            percentiles: PERCENTILES
+                .iter()
+                .map(|p| percentile(&sorted, *p))
+                .collect(),
+            worst_start: worst.start,
+        })
+    }
+
+    fn histogram(&self, outcome: Outcome, summary: &OutcomeSummary) -> Vec<Bar> {
+        let width = (summary.max - summary.min) / N_BINS as f64;
+        let mut counts = [0usize; N_BINS];
+        for w in &self.windows {
+            let bin = if width > 0.0 {
+                ((outcome.of(w) - summary.min) / width) as usize
+            } else {
+                0
+            };
+            counts[bin.min(N_BINS - 1)] += 1;
+        }
+        counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                Bar::new(summary.min + (i as f64 + 0.5) * width, *count as f64)
+                    .width(width.max(f64::EPSILON))
+            })
+            .collect()
+    }
+
+    /// Values of the paths of all cohorts at [`PERCENTILES`] for each month since their start
+    pub fn envelope(&self, per_payments: bool) -> Vec<Vec<f64>> {
+        let paths = self
+            .windows
+            .iter()
+            .map(|w| w.path(per_payments))
+            .collect::<Vec<_>>();
+        let n_months = paths.iter().map(|p| p.len()).min().unwrap_or(0);
+        let sorted_by_month = (0..n_months)
+            .map(|month| {
+                let mut values = paths.iter().map(|p| p[month]).collect::<Vec<_>>();
+                values.sort_by(f64::total_cmp);
+                values
+            })
+            .collect::<Vec<_>>();
+        PERCENTILES
+            .iter()
+            .map(|perc| {
+                sorted_by_month
+                    .iter()
+                    .map(|values| percentile(values, *perc))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Overlays the paths of all start months aligned at month 0 and highlights the percentiles
+    /// and the selected cohorts
+    pub fn show_cohorts(&self, ui: &mut Ui, per_payments: bool, selected: &mut Vec<Date>) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("cohort-to-add")
+                .selected_text("highlight start month")
+                .show_ui(ui, |ui| {
+                    for w in &self.windows {
+                        if ui
+                            .selectable_label(selected.contains(&w.start), w.start.to_string())
+                            .clicked()
+                            && !selected.contains(&w.start)
+                        {
+                            selected.push(w.start);
+                        }
+                    }
+                });
+            let mut to_remove = None;
+            for (idx, start) in selected.iter().enumerate() {
+                if ui.button(format!("{start} x")).clicked() {
+                    to_remove = Some(idx);
+                }
+            }
+            if let Some(idx) = to_remove {
+                selected.remove(idx);
+            }
+        });
+        let to_points = |path: &[f64]| {
+            path.iter()
+                .enumerate()
+                .map(|(month, v)| [month as f64, *v])
+                .collect::<Vec<_>>()
+        };
+        let envelope = self.envelope(per_payments);
+        let envelope_color = Color32::from_rgb(230, 140, 40);
+        Plot::new("cohort paths")
+            .legend(Legend::default())
+            .height(ui.available_height().clamp(200.0, 500.0))
+            .x_axis_label("months since start")
+            .y_axis_label(if per_payments {
+                "portfolio value / payments"
+            } else {
+                "portfolio value"
+            })
+            .show(ui, |plot_ui| {
+                for w in &self.windows {
+                    plot_ui.line(
+                        Line::new("", to_points(&w.path(per_payments)))
+                            .color(Color32::from_gray(128).gamma_multiply(0.25))
+                            .width(0.5),
+                    );
+                }
+                for (idx, (perc, values)) in PERCENTILES.iter().zip(envelope.iter()).enumerate() {
+                    let line = Line::new(format!("{perc:0.0}% percentile"), to_points(values))
+                        .color(envelope_color);
+                    // the median is in the middle of the percentiles
+                    plot_ui.line(match idx.abs_diff(PERCENTILES.len() / 2) {
+                        0 => line.width(3.0),
+                        1 => line.width(2.0),
+                        _ => line.width(1.5).style(LineStyle::dashed_loose()),
+                    });
+                }
+                for start in selected.iter() {
+                    if let Some(w) = self.windows.iter().find(|w| w.start == *start) {
+                        plot_ui.line(
+                            Line::new(format!("start {start}"), to_points(&w.path(per_payments)))
+                                .width(2.0),
+                        );
+                    }
+                }
+            });
+    }
+
+    pub fn show(&self, ui: &mut Ui, outcome: Outcome) -> BlcResult<()> {
+        let summary = self.summary(outcome)?;
+        egui::Grid::new("grid-rolling-summary").show(ui, |ui| {
+            ui.label("min");
+            for p in PERCENTILES {
+                ui.label(format!("{p:0.0}%"));
+            }
+            ui.label("max");
+            ui.label("worst start");
+            ui.end_row();
+            ui.label(format!("{:0.2}", summary.min));
+            for value in &summary.percentiles {
+                ui.label(format!("{value:0.2}"));
+            }
+            ui.label(format!("{:0.2}", summary.max));
+            ui.label(summary.worst_start.to_string());
+            ui.end_row();
+        });
+        ui.label(format!(
+            "{} of {} plans over {} months, one for each start month",
+            outcome,
+            self.windows.len(),
+            self.n_months
+        ));
+        let height = (ui.available_height() / 2.0).clamp(150.0, 300.0);
+        let starts = self.windows.iter().map(|w| w.start).collect::<Vec<_>>();
+        let x_fmt = move |x: GridMark, _range: &RangeInclusive<f64>| {
+            let i = x.value.round();
+            if (x.value - i).abs() < 1e-6 && i >= 0.0 && (i as usize) < starts.len() {
+                starts[i as usize].to_string()
+            } else {
+                String::new()
+            }
+        };
+        let by_start = self
+            .windows
+            .iter()
+            .enumerate()
+            .map(|(i, w)| [i as f64, outcome.of(w)])
+            .collect::<Vec<_>>();
+        Plot::new("outcome vs start month")
+            .legend(Legend::default())
+            .height(height)
+            .x_axis_formatter(x_fmt)
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(outcome.to_string(), by_start));
+                plot_ui.hline(
+                    HLine::new("median", summary.percentiles[2]).style(LineStyle::dashed_loose()),
+                );
+            });
+        Plot::new("outcome distribution")
+            .height(height)
+            .x_axis_label(outcome.to_string())
+            .y_axis_label("start months")
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new(
+                    "start months",
+                    self.histogram(outcome, &summary),
+                ));
+            });
+        Ok(())
+    }
+}
+
+#[test]
+fn test_rolling_summary() {
+    let start = Date::new(2000, 11).unwrap();
+    let windows = [3.0, 1.0, 2.0, 5.0, 4.0]
+        .iter()
+        .enumerate()
+        .map(|(i, b)| WindowOutcome {
+            start: (start + i).unwrap(),
+            final_balance: *b,
+            irr: -b,
+            balances: vec![1.0, *b],
+            payments: vec![1.0, 2.0],
+        })
+        .collect();
+    let rolling = RollingWindows {
+        n_months: 12,
+        windows,
+    };
+    let summary = rolling.summary(Outcome::FinalBalance).unwrap();
+    assert_eq!((summary.min, summary.max), (1.0, 5.0));
+    assert_eq!(summary.percentiles[2], 3.0);
+    assert!((summary.percentiles[0] - 1.2).abs() < 1e-12);
+    assert_eq!(summary.worst_start, Date::new(2000, 12).unwrap());
+    let bars = rolling.histogram(Outcome::FinalBalance, &summary);
+    assert_eq!(bars.len(), N_BINS);
+    assert_eq!(bars.iter().map(|b| b.value).sum::<f64>(), 5.0);
+    assert_eq!(bars[N_BINS - 1].value, 1.0);
+    let summary = rolling.summary(Outcome::Irr).unwrap();
+    assert_eq!(summary.worst_start, Date::new(2001, 2).unwrap());
+    let envelope = rolling.envelope(false);
+    assert_eq!(envelope.len(), PERCENTILES.len());
+    assert_eq!(envelope[2], vec![1.0, 3.0]);
+    assert_eq!(
+        rolling.envelope(true)[4][1],
+        0.5 * percentile(&[1.0, 2.0, 3.0, 4.0, 5.0], 95.0)
+    );
+    let empty = RollingWindows {
+        n_months: 12,
+        windows: vec![],
+    };
+    assert!(empty.summary(Outcome::Irr).is_err());
+}
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::core_types::BlcResult;
 
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct MutItemList<T: Default> {
     items: Vec<T>,
 }
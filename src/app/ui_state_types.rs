@@ -1,30 +1,32 @@
 use exmex::parse_val;
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::{borrow::Cow, fmt::Display};
 
 use egui::Context;
 
-use crate::compute::{Expr, MonthlyPayments};
+use crate::compute::{Expr, GoalTarget, GoalVariable, InterestRates, MonthlyPayments, ReturnKind};
 use crate::{
     blcerr,
-    compute::yearly_return,
+    compute::{name_positional_variables, yearly_return},
     core_types::{to_blc, BlcResult},
     date::{Date, Interval},
+    io::CsvImportOptions,
 };
 
 use super::ui_mut_itemlist::MutItemList;
 use super::{
-    charts::Chart,
+    charts::{Chart, MatrixKind},
     month_slider::{MonthSlider, MonthSliderPair, SliderState},
+    rolling::{Outcome, RollingView},
 };
 
 #[derive(Debug, Default, Clone)]
 pub enum RestRequestState<'a> {
     #[default]
     None,
-    InProgress(&'a str),
-    Done((&'a str, ehttp::Result<ehttp::Response>)),
+    InProgress(Cow<'a, str>),
+    Done((Cow<'a, str>, ehttp::Result<ehttp::Response>)),
 }
 
 pub enum RestMethod {
@@ -47,9 +49,9 @@ impl<'a> RestRequest<'a> {
         }
     }
     pub fn check(&self) -> (Option<String>, RestRequestState<'a>) {
-        if let RestRequestState::InProgress(s) = self.state {
+        if let RestRequestState::InProgress(s) = &self.state {
             match self.rx.try_recv() {
-                Ok(d) => (None, RestRequestState::Done((s, d))),
+                Ok(d) => (None, RestRequestState::Done((s.clone(), d))),
                 _ => (
                     Some("waiting for REST call...".to_string()),
                     self.state.clone(),
@@ -59,7 +61,13 @@ impl<'a> RestRequest<'a> {
             (None, self.state.clone())
         }
     }
-    pub fn trigger(&mut self, url: &str, name: &'a str, method: RestMethod, ctx: Option<Context>) {
+    pub fn trigger(
+        &mut self,
+        url: &str,
+        name: impl Into<Cow<'a, str>>,
+        method: RestMethod,
+        ctx: Option<Context>,
+    ) {
         let req = match method {
             RestMethod::Get => ehttp::Request::get(url),
             RestMethod::Post(body) => ehttp::Request::post(url, body),
@@ -74,7 +82,7 @@ impl<'a> RestRequest<'a> {
                 ctx.request_repaint();
             }
         });
-        self.state = RestRequestState::InProgress(name);
+        self.state = RestRequestState::InProgress(name.into());
     }
 }
 impl Default for RestRequest<'_> {
@@ -138,6 +146,74 @@ impl Display for VolaAmount {
     }
 }
 
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct RateOverride {
+    pub rate: String,
+    pub interval: MonthSliderPair,
+}
+
+pub struct ParsedCashInput {
+    pub rates: InterestRates,
+    pub start_month: Date,
+    pub n_months: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CashInput {
+    /// default yearly interest rate in percent, can be an expression
+    pub rate: String,
+    pub start_month_slider: MonthSlider,
+    pub n_months: String,
+    pub name: String,
+    pub rate_overrides: MutItemList<RateOverride>,
+}
+impl CashInput {
+    pub fn parse(&self) -> BlcResult<ParsedCashInput> {
+        let parse_rate = |rate: &str| {
+            parse_val::<i32, f64>(rate)
+                .map_err(|e| blcerr!("could not parse interest rate '{rate}'; {e}"))
+        };
+        let overrides = self
+            .rate_overrides
+            .iter()
+            .map(|ro| {
+                let ok_or_date =
+                    |d: Option<Date>| d.ok_or_else(|| blcerr!("no date selected for rate"));
+                Ok((
+                    parse_rate(&ro.rate)?,
+                    Interval::new(
+                        ok_or_date(ro.interval.selected_start_date())?,
+                        ok_or_date(ro.interval.selected_end_date())?,
+                    )?,
+                ))
+            })
+            .collect::<BlcResult<Vec<_>>>()?;
+        Ok(ParsedCashInput {
+            rates: InterestRates::new(parse_rate(&self.rate)?, overrides),
+            start_month: self
+                .start_month_slider
+                .selected_date()
+                .ok_or_else(|| blcerr!("no date selected"))?,
+            n_months: self.n_months.parse().map_err(to_blc)?,
+        })
+    }
+}
+impl Default for CashInput {
+    fn default() -> Self {
+        CashInput {
+            rate: "2.0".to_string(),
+            start_month_slider: MonthSlider::new(
+                Date::new(1970, 1).unwrap(),
+                Date::new(2050, 12).unwrap(),
+                SliderState::Some(480),
+            ),
+            n_months: "360".to_string(),
+            name: "".to_string(),
+            rate_overrides: MutItemList::default(),
+        }
+    }
+}
+
 pub struct ParsedSimInput {
     pub vola: f64,
     pub vola_window: usize,
@@ -204,6 +280,14 @@ pub struct MonthlyPaymentState {
     pub payments: MonthlyPayments,
     pub pay_fields: Vec<String>,
     pub sliders: Vec<MonthSliderPair>,
+    /// user-defined constants as pairs of name and value
+    #[serde(default)]
+    pub constants: MutItemList<(String, String)>,
+    #[serde(default)]
+    pub inflation_indexed: bool,
+    /// false for sessions whose payment expressions refer to variables by position
+    #[serde(default)]
+    pub variables_by_name: bool,
 }
 impl MonthlyPaymentState {
     fn new() -> Self {
@@ -213,9 +297,25 @@ impl MonthlyPaymentState {
             payments: MonthlyPayments::from_single_payment(parse_val(&payment_str).unwrap()),
             pay_fields: vec![payment_str],
             sliders: vec![],
+            constants: MutItemList::default(),
+            inflation_indexed: false,
+            variables_by_name: true,
         }
     }
     fn parse(&mut self) -> BlcResult<()> {
+        if !self.variables_by_name {
+            let constants = self
+                .constants
+                .iter()
+                .map(|(name, _)| name.trim())
+                .collect::<Vec<_>>();
+            self.pay_fields = self
+                .pay_fields
+                .iter()
+                .map(|ps| name_positional_variables(ps, &constants))
+                .collect::<BlcResult<Vec<_>>>()?;
+            self.variables_by_name = true;
+        }
         let payments = self
             .pay_fields
             .iter()
@@ -233,11 +333,24 @@ impl MonthlyPaymentState {
                 )
             })
             .collect::<BlcResult<Vec<Interval>>>()?;
+        let constants = self
+            .constants
+            .iter()
+            .map(|(name, value)| {
+                let value = value
+                    .replace(' ', "")
+                    .parse::<f64>()
+                    .map_err(|_| blcerr!("could not parse value of constant '{name}'"))?;
+                Ok((name.trim().to_string(), value))
+            })
+            .collect::<BlcResult<Vec<_>>>()?;
         self.payments = if intervals.is_empty() && payments.len() == 1 {
             MonthlyPayments::from_single_payment(payments[0].clone())
         } else {
             MonthlyPayments::from_intervals(payments, intervals)?
-        };
+        }
+        .with_constants(constants)?
+        .indexed_to_inflation(self.inflation_indexed);
         Ok(())
     }
 }
@@ -280,26 +393,227 @@ impl Default for PaymentData {
     }
 }
 
+pub struct ParsedGoalSeek {
+    pub target: GoalTarget,
+    pub variable: GoalVariable,
+    pub chart_idx: usize,
+    pub success_probability: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoalSeekInput {
+    pub variable: GoalVariable,
+    /// index of the price development whose return is solved for
+    pub chart_idx: usize,
+    pub target_is_yearly_return: bool,
+    pub target: String,
+    /// in percent, empty for a deterministic computation
+    pub success_probability: String,
+}
+impl GoalSeekInput {
+    pub fn parse(&self) -> BlcResult<ParsedGoalSeek> {
+        let target = self
+            .target
+            .replace(' ', "")
+            .parse()
+            .map_err(|_| blcerr!("could not parse target {}", self.target))?;
+        let success_probability = if self.success_probability.trim().is_empty() {
+            None
+        } else {
+            let p: f64 = self.success_probability.trim().parse().map_err(to_blc)?;
+            if !(0.0..=100.0).contains(&p) {
+                Err(blcerr!("success probability needs to be between 0 and 100"))?;
+            }
+            Some(p / 100.0)
+        };
+        Ok(ParsedGoalSeek {
+            target: if self.target_is_yearly_return {
+                GoalTarget::YearlyReturn(target)
+            } else {
+                GoalTarget::FinalBalance(target)
+            },
+            variable: self.variable,
+            chart_idx: self.chart_idx,
+            success_probability,
+        })
+    }
+}
+impl Default for GoalSeekInput {
+    fn default() -> Self {
+        GoalSeekInput {
+            variable: GoalVariable::MonthlyPayment,
+            chart_idx: 0,
+            target_is_yearly_return: false,
+            target: "500000.0".to_string(),
+            success_probability: "".to_string(),
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct CsvImportInput {
+    /// pasted file content, not persisted to keep sessions and sharelinks small
+    #[serde(skip)]
+    pub text: String,
+    pub options: CsvImportOptions,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct ReturnsExportInput {
+    /// persisted chart, `None` for the portfolio value
+    pub chart: Option<usize>,
+    pub kind: ReturnKind,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct DerivedChartInput {
+    /// the expression is used if empty
+    pub name: String,
+    pub expression: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct LeverageInput {
+    /// persisted chart the product is built on
+    pub underlying: usize,
+    /// e.g., 2 for a 2x and -1 for an inverse product
+    pub leverage: f64,
+    /// yearly borrowing cost in percent
+    pub borrowing_cost: f64,
+    /// yearly expense ratio in percent
+    pub ter: f64,
+}
+impl Default for LeverageInput {
+    fn default() -> Self {
+        LeverageInput {
+            underlying: 0,
+            leverage: 2.0,
+            borrowing_cost: 3.0,
+            ter: 0.6,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SpliceInput {
+    /// persisted chart that is extended backwards
+    pub target: usize,
+    /// persisted chart used as proxy, `None` for a random walk calibrated to the target
+    pub proxy: Option<usize>,
+    /// length of the calibrated random walk
+    pub n_months: usize,
+}
+impl Default for SpliceInput {
+    fn default() -> Self {
+        SpliceInput {
+            target: 0,
+            proxy: None,
+            n_months: 120,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CorrelationInput {
+    pub kind: MatrixKind,
+    /// persisted charts whose rolling correlation is shown
+    pub pair: (usize, usize),
+    /// rolling window in months
+    pub window: usize,
+}
+impl Default for CorrelationInput {
+    fn default() -> Self {
+        CorrelationInput {
+            kind: MatrixKind::Correlation,
+            pair: (0, 1),
+            window: 36,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RollingInput {
+    /// horizon of each plan
+    pub n_years: usize,
+    pub outcome: Outcome,
+    #[serde(default)]
+    pub view: RollingView,
+    /// if true, cohort paths are divided by the payments made so far
+    #[serde(default)]
+    pub per_payments: bool,
+    /// start months of the highlighted cohorts
+    #[serde(default)]
+    pub cohorts: Vec<Date>,
+}
+impl Default for RollingInput {
+    fn default() -> Self {
+        RollingInput {
+            n_years: 15,
+            outcome: Outcome::FinalBalance,
+            view: RollingView::Outcomes,
+            per_payments: false,
+            cohorts: vec![],
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct FinalBalance {
     pub final_balance: f64,
     pub yearly_return_perc: Option<f64>, // Option since this might be NAN and json makes NANs to nulls
     pub total_payments: f64,
+    #[serde(default)]
+    pub sharpe_ratio: Option<f64>,
+    /// final balance if all payments went into the savings account
+    #[serde(default)]
+    pub savings_final_balance: Option<f64>,
+    /// expense ratios and advisory fees paid until the end
+    #[serde(default)]
+    pub total_fees: Option<f64>,
+    /// dividends paid out until the end
+    #[serde(default)]
+    pub total_payouts: Option<f64>,
 }
 impl FinalBalance {
-    pub fn from_chart(price_dev: &Chart, payments: &Chart, n_months: usize) -> BlcResult<Self> {
+    /// The yearly return takes dividend payouts into account
+    pub fn from_chart(
+        price_dev: &Chart,
+        payments: &Chart,
+        payouts: Option<&Chart>,
+        n_months: usize,
+    ) -> BlcResult<Self> {
         if let (Some(final_balance), Some(total_payments)) = (
             price_dev.values().iter().last().copied(),
             payments.values().iter().last().copied(),
         ) {
-            let (yearly_return_perc, _) = yearly_return(total_payments, n_months, final_balance);
+            let total_payouts = payouts.and_then(|po| po.values().last().copied());
+            let (yearly_return_perc, _) = yearly_return(
+                total_payments - total_payouts.unwrap_or(0.0),
+                n_months,
+                final_balance,
+            );
             Ok(FinalBalance {
                 final_balance,
                 yearly_return_perc: Some(yearly_return_perc),
                 total_payments,
+                sharpe_ratio: None,
+                savings_final_balance: None,
+                total_fees: None,
+                total_payouts,
             })
         } else {
             Err(blcerr!("cannot compute final balance from empty chart"))
         }
     }
 }
+
+#[test]
+fn test_positional_payment_variables() {
+    let mut state = serde_json::to_value(MonthlyPaymentState::new()).unwrap();
+    state["pay_fields"] = serde_json::json!(["x / 100"]);
+    state.as_object_mut().unwrap().remove("variables_by_name");
+    let mut state: MonthlyPaymentState = serde_json::from_value(state).unwrap();
+    state.parse().unwrap();
+    assert!(state.variables_by_name);
+    assert_eq!(state.pay_fields, vec!["current_balance / 100".to_string()]);
+}
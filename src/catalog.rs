@@ -0,0 +1,197 @@
+use crate::{
+    blcerr,
+    core_types::{to_blc, BlcResult},
+    date::parse_month,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+pub const BUILTIN_CATALOG_LOCATION: &str = "https://www.bertiqwerty.com/data/catalog.json";
+// const BUILTIN_CATALOG_LOCATION: &str = "http://localhost:8000/data/catalog.json";
+
+/// Datasets that are available without loading a catalog. Relative URLs are resolved against
+/// [`BUILTIN_CATALOG_LOCATION`].
+const BUILTIN_CATALOG: &str = r#"{
+    "datasets": [
+        {"name": "MSCI ACWI", "url": "msciacwi.csv", "source": "curvo"},
+        {"name": "MSCI World", "url": "msciworld.csv", "source": "curvo"},
+        {"name": "MSCI EM", "url": "msciem.csv", "source": "curvo"},
+        {"name": "MSCI Europe", "url": "mscieurope.csv", "source": "curvo"},
+        {"name": "S&P 500", "url": "sandp500.csv", "source": "curvo"}
+    ],
+    "sources": [
+        {"id": "curvo", "name": "Backtest by Curvo", "url": "https://curvo.eu/backtest/faq#is-it-free"}
+    ]
+}"#;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexType {
+    /// prices without dividends
+    Price,
+    /// dividends reinvested after withholding taxes
+    NetReturn,
+    /// dividends reinvested without taxes
+    GrossReturn,
+}
+impl Display for IndexType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IndexType::Price => "price index",
+            IndexType::NetReturn => "net total return index",
+            IndexType::GrossReturn => "gross total return index",
+        })
+    }
+}
+
+/// Attribution of the data provider that is shown next to the datasets
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub url: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Dataset {
+    pub name: String,
+    /// absolute or relative to the location of the catalog
+    pub url: String,
+    /// currency code, empty if unknown
+    #[serde(default)]
+    pub currency: String,
+    #[serde(default)]
+    pub index_type: Option<IndexType>,
+    /// id of an entry in the sources of the catalog
+    #[serde(default)]
+    pub source: String,
+    /// first month of the data, e.g., `1999/01`
+    #[serde(default)]
+    pub start: String,
+    /// last month of the data
+    #[serde(default)]
+    pub end: String,
+}
+impl Dataset {
+    /// Description of the dataset for tooltips
+    pub fn details(&self, catalog: &Catalog) -> String {
+        let mut details = vec![];
+        if !self.currency.is_empty() {
+            details.push(format!("currency {}", self.currency));
+        }
+        if let Some(index_type) = self.index_type {
+            details.push(index_type.to_string());
+        }
+        if !self.start.is_empty() || !self.end.is_empty() {
+            details.push(format!("from {} to {}", self.start, self.end));
+        }
+        if let Some(source) = catalog.source(&self.source) {
+            details.push(format!("source {}", source.name));
+        }
+        details.join("\n")
+    }
+}
+
+/// List of historical datasets that can be downloaded, given as JSON manifest
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Catalog {
+    pub datasets: Vec<Dataset>,
+    #[serde(default)]
+    pub sources: Vec<Source>,
+    /// URL or path the catalog was loaded from, relative dataset URLs are resolved against it
+    #[serde(default)]
+    pub location: String,
+}
+impl Catalog {
+    /// Parses and validates a manifest that has been loaded from `location`
+    pub fn from_json(json: &str, location: &str) -> BlcResult<Self> {
+        let mut catalog: Catalog = serde_json::from_str(json).map_err(to_blc)?;
+        catalog.location = location.to_string();
+        for dataset in &catalog.datasets {
+            if dataset.name.is_empty() || dataset.url.is_empty() {
+                Err(blcerr!("datasets need a name and a url"))?;
+            }
+            for month in [&dataset.start, &dataset.end] {
+                if !month.is_empty() {
+                    parse_month(month)
+                        .map_err(|e| blcerr!("dataset {}, {}", dataset.name, e.msg))?;
+                }
+            }
+            if !dataset.source.is_empty() && catalog.source(&dataset.source).is_none() {
+                Err(blcerr!(
+                    "dataset {} has unknown source {}",
+                    dataset.name,
+                    dataset.source
+                ))?;
+            }
+        }
+        Ok(catalog)
+    }
+
+    pub fn source(&self, id: &str) -> Option<&Source> {
+        self.sources.iter().find(|s| s.id == id)
+    }
+
+    /// URL or path of a dataset
+    pub fn resolve(&self, dataset: &Dataset) -> String {
+        if is_remote(&dataset.url) || dataset.url.starts_with('/') {
+            dataset.url.clone()
+        } else {
+            match self.location.rfind(['/', '\\']) {
+                Some(idx) => format!("{}{}", &self.location[..(idx + 1)], dataset.url),
+                None => dataset.url.clone(),
+            }
+        }
+    }
+}
+impl Default for Catalog {
+    fn default() -> Self {
+        Catalog::from_json(BUILTIN_CATALOG, BUILTIN_CATALOG_LOCATION).unwrap()
+    }
+}
+
+/// Locations that are not remote are local files
+pub fn is_remote(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+#[test]
+fn test_builtin_catalog() {
+    let catalog = Catalog::default();
+    assert_eq!(catalog.datasets.len(), 5);
+    assert_eq!(
+        catalog.resolve(&catalog.datasets[1]),
+        "https://www.bertiqwerty.com/data/msciworld.csv"
+    );
+    assert!(catalog.datasets[0]
+        .details(&catalog)
+        .contains("Backtest by Curvo"));
+}
+
+#[test]
+fn test_catalog_from_json() {
+    let json = r#"{"datasets": [
+        {"name": "World", "url": "world.csv", "currency": "EUR", "index_type": "net_return",
+         "start": "1999/01", "end": "2023-12"},
+        {"name": "EM", "url": "https://mirror.example/em.csv"}
+    ]}"#;
+    let catalog = Catalog::from_json(json, "data/catalog.json").unwrap();
+    assert_eq!(catalog.datasets[0].index_type, Some(IndexType::NetReturn));
+    assert_eq!(catalog.resolve(&catalog.datasets[0]), "data/world.csv");
+    assert_eq!(
+        catalog.resolve(&catalog.datasets[1]),
+        "https://mirror.example/em.csv"
+    );
+    let details = catalog.datasets[0].details(&catalog);
+    assert!(details.contains("currency EUR"));
+    assert!(details.contains("from 1999/01 to 2023-12"));
+    let catalog = Catalog::from_json(json, "catalog.json").unwrap();
+    assert_eq!(catalog.resolve(&catalog.datasets[0]), "world.csv");
+
+    let invalid_month = r#"{"datasets": [{"name": "World", "url": "w.csv", "start": "1999"}]}"#;
+    assert!(Catalog::from_json(invalid_month, "").is_err());
+    let unknown_source = r#"{"datasets": [{"name": "World", "url": "w.csv", "source": "x"}]}"#;
+    assert!(Catalog::from_json(unknown_source, "").is_err());
+    assert!(Catalog::from_json("{}", "").is_err());
+}
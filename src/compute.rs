@@ -4,7 +4,7 @@ use crate::{
     core_types::{to_blc, BlcError, BlcResult},
     date::{Date, Interval},
 };
-use exmex::{Express, FlatExVal, Val};
+use exmex::{parse_val, Express, FlatExVal, Val};
 use rand::{rngs::StdRng, SeedableRng};
 use rand_distr::{Distribution, Normal};
 use serde::{Deserialize, Serialize};
@@ -31,12 +31,168 @@ fn eval(expr: &Expr, vars: &[Val<i32, f64>]) -> BlcResult<f64> {
     Ok(x)
 }
 
+/// Names and descriptions of the variables that can be used in payment expressions
+pub const PAYMENT_VARIABLES: [(&str, &str); 12] = [
+    (
+        "current_balance",
+        "portfolio value at the beginning of the month",
+    ),
+    ("initial_balance", "initial capital"),
+    ("month", "number of months since the start"),
+    (
+        "years_elapsed",
+        "number of years since the start, i.e., month / 12",
+    ),
+    ("calendar_year", "year of the current month, e.g., 2024"),
+    ("calendar_month", "month of the year from 1 to 12"),
+    (
+        "value_i",
+        "value of the i-th price development, e.g., value_0",
+    ),
+    (
+        "weight_i",
+        "current fraction of the i-th price development, e.g., weight_1",
+    ),
+    ("peak_balance", "highest portfolio value so far"),
+    (
+        "drawdown",
+        "relative distance of the current balance to the peak balance, between 0 and 1",
+    ),
+    (
+        "total_payments",
+        "initial capital plus all monthly payments so far",
+    ),
+    (
+        "cpi_factor",
+        "consumer prices relative to the start, 1 without inflation series",
+    ),
+];
+
+/// Variables that depend only on the time and are available in all expressions
+fn time_var(name: &str, month: usize, date: Date) -> Option<f64> {
+    match name {
+        "month" => Some(month as f64),
+        "years_elapsed" => Some(month as f64 / 12.0),
+        "calendar_year" => Some(date.year() as f64),
+        "calendar_month" => Some(date.month() as f64),
+        _ => None,
+    }
+}
+
+fn security_var_idx(name: &str, prefix: &str) -> Option<usize> {
+    name.strip_prefix(prefix).and_then(|idx| idx.parse().ok())
+}
+
+fn is_payment_variable(name: &str) -> bool {
+    PAYMENT_VARIABLES.iter().any(|(var, _)| *var == name)
+        || security_var_idx(name, "value_").is_some()
+        || security_var_idx(name, "weight_").is_some()
+}
+
+/// Names of the variables of a payment expression of sessions that were saved before variables
+/// were referred to by name. Back then, the first variable in alphabetical order was the current
+/// balance and the second one the initial balance. Expressions that use a predefined variable or
+/// one of `constants` are returned unchanged.
+pub fn name_positional_variables(expr: &str, constants: &[&str]) -> BlcResult<String> {
+    let parsed = parse_val::<i32, f64>(expr).map_err(to_blc)?;
+    let var_names = parsed.var_names();
+    if var_names.len() > 2
+        || var_names
+            .iter()
+            .any(|name| is_payment_variable(name) || constants.contains(&name.as_str()))
+    {
+        return Ok(expr.to_string());
+    }
+    let renamed = |name: &str| {
+        var_names
+            .iter()
+            .position(|var| var == name)
+            .map(|idx| ["current_balance", "initial_balance"][idx])
+    };
+    let mut res = String::with_capacity(expr.len());
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_alphabetic() || c == '_' {
+            let mut ident = c.to_string();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                ident.push(c);
+                chars.next();
+            }
+            res.push_str(renamed(&ident).unwrap_or(&ident));
+        } else {
+            res.push(c);
+        }
+    }
+    Ok(res)
+}
+
+/// State of the portfolio at the beginning of a month that is exposed to payment expressions
+#[derive(Clone, Copy, Debug)]
+pub struct PaymentVars<'a> {
+    pub initial_balance: f64,
+    /// number of months since the start
+    pub month: usize,
+    pub date: Date,
+    /// current values of the individual securities
+    pub values: &'a [f64],
+    /// outstanding margin loan, the current balance is the equity, i.e., the values minus the debt
+    pub debt: f64,
+    /// highest equity so far
+    pub peak_balance: f64,
+    /// initial balance plus monthly payments up to now
+    pub total_payments: f64,
+}
+impl PaymentVars<'_> {
+    fn current_balance(&self) -> f64 {
+        self.values.iter().sum::<f64>() - self.debt
+    }
+    pub fn get(&self, name: &str) -> Option<f64> {
+        let current_balance = self.current_balance();
+        match name {
+            "current_balance" => Some(current_balance),
+            "initial_balance" => Some(self.initial_balance),
+            "peak_balance" => Some(self.peak_balance),
+            "drawdown" => Some(if self.peak_balance > 0.0 {
+                (1.0 - current_balance / self.peak_balance).max(0.0)
+            } else {
+                0.0
+            }),
+            "total_payments" => Some(self.total_payments),
+            _ => {
+                if let Some(x) = time_var(name, self.month, self.date) {
+                    Some(x)
+                } else if let Some(idx) = security_var_idx(name, "value_") {
+                    self.values.get(idx).copied()
+                } else if let Some(idx) = security_var_idx(name, "weight_") {
+                    self.values.get(idx).map(|v| {
+                        if current_balance != 0.0 {
+                            v / current_balance
+                        } else {
+                            0.0
+                        }
+                    })
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct MonthlyPayments {
-    // payment per interval, the expression can evaluate the variables current_balance and
-    // initial_balance.
+    // payment per interval, the expression can evaluate the variables listed in
+    // PAYMENT_VARIABLES and user-defined constants.
     payments: Vec<Expr>,
     intervals: Vec<Option<Interval>>,
+    #[serde(default)]
+    constants: Vec<(String, f64)>,
+    /// if true, payments are multiplied by the inflation since the start
+    #[serde(default)]
+    inflation_indexed: bool,
+    /// consumer price index starting at the first month of the computation
+    #[serde(skip)]
+    price_index: Vec<f64>,
 }
 impl MonthlyPayments {
     pub fn from_intervals(payments: Vec<Expr>, intervals: Vec<Interval>) -> BlcResult<Self> {
@@ -46,6 +202,7 @@ impl MonthlyPayments {
             Ok(MonthlyPayments {
                 payments,
                 intervals: intervals.into_iter().map(Some).collect(),
+                ..Self::default()
             })
         }
     }
@@ -53,24 +210,147 @@ impl MonthlyPayments {
         MonthlyPayments {
             payments: vec![payment],
             intervals: vec![None],
+            ..Self::default()
+        }
+    }
+    /// The same payment every month, e.g., to solve for the required monthly payment
+    pub fn from_constant(payment: f64) -> Self {
+        MonthlyPayments {
+            payments: vec![parse_val("payment").unwrap()],
+            intervals: vec![None],
+            constants: vec![("payment".to_string(), payment)],
+            ..Self::default()
         }
     }
-    /// Computes all payments of the current_date
-    pub fn compute(&self, current_date: Date, vars: &[Val<i32, f64>]) -> BlcResult<f64> {
+    pub fn indexed_to_inflation(mut self, inflation_indexed: bool) -> Self {
+        self.inflation_indexed = inflation_indexed;
+        self
+    }
+    /// Sets the consumer price index of the computation's timeline
+    pub fn with_price_index(mut self, price_index: Vec<f64>) -> Self {
+        self.price_index = price_index;
+        self
+    }
+    fn inflation(&self, month: usize) -> f64 {
+        match (self.price_index.first(), self.price_index.get(month)) {
+            (Some(first), Some(current)) => current / first,
+            _ => 1.0,
+        }
+    }
+    /// Adds user-defined constants such as `salary` that can be used in payment expressions
+    pub fn with_constants(mut self, constants: Vec<(String, f64)>) -> BlcResult<Self> {
+        for (i, (name, _)) in constants.iter().enumerate() {
+            let is_identifier = name
+                .chars()
+                .next()
+                .map(|c| c.is_alphabetic() || c == '_')
+                .unwrap_or(false)
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            if !is_identifier {
+                Err(blcerr!("'{name}' is not a valid constant name"))?;
+            }
+            if is_payment_variable(name) {
+                Err(blcerr!(
+                    "'{name}' is a predefined variable and cannot be a constant"
+                ))?;
+            }
+            if constants[..i].iter().any(|(other, _)| other == name) {
+                Err(blcerr!("constant '{name}' is defined twice"))?;
+            }
+        }
+        self.constants = constants;
+        Ok(self)
+    }
+    fn resolve_vars(&self, expr: &Expr, vars: &PaymentVars<'_>) -> BlcResult<Vec<Val<i32, f64>>> {
+        expr.var_names()
+            .iter()
+            .map(|name| {
+                self.constants
+                    .iter()
+                    .find(|(c, _)| c == name)
+                    .map(|(_, v)| *v)
+                    .or_else(|| {
+                        if name == "cpi_factor" {
+                            Some(self.inflation(vars.month))
+                        } else {
+                            vars.get(name)
+                        }
+                    })
+                    .map(Val::Float)
+                    .ok_or_else(|| blcerr!("unknown variable '{name}' in payment expression"))
+            })
+            .collect()
+    }
+    /// Computes all payments of the month given by `vars.date`
+    pub fn compute(&self, vars: &PaymentVars<'_>) -> BlcResult<f64> {
         self.payments
             .iter()
             .zip(self.intervals.iter())
             .filter(|(_, inter)| {
                 if let Some(inter) = inter {
-                    inter.contains(current_date)
+                    inter.contains(vars.date)
                 } else {
                     true
                 }
             })
-            .map(|(pay, _)| eval(pay, vars))
+            .map(|(pay, _)| eval(pay, &self.resolve_vars(pay, vars)?))
             .try_fold::<f64, _, _>(0.0, |x, y| y.map(|y| x + y))
+            .map(|payment| {
+                if self.inflation_indexed {
+                    payment * self.inflation(vars.month)
+                } else {
+                    payment
+                }
+            })
+    }
+}
+/// Yearly interest rates in percent of a deterministic asset such as a savings account. Rates of
+/// later intervals override earlier ones. The default rate applies to all months that are not
+/// covered by any interval. Rate expressions can use the variables `month`, `years_elapsed`,
+/// `calendar_year`, and `calendar_month`.
+#[derive(Debug, Clone)]
+pub struct InterestRates {
+    default_rate: Expr,
+    overrides: Vec<(Expr, Interval)>,
+}
+impl InterestRates {
+    pub fn new(default_rate: Expr, overrides: Vec<(Expr, Interval)>) -> Self {
+        InterestRates {
+            default_rate,
+            overrides,
+        }
+    }
+    fn rate(&self, month: usize, date: Date) -> BlcResult<f64> {
+        let expr = self
+            .overrides
+            .iter()
+            .rev()
+            .find(|(_, inter)| inter.contains(date))
+            .map(|(expr, _)| expr)
+            .unwrap_or(&self.default_rate);
+        let vars = expr
+            .var_names()
+            .iter()
+            .map(|name| {
+                time_var(name, month, date)
+                    .map(Val::Float)
+                    .ok_or_else(|| blcerr!("unknown variable '{name}' in interest rate"))
+            })
+            .collect::<BlcResult<Vec<_>>>()?;
+        eval(expr, &vars)
+    }
+    /// Price development with `n_months` months of interest after `start_date`
+    pub fn price_dev(&self, start_date: Date, n_months: usize) -> BlcResult<Vec<f64>> {
+        let start_price = 1e5;
+        let mut res = vec![start_price; n_months + 1];
+        for i in 1..(n_months + 1) {
+            let rate = self.rate(i, (start_date + i)?)?;
+            res[i] = res[i - 1] * (1.0 + rate / 100.0).powf(1.0 / 12.0);
+        }
+        Ok(res)
     }
 }
+
 pub fn yearly_return(total_payments: f64, n_months: usize, final_balance: f64) -> (f64, f64) {
     let total_yield = final_balance / total_payments;
     if total_payments < 0.0 {
@@ -116,33 +396,175 @@ impl RebalanceData<'_> {
             false
         }
     }
-    fn is_triggered_by_deviation(&self, values: &[f64]) -> bool {
+    fn is_triggered_by_deviation(&self, values: &[f64], fractions: &[f64]) -> bool {
         if let Some(max_dev) = self.trigger.deviation {
             let portfolio_value = values.iter().sum::<f64>();
             let deviation = values
                 .iter()
-                .zip(self.fractions)
-                .map(|(b, fr)| ((fr - b / portfolio_value).abs()))
+                .zip(fractions)
+                .map(|(b, fr)| (fr - b / portfolio_value).abs())
                 .max_by(|a, b| a.partial_cmp(b).unwrap());
             deviation > Some(max_dev)
         } else {
             false
         }
     }
-    pub fn is_triggered(&self, values: &[f64], month: usize) -> bool {
+    /// `fractions` are the targets of the securities that are available
+    pub fn is_triggered(&self, values: &[f64], fractions: &[f64], month: usize) -> bool {
         if self.trigger.interval.is_some() && self.trigger.deviation.is_some() {
-            self.is_triggered_by_interval(month) && self.is_triggered_by_deviation(values)
+            self.is_triggered_by_interval(month)
+                && self.is_triggered_by_deviation(values, fractions)
         } else {
-            self.is_triggered_by_interval(month) || self.is_triggered_by_deviation(values)
+            self.is_triggered_by_interval(month)
+                || self.is_triggered_by_deviation(values, fractions)
+        }
+    }
+}
+/// Converts a yearly fee in percent into the fraction of the value that is charged per month
+fn monthly_fee_rate(yearly_fee_perc: f64) -> f64 {
+    1.0 - (1.0 - yearly_fee_perc / 100.0).powf(1.0 / 12.0)
+}
+
+/// Yearly fees in percent that are charged monthly on the current value
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Fees<'a> {
+    /// expense ratios (TER) of the individual securities, no expense ratio if empty
+    pub expense_ratios: &'a [f64],
+    /// charged on the total portfolio value, e.g., by an advisor or a robo-advisor
+    pub advisory_fee: f64,
+}
+impl Fees<'_> {
+    fn expense_ratio(&self, i_security: usize) -> f64 {
+        self.expense_ratios.get(i_security).copied().unwrap_or(0.0)
+    }
+    /// Deducts the fees of one month from the values and returns the deducted amount
+    fn deduct(&self, values: &mut [f64]) -> f64 {
+        let advisory_rate = monthly_fee_rate(self.advisory_fee);
+        let mut fees_this_month = 0.0;
+        for (i_security, value) in values.iter_mut().enumerate() {
+            let rate = 1.0
+                - (1.0 - monthly_fee_rate(self.expense_ratio(i_security))) * (1.0 - advisory_rate);
+            let fee = *value * rate;
+            *value -= fee;
+            fees_this_month += fee;
         }
+        fees_this_month
     }
 }
+
+/// Monthly dividend yield from a yearly yield in percent after taxes on dividends in percent
+pub fn net_monthly_dividend_yield(yearly_yield_perc: f64, tax_rate_perc: f64) -> f64 {
+    yearly_yield_perc / 1200.0 * (1.0 - tax_rate_perc / 100.0)
+}
+
+/// Adds reinvested dividends to a price index to obtain a total-return index. The net monthly
+/// yields refer to the price of the previous month.
+pub fn with_reinvested_dividends(price_dev: &[f64], net_monthly_yields: &[f64]) -> Vec<f64> {
+    let mut total_return = price_dev.first().copied().unwrap_or(0.0);
+    iter::once(total_return)
+        .chain(
+            price_dev
+                .iter()
+                .zip(price_dev.iter().skip(1))
+                .zip(net_monthly_yields.iter().skip(1))
+                .map(|((p_prev, p), y)| {
+                    total_return *= (p + p_prev * y) / p_prev;
+                    total_return
+                }),
+        )
+        .take(price_dev.len())
+        .collect()
+}
+
+/// The portfolio is held at a target leverage that is financed by a loan
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Margin {
+    /// value of the assets divided by the equity, e.g., 1.5
+    pub leverage: f64,
+    /// yearly interest rate of the loan in percent
+    pub interest: f64,
+    /// minimum equity relative to the assets, below the assets are sold until the target
+    /// leverage is restored
+    pub maintenance: f64,
+}
+impl Margin {
+    pub fn new(leverage: f64, interest: f64, maintenance: f64) -> BlcResult<Self> {
+        if leverage < 1.0 {
+            Err(blcerr!("leverage needs to be at least 1"))?;
+        }
+        if !(0.0..1.0).contains(&maintenance) || maintenance * leverage >= 1.0 {
+            Err(blcerr!(
+                "maintenance ratio needs to be below the equity ratio 1/leverage"
+            ))?;
+        }
+        Ok(Margin {
+            leverage,
+            interest,
+            maintenance,
+        })
+    }
+    fn monthly_interest_factor(&self) -> f64 {
+        (1.0 + self.interest / 100.0).powf(1.0 / 12.0)
+    }
+    /// Accrues the interest of one month and sells assets proportionally to repay the debt if
+    /// the equity falls below the maintenance ratio. Returns true in case of a margin call.
+    fn accrue_and_check(&self, values: &mut [f64], debt: &mut f64) -> bool {
+        *debt *= self.monthly_interest_factor();
+        let total = values.iter().sum::<f64>();
+        let equity = total - *debt;
+        if *debt > 0.0 && equity < self.maintenance * total {
+            let target_total = (self.leverage * equity).max(0.0);
+            let scale = if total > 0.0 {
+                target_total / total
+            } else {
+                0.0
+            };
+            values.iter_mut().for_each(|v| *v *= scale);
+            *debt -= total - target_total;
+            true
+        } else {
+            false
+        }
+    }
+    /// Assets at the target leverage, the debt is adapted accordingly. Without equity, nothing
+    /// is borrowed.
+    fn relever(&self, total: f64, debt: &mut f64) -> f64 {
+        let equity = total - *debt;
+        if equity > 0.0 {
+            *debt = (self.leverage - 1.0) * equity;
+            self.leverage * equity
+        } else {
+            total
+        }
+    }
+}
+
+/// Dividends that are paid out instead of reinvested
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DividendPayouts<'a> {
+    /// net monthly yields per security and month, empty for securities without payouts
+    pub net_monthly_yields: &'a [&'a [f64]],
+}
+impl DividendPayouts<'_> {
+    fn payout(&self, i_security: usize, i_month: usize, value: f64) -> f64 {
+        self.net_monthly_yields
+            .get(i_security)
+            .and_then(|yields| yields.get(i_month))
+            .map(|y| value * y)
+            .unwrap_or(0.0)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RebalanceData<'a> {
     /// after how many months is re-balancing applied
     pub trigger: RebalanceTrigger,
     /// fractions of the indices
     pub fractions: &'a [f64],
+    pub fees: Fees<'a>,
+    pub dividend_payouts: DividendPayouts<'a>,
+    /// loan to hold the portfolio at a leverage
+    pub margin: Option<Margin>,
 }
 impl<'a> RebalanceData<'a> {
     fn wo_trigger(other: Self) -> Self {
@@ -152,6 +574,9 @@ impl<'a> RebalanceData<'a> {
                 deviation: None,
             },
             fractions: other.fractions,
+            fees: other.fees,
+            dividend_payouts: other.dividend_payouts,
+            margin: other.margin,
         }
     }
     fn from_fractions(fractions: &'a [f64]) -> Self {
@@ -161,10 +586,36 @@ impl<'a> RebalanceData<'a> {
                 deviation: None,
             },
             fractions,
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         }
     }
 }
 
+/// Target fractions restricted to the securities that have a price in `month`. Price developments
+/// are `NaN` before the inception of a security. The fractions of missing securities are
+/// redistributed proportionally among the available ones.
+fn available_fractions(fractions: &[f64], price_devs: &[&[f64]], month: usize) -> Vec<f64> {
+    let is_available = |i: usize| price_devs[i].get(month).is_some_and(|p| p.is_finite());
+    let n_available = (0..fractions.len()).filter(|i| is_available(*i)).count();
+    let total = (0..fractions.len())
+        .filter(|i| is_available(*i))
+        .map(|i| fractions[i])
+        .sum::<f64>();
+    (0..fractions.len())
+        .map(|i| {
+            if !is_available(i) {
+                0.0
+            } else if total > 0.0 {
+                fractions[i] / total
+            } else {
+                1.0 / n_available as f64
+            }
+        })
+        .collect()
+}
+
 pub fn find_shortestlen<'a>(price_devs: &'a [&'a [f64]]) -> Option<usize> {
     price_devs.iter().map(|pd| pd.len()).min()
 }
@@ -173,13 +624,18 @@ pub fn find_shortestlen<'a>(price_devs: &'a [&'a [f64]]) -> Option<usize> {
 /// Compute the balance given initial values and price developments of securities
 ///
 /// Arguments
-/// * `price_devs`         - developments of the individual securities (e.g., stock prices, index prices, ...) 2d-vector, first axis addresses the security, second axis is the price
+/// * `price_devs`         - developments of the individual securities (e.g., stock prices, index prices, ...) 2d-vector, first axis addresses the security, second axis is the price, `NaN` before the inception of a security
 /// * `initial_capital`    - total amount of initial investment
 /// * `monthly_payments    - monthly payments for each security, e.g., from a savings plan
 /// * `rebalance_interval` - pass if indices are rebalanced
 /// * `start_date`         - needed to check if which monthly payments are due
 ///
-/// Returns an iterator that yields total balance and the sum of all payments per months up to each month
+/// Returns an iterator that yields total balance, the sum of all payments, the sum of all fees,
+/// and the sum of all dividend payouts up to each month. Payouts are cash flows out of the
+/// portfolio, see [`net_payments`]. Securities are phased in by rebalancing in the month of their
+/// inception.
+/// With a margin loan, the balance is the net equity, i.e., the assets minus the debt, and the
+/// leverage is restored at each rebalancing.
 ///
 pub fn compute_balance_over_months<'a>(
     price_devs: &'a [&'a [f64]],
@@ -187,43 +643,90 @@ pub fn compute_balance_over_months<'a>(
     monthly_payments: Option<&'a MonthlyPayments>,
     rebalance_data: RebalanceData<'a>,
     start_date: Date,
-) -> impl Iterator<Item = BlcResult<(f64, f64)>> + 'a {
-    let initial_values = rebalance_data
-        .fractions
+) -> impl Iterator<Item = BlcResult<(f64, f64, f64, f64)>> + 'a {
+    let fractions = available_fractions(rebalance_data.fractions, price_devs, 0);
+    let margin = rebalance_data.margin;
+    let leverage = margin.map_or(1.0, |m| m.leverage);
+    let initial_values = fractions
         .iter()
-        .map(|fr| fr * initial_capital)
+        .map(|fr| fr * initial_capital * leverage)
         .collect::<Vec<f64>>();
+    let initial_debt = (leverage - 1.0) * initial_capital;
     let shortest_len = find_shortestlen(price_devs).unwrap_or(0);
     let total_value_over_months = (0..shortest_len).zip(1..shortest_len).scan(
-        (initial_values, 0.0),
-        move |(prices, monthly_payments_upto_now), (i_prev_month, i_month)| {
-            let vars = vec![
-                Val::Float(prices.iter().sum::<f64>()),
-                Val::Float(initial_capital),
-            ];
+        (
+            initial_values,
+            0.0,
+            initial_capital,
+            0.0,
+            0.0,
+            fractions,
+            initial_debt,
+        ),
+        move |(
+            prices,
+            monthly_payments_upto_now,
+            peak_balance,
+            fees_upto_now,
+            payouts_upto_now,
+            fractions,
+            debt,
+        ),
+              (i_prev_month, i_month)| {
             let payment_this_month = monthly_payments
-                .map(|mp| mp.compute((start_date + i_month)?, &vars))
-                .unwrap_or(Ok(0.0))
-                .unwrap_or(0.0);
+                .map(|mp| {
+                    let vars = PaymentVars {
+                        initial_balance: initial_capital,
+                        month: i_month,
+                        date: (start_date + i_month)?,
+                        values: prices,
+                        debt: *debt,
+                        peak_balance: *peak_balance,
+                        total_payments: initial_capital + *monthly_payments_upto_now
+                            - *payouts_upto_now,
+                    };
+                    mp.compute(&vars)
+                })
+                .unwrap_or(Ok(0.0));
+            let payment_this_month = match payment_this_month {
+                Ok(p) => p,
+                Err(e) => return Some(Err(e)),
+            };
 
-            // immediately called closure for error handling,
-            // since outer closure has to return Option
-            let fractions = &rebalance_data.fractions;
+            let mut is_inception = false;
             for i_security in 0..prices.len() {
+                if !price_devs[i_security][i_prev_month].is_finite() {
+                    is_inception |= price_devs[i_security][i_month].is_finite();
+                    continue;
+                }
                 let payment_this_monthsec = payment_this_month * fractions[i_security];
                 // we assume the monthly payment at the beggining of the month
-                let price_update = (payment_this_monthsec * fractions[i_security]
-                    + prices[i_security])
-                    * price_devs[i_security][i_month]
+                let value_start_of_month =
+                    payment_this_monthsec * fractions[i_security] + prices[i_security];
+                let price_update = value_start_of_month * price_devs[i_security][i_month]
                     / price_devs[i_security][i_prev_month];
                 prices[i_security] = price_update;
                 *monthly_payments_upto_now += payment_this_monthsec;
+                *payouts_upto_now += rebalance_data.dividend_payouts.payout(
+                    i_security,
+                    i_month,
+                    value_start_of_month,
+                );
             }
+            *fees_upto_now += rebalance_data.fees.deduct(prices);
+            let is_margin_call = margin.is_some_and(|m| m.accrue_and_check(prices, debt));
 
             let total: f64 = prices.iter().sum();
-            if rebalance_data.is_triggered(prices, i_month) {
-                rebalance_data
-                    .fractions
+            *peak_balance = peak_balance.max(total - *debt);
+            if is_inception {
+                *fractions = available_fractions(rebalance_data.fractions, price_devs, i_month);
+            }
+            if is_inception || rebalance_data.is_triggered(prices, fractions, i_month) {
+                let total = match margin {
+                    Some(margin) if !is_margin_call => margin.relever(total, debt),
+                    _ => total,
+                };
+                fractions
                     .iter()
                     .zip(prices.iter_mut())
                     .for_each(|(frac, price)| {
@@ -231,25 +734,275 @@ pub fn compute_balance_over_months<'a>(
                     });
             }
             Some(Ok((
-                prices.iter().sum::<f64>(),
+                prices.iter().sum::<f64>() - *debt,
                 initial_capital + *monthly_payments_upto_now,
+                *fees_upto_now,
+                *payouts_upto_now,
             )))
         },
     );
-    iter::once(Ok((initial_capital, initial_capital))).chain(total_value_over_months)
+    iter::once(Ok((initial_capital, initial_capital, 0.0, 0.0))).chain(total_value_over_months)
+}
+
+/// Converts values given in a foreign currency into the base currency, where `fx_rates` contains
+/// the value of one unit of the foreign currency in the base currency
+pub fn convert_currency(values: &[f64], fx_rates: &[f64]) -> Vec<f64> {
+    values
+        .iter()
+        .zip(fx_rates.iter())
+        .map(|(v, fx)| v * fx)
+        .collect()
+}
+
+/// Deducts a yearly hedging cost in percent monthly from a price development to model a
+/// currency-hedged share class
+pub fn with_hedging_cost(price_dev: &[f64], yearly_cost_perc: f64) -> Vec<f64> {
+    let monthly_factor = 1.0 - monthly_fee_rate(yearly_cost_perc);
+    price_dev
+        .iter()
+        .enumerate()
+        .map(|(i, v)| v * monthly_factor.powi(i as i32))
+        .collect()
+}
+
+/// Number of trailing months the variance of the volatility drag of leveraged products is
+/// estimated from
+const LEVERAGE_VARIANCE_MONTHS: usize = 12;
+
+/// Approximates a product that resets its leverage daily from monthly prices of the underlying.
+/// The monthly log return is `leverage` times the log return of the underlying minus the
+/// volatility drag `(leverage² - leverage) σ² / 2`, where `σ²` is the variance of the monthly log
+/// returns of the underlying over the trailing year up to the month such that later months do
+/// not change earlier values. The borrowed amount of `leverage - 1` is charged with the yearly
+/// borrowing cost in percent, inverse products earn it on their cash.
+pub fn leveraged(
+    price_dev: &[f64],
+    leverage: f64,
+    yearly_borrowing_cost_perc: f64,
+) -> BlcResult<Vec<f64>> {
+    if leverage == 0.0 {
+        Err(blcerr!("leverage must not be 0"))?;
+    }
+    if price_dev.iter().any(|p| *p <= 0.0 || !p.is_finite()) {
+        Err(blcerr!("leverage needs positive prices"))?;
+    }
+    let log_returns = price_dev
+        .iter()
+        .zip(price_dev.iter().skip(1))
+        .map(|(prev, cur)| (cur / prev).ln())
+        .collect::<Vec<_>>();
+    let trailing_variance = |idx: usize| {
+        let window = &log_returns[(idx + 1).saturating_sub(LEVERAGE_VARIANCE_MONTHS)..idx + 1];
+        let n = window.len() as f64;
+        if window.len() > 1 {
+            let mean = window.iter().sum::<f64>() / n;
+            window.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        }
+    };
+    let monthly_borrowing = (1.0 + yearly_borrowing_cost_perc / 100.0).ln() / 12.0;
+    let start = price_dev.first().copied().unwrap_or(1.0);
+    Ok(iter::once(start)
+        .chain(
+            log_returns
+                .iter()
+                .enumerate()
+                .scan(start, |value, (idx, r)| {
+                    let drag = (leverage * leverage - leverage) * trailing_variance(idx) / 2.0
+                        + (leverage - 1.0) * monthly_borrowing;
+                    *value *= (leverage * r - drag).exp();
+                    Some(*value)
+                }),
+        )
+        .collect())
+}
+
+/// Converts nominal values into values with the purchasing power of the first month
+pub fn deflate(values: &[f64], price_index: &[f64]) -> Vec<f64> {
+    values
+        .iter()
+        .zip(price_index.iter())
+        .map(|(v, pi)| v * price_index[0] / pi)
+        .collect()
+}
+
+/// Converts cumulative nominal payments into cumulative payments with the purchasing power of
+/// the first month, i.e., each payment is deflated at the time it is made
+pub fn deflate_cumulative(cumulative_values: &[f64], price_index: &[f64]) -> Vec<f64> {
+    let mut prev_nominal = 0.0;
+    let mut real = 0.0;
+    cumulative_values
+        .iter()
+        .zip(price_index.iter())
+        .map(|(v, pi)| {
+            real += (v - prev_nominal) * price_index[0] / pi;
+            prev_nominal = *v;
+            real
+        })
+        .collect()
+}
+
+pub fn monthly_returns(price_dev: &[f64]) -> Vec<f64> {
+    price_dev
+        .iter()
+        .zip(price_dev.iter().skip(1))
+        .map(|(prev, cur)| cur / prev - 1.0)
+        .collect()
+}
+
+/// Monthly returns of a portfolio whose payments are made at the beginning of each month
+pub fn portfolio_returns(balances: &[f64], cumulative_payments: &[f64]) -> Vec<f64> {
+    (1..balances.len().min(cumulative_payments.len()))
+        .map(|i| {
+            let payment = cumulative_payments[i] - cumulative_payments[i - 1];
+            balances[i] / (balances[i - 1] + payment) - 1.0
+        })
+        .collect()
+}
+
+/// Monthly returns can be simple returns, i.e., `p_t / p_{t-1} - 1`, or log returns `ln(p_t / p_{t-1})`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReturnKind {
+    #[default]
+    Simple,
+    Log,
+}
+impl ReturnKind {
+    pub fn convert_simple(self, simple: f64) -> f64 {
+        match self {
+            ReturnKind::Simple => simple,
+            ReturnKind::Log => simple.ln_1p(),
+        }
+    }
+    pub fn to_simple(self, ret: f64) -> f64 {
+        match self {
+            ReturnKind::Simple => ret,
+            ReturnKind::Log => ret.exp_m1(),
+        }
+    }
+}
+
+/// Level series that starts at `base` one month before the first return, hence it has one
+/// element more than `returns`
+pub fn returns_to_levels(returns: &[f64], kind: ReturnKind, base: f64) -> Vec<f64> {
+    iter::once(base)
+        .chain(returns.iter().scan(base, |level, r| {
+            *level *= 1.0 + kind.to_simple(*r);
+            Some(*level)
+        }))
+        .collect()
+}
+
+pub fn levels_to_returns(levels: &[f64], kind: ReturnKind) -> Vec<f64> {
+    monthly_returns(levels)
+        .into_iter()
+        .map(|r| kind.convert_simple(r))
+        .collect()
+}
+
+/// Annualized Sharpe ratio of monthly returns, `None` if the volatility vanishes
+pub fn sharpe_ratio(returns: &[f64], risk_free_returns: &[f64]) -> Option<f64> {
+    let excess = returns
+        .iter()
+        .zip(risk_free_returns.iter())
+        .map(|(r, rf)| r - rf)
+        .collect::<Vec<_>>();
+    if excess.len() < 2 {
+        return None;
+    }
+    let n = excess.len() as f64;
+    let mean = excess.iter().sum::<f64>() / n;
+    let var = excess.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    if var > 0.0 {
+        Some(mean / var.sqrt() * 12f64.sqrt())
+    } else {
+        None
+    }
+}
+
+/// Annualized return, volatility, and maximum drawdown in percent of monthly returns
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RiskReturn {
+    pub yearly_return: f64,
+    pub volatility: f64,
+    pub max_drawdown: f64,
+}
+impl RiskReturn {
+    /// The return is time-weighted, i.e., independent of the payments if applied to
+    /// [`portfolio_returns`]
+    pub fn from_returns(returns: &[f64]) -> Self {
+        let n = returns.len() as f64;
+        let growth = returns.iter().map(|r| r.ln_1p()).sum::<f64>();
+        let mean = returns.iter().sum::<f64>() / n;
+        let var = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        RiskReturn {
+            yearly_return: 100.0 * (growth * 12.0 / n).exp_m1(),
+            volatility: 100.0 * (var * 12.0).sqrt(),
+            max_drawdown: max_drawdown(&returns_to_levels(returns, ReturnKind::Simple, 1.0)),
+        }
+    }
 }
 
+/// Largest loss in percent from a previous peak
+pub fn max_drawdown(levels: &[f64]) -> f64 {
+    levels
+        .iter()
+        .scan(f64::NEG_INFINITY, |peak, level| {
+            *peak = level.max(*peak);
+            Some(100.0 * (1.0 - level / *peak))
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Yearly internal rate of return in percent, i.e., the money-weighted return, of payments that
+/// are made at the beginning of each month as in [`compute_balance_over_months`]
+pub fn internal_rate_of_return(cumulative_payments: &[f64], final_balance: f64) -> BlcResult<f64> {
+    let n_months = cumulative_payments.len();
+    if n_months < 2 {
+        Err(blcerr!(
+            "the internal rate of return needs at least 2 months"
+        ))?;
+    }
+    let flows = iter::once((0, cumulative_payments[0]))
+        .chain((1..n_months).map(|i| (i - 1, cumulative_payments[i] - cumulative_payments[i - 1])));
+    let excess = |monthly_rate: f64| {
+        let future_value = flows
+            .clone()
+            .map(|(month, flow)| flow * (1.0 + monthly_rate).powi((n_months - 1 - month) as i32))
+            .sum::<f64>();
+        Ok(future_value - final_balance)
+    };
+    let monthly_rate = bisection(excess, -0.99, 0.1)?;
+    Ok(100.0 * ((1.0 + monthly_rate).powi(12) - 1.0))
+}
+
+#[allow(clippy::type_complexity)]
 pub fn unzip_balance_iter(
-    balance_over_month: impl Iterator<Item = BlcResult<(f64, f64)>>,
-) -> BlcResult<(Vec<f64>, Vec<f64>)> {
+    balance_over_month: impl Iterator<Item = BlcResult<(f64, f64, f64, f64)>>,
+) -> BlcResult<(Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>)> {
     let mut balance_development = vec![];
     let mut payments = vec![];
+    let mut fees = vec![];
+    let mut payouts = vec![];
     for bom in balance_over_month {
-        let (b, p) = bom?;
+        let (b, p, f, po) = bom?;
         balance_development.push(b);
         payments.push(p);
+        fees.push(f);
+        payouts.push(po);
     }
-    Ok((balance_development, payments))
+    Ok((balance_development, payments, fees, payouts))
+}
+
+/// Cumulative cash flows into the portfolio, i.e., payments minus dividend payouts, e.g., to
+/// compute returns
+pub fn net_payments(payments: &[f64], payouts: &[f64]) -> Vec<f64> {
+    payments
+        .iter()
+        .zip(payouts.iter())
+        .map(|(p, po)| p - po)
+        .collect()
 }
 
 #[allow(clippy::needless_lifetimes)]
@@ -285,6 +1038,7 @@ pub fn unix_to_now_nanos() -> BlcResult<u64> {
         % (u64::MAX as u128)) as u64)
 }
 
+#[cfg(test)]
 pub fn random_walk(
     expected_yearly_return: f64,
     is_markovian: bool,
@@ -293,8 +1047,47 @@ pub fn random_walk(
     n_months: usize,
     crashes: &[usize],
 ) -> BlcResult<Vec<f64>> {
-    let mut rng = StdRng::seed_from_u64(unix_to_now_nanos()?);
-    let mut sigma_rng = StdRng::seed_from_u64(unix_to_now_nanos()?);
+    random_walk_from_seed(
+        unix_to_now_nanos()?,
+        expected_yearly_return,
+        is_markovian,
+        sigma_mean,
+        sigma_window_size,
+        n_months,
+        crashes,
+    )
+}
+
+/// Expected yearly return in percent and volatility of a price development as parameters of
+/// [`random_walk_from_seed`] with a volatility window of 1. Like the random walk, the expected
+/// return is based on the arithmetic mean of the monthly returns. The monthly volatilities of the
+/// random walk are drawn from a normal distribution with the volatility parameter as mean and
+/// standard deviation, hence the squared standard deviation of the returns is twice the squared
+/// parameter.
+pub fn calibrate_random_walk(price_dev: &[f64]) -> BlcResult<(f64, f64)> {
+    if price_dev.len() < 3 {
+        Err(blcerr!("calibration needs at least 3 months"))?;
+    }
+    let returns = monthly_returns(price_dev);
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let var = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    let expected_yearly_return = 100.0 * ((1.0 + mean).powi(12) - 1.0);
+    Ok((expected_yearly_return, (var / 2.0).sqrt()))
+}
+
+/// Reproducible random walk, e.g., for many paths of a Monte Carlo simulation or for simulated
+/// charts that record their seed
+pub fn random_walk_from_seed(
+    seed: u64,
+    expected_yearly_return: f64,
+    is_markovian: bool,
+    sigma_mean: f64,
+    sigma_window_size: usize,
+    n_months: usize,
+    crashes: &[usize],
+) -> BlcResult<Vec<f64>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut sigma_rng = StdRng::seed_from_u64(seed.wrapping_add(1));
     let sigma_distribution = Normal::new(sigma_mean, sigma_mean).map_err(to_blc)?;
     let mut last_sigmas = vec![sigma_mean; sigma_window_size];
     let start_price = 1e5;
@@ -489,23 +1282,26 @@ pub struct BestRebalanceTrigger {
     pub with_best_interval: (RebalanceTrigger, f64, f64),
 }
 
+/// Tries rebalancing intervals and deviation thresholds, all other settings are taken from
+/// `rebalance_data` whose trigger is ignored
 pub fn best_rebalance_trigger(
     price_devs: &[&[f64]],
     initial_balance: f64,
     monthly_payments: Option<&MonthlyPayments>,
-    fractions: &[f64],
+    rebalance_data: RebalanceData<'_>,
     start_date: Date,
 ) -> BlcResult<BestRebalanceTrigger> {
     let shortest_len =
         find_shortestlen(price_devs).ok_or_else(|| BlcError::new("empty price dev"))?;
     let months_to_test = 0..(shortest_len / 2);
     let deviations_to_test = (0..10).chain((20..50).step_by(10)).chain(iter::once(75));
+    let rebalance_data = &rebalance_data;
     let triggers: Vec<(RebalanceTrigger, f64, f64)> = months_to_test
         .flat_map(move |n_months| {
             iter::repeat(n_months).zip(deviations_to_test.clone()).map(
                 move |(n_months, d)| -> BlcResult<_> {
                     let rebalance_data = if n_months == 0 && d == 0 {
-                        RebalanceData::from_fractions(fractions)
+                        RebalanceData::wo_trigger(rebalance_data.clone())
                     } else {
                         let trigger = if n_months == 0 {
                             RebalanceTrigger::from_dev(d as f64 / 100.0)
@@ -514,7 +1310,10 @@ pub fn best_rebalance_trigger(
                         } else {
                             RebalanceTrigger::from_both(n_months, d as f64 / 100.0)
                         };
-                        RebalanceData { trigger, fractions }
+                        RebalanceData {
+                            trigger,
+                            ..rebalance_data.clone()
+                        }
                     };
                     let trigger = rebalance_data.trigger;
                     let (balance, total_payments) = compute_total_balance(
@@ -551,14 +1350,15 @@ pub fn best_rebalance_trigger(
     })
 }
 
-fn compute_total_balance(
+/// Final balance and the sum of all payments minus dividend payouts
+pub fn compute_total_balance(
     price_devs: &[&[f64]],
     initial_balance: f64,
     monthly_payments: Option<&MonthlyPayments>,
     rebalance_data: RebalanceData<'_>,
     start_date: Date,
 ) -> BlcResult<(f64, f64)> {
-    compute_balance_over_months(
+    let (balance, total_payments, _, payouts) = compute_balance_over_months(
         price_devs,
         initial_balance,
         monthly_payments,
@@ -566,11 +1366,236 @@ fn compute_total_balance(
         start_date,
     )
     .last()
-    .unwrap()
+    .unwrap()?;
+    Ok((balance, total_payments - payouts))
 }
 
-#[cfg(test)]
-use exmex::parse_val;
+/// Finds the root of an increasing function `f` by bisection. The upper bound is expanded until
+/// the sign changes. If `f` is already non-negative at the lower bound, the lower bound is returned.
+/// An infinite lower bound is expanded downwards from the upper bound, e.g., to solve for
+/// withdrawals. Non-finite values of `f` are errors.
+pub fn bisection(f: impl Fn(f64) -> BlcResult<f64>, lower: f64, upper: f64) -> BlcResult<f64> {
+    let f = |x: f64| {
+        let y = f(x)?;
+        if y.is_finite() {
+            Ok(y)
+        } else {
+            Err(blcerr!("goal is not finite at {x}"))
+        }
+    };
+    let mut lower = lower;
+    let mut upper = upper;
+    if lower.is_finite() {
+        if f(lower)? >= 0.0 {
+            return Ok(lower);
+        }
+    } else {
+        let mut width = upper.abs().max(1.0);
+        lower = upper - width;
+        let mut n_expansions = 0;
+        while f(lower)? >= 0.0 {
+            if n_expansions > 50 {
+                Err(blcerr!("target is exceeded for all values"))?;
+            }
+            upper = lower;
+            width *= 2.0;
+            lower = upper - width;
+            n_expansions += 1;
+        }
+    }
+    let mut n_expansions = 0;
+    while f(upper)? < 0.0 {
+        if n_expansions > 50 {
+            Err(blcerr!("target cannot be reached"))?;
+        }
+        upper = lower + 2.0 * (upper - lower);
+        n_expansions += 1;
+    }
+    for _ in 0..200 {
+        let mid = 0.5 * (lower + upper);
+        if upper - lower < 1e-9 * mid.abs().max(1.0) {
+            break;
+        }
+        if f(mid)? < 0.0 {
+            lower = mid;
+        } else {
+            upper = mid;
+        }
+    }
+    Ok(upper)
+}
+
+/// Evaluates `expr` month by month. Variables are time variables such as `month` or names of
+/// `inputs`, i.e., developments that start at `start_date`. The result is as long as the shortest
+/// input.
+pub fn evaluate_over_months(
+    expr: &Expr,
+    inputs: &[(&str, &[f64])],
+    start_date: Date,
+) -> BlcResult<Vec<f64>> {
+    let n_months = inputs
+        .iter()
+        .map(|(_, values)| values.len())
+        .min()
+        .ok_or_else(|| blcerr!("expressions need at least one chart"))?;
+    let var_names = expr.var_names();
+    let input_indices = var_names
+        .iter()
+        .map(|name| {
+            if time_var(name, 0, start_date).is_some() {
+                Ok(None)
+            } else {
+                inputs
+                    .iter()
+                    .position(|(input, _)| input == name)
+                    .map(Some)
+                    .ok_or_else(|| blcerr!("unknown variable '{name}' in chart expression"))
+            }
+        })
+        .collect::<BlcResult<Vec<_>>>()?;
+    (0..n_months)
+        .map(|month| {
+            let date = (start_date + month)?;
+            let vars = var_names
+                .iter()
+                .zip(input_indices.iter())
+                .map(|(name, input_idx)| {
+                    Val::Float(match input_idx {
+                        Some(idx) => inputs[*idx].1[month],
+                        None => time_var(name, month, date).unwrap_or_default(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let value = eval(expr, &vars)?;
+            if value.is_finite() {
+                Ok(value)
+            } else {
+                Err(blcerr!("chart expression is not finite in {date}"))
+            }
+        })
+        .collect()
+}
+
+/// Rescales a price development such that its average yearly return becomes
+/// `yearly_return_perc` while the relative fluctuations are preserved.
+pub fn with_yearly_return(price_dev: &[f64], yearly_return_perc: f64) -> Vec<f64> {
+    // leading `NaN`s before the inception stay untouched
+    let first = price_dev.iter().position(|v| v.is_finite()).unwrap_or(0);
+    if price_dev.len() < first + 2 {
+        return price_dev.to_vec();
+    }
+    let n_steps = (price_dev.len() - 1 - first) as f64;
+    let current_monthly = (price_dev[price_dev.len() - 1] / price_dev[first]).powf(1.0 / n_steps);
+    let target_monthly = (1.0 + yearly_return_perc / 100.0).powf(1.0 / 12.0);
+    let factor = target_monthly / current_monthly;
+    price_dev
+        .iter()
+        .enumerate()
+        .map(|(i, v)| v * factor.powi(i.saturating_sub(first) as i32))
+        .collect()
+}
+
+/// Free variable of the goal seeking
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GoalVariable {
+    /// constant monthly payment that replaces all other payments, negative for withdrawals
+    #[default]
+    MonthlyPayment,
+    InitialBalance,
+    /// average yearly return in percent of a single price development
+    YearlyReturn,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum GoalTarget {
+    FinalBalance(f64),
+    /// yearly return in percent as computed by [`yearly_return`]
+    YearlyReturn(f64),
+}
+impl GoalTarget {
+    /// Positive if the target is exceeded
+    pub fn excess(&self, final_balance: f64, total_payments: f64, n_months: usize) -> f64 {
+        match self {
+            GoalTarget::FinalBalance(target) => final_balance - target,
+            GoalTarget::YearlyReturn(target) => {
+                let (yearly_return_perc, _) =
+                    yearly_return(total_payments, n_months, final_balance);
+                yearly_return_perc - target
+            }
+        }
+    }
+}
+
+/// Fraction of the price developments for which the single-asset portfolio reaches the target
+pub fn success_probability(
+    price_devs: &[Vec<f64>],
+    initial_balance: f64,
+    monthly_payments: &MonthlyPayments,
+    start_date: Date,
+    target: GoalTarget,
+) -> BlcResult<f64> {
+    let n_successes = price_devs
+        .iter()
+        .map(|pd| {
+            let (balance, total_payments) = compute_total_balance(
+                &[pd],
+                initial_balance,
+                Some(monthly_payments),
+                RebalanceData::from_fractions(&[1.0]),
+                start_date,
+            )?;
+            Ok(target.excess(balance, total_payments, pd.len() - 1) >= 0.0)
+        })
+        .collect::<BlcResult<Vec<_>>>()?
+        .into_iter()
+        .filter(|success| *success)
+        .count();
+    Ok(n_successes as f64 / price_devs.len() as f64)
+}
+
+/// Solves for the monthly payment or the initial balance such that the target is reached
+/// with the given probability across all price developments
+pub fn solve_for_success_probability(
+    price_devs: &[Vec<f64>],
+    initial_balance: f64,
+    monthly_payments: &MonthlyPayments,
+    start_date: Date,
+    variable: GoalVariable,
+    target: GoalTarget,
+    probability: f64,
+) -> BlcResult<f64> {
+    if price_devs.is_empty() {
+        Err(blcerr!("no price developments for Monte Carlo simulation"))?;
+    }
+    let upper = initial_balance.abs().max(1.0);
+    match variable {
+        GoalVariable::MonthlyPayment => bisection(
+            |x| {
+                let p = success_probability(
+                    price_devs,
+                    initial_balance,
+                    &MonthlyPayments::from_constant(x),
+                    start_date,
+                    target,
+                )?;
+                Ok(p - probability)
+            },
+            f64::NEG_INFINITY,
+            upper / 100.0,
+        ),
+        GoalVariable::InitialBalance => bisection(
+            |x| {
+                let p = success_probability(price_devs, x, monthly_payments, start_date, target)?;
+                Ok(p - probability)
+            },
+            0.0,
+            upper,
+        ),
+        GoalVariable::YearlyReturn => Err(blcerr!(
+            "Monte Carlo goal seeking supports monthly payment and initial capital only"
+        )),
+    }
+}
 
 #[test]
 fn test_adapt() {
@@ -602,6 +1627,9 @@ fn test_compute_balance() {
                 deviation: None,
             },
             fractions: &[0.5, 0.5],
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         },
         d202005,
     )
@@ -630,6 +1658,9 @@ fn test_compute_balance() {
                 deviation: None,
             },
             fractions: &[0.7, 0.3],
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         },
         d202005,
     )
@@ -647,6 +1678,9 @@ fn test_compute_balance() {
                 deviation: None,
             },
             fractions: &[1.0, 0.0],
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         },
         d202005,
     )
@@ -666,6 +1700,9 @@ fn test_compute_balance() {
                 deviation: None,
             },
             fractions: &[0.7, 0.3],
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         },
         d202005,
     )
@@ -691,6 +1728,9 @@ fn test_compute_balance() {
                 deviation: None,
             },
             fractions: &[0.7, 0.3],
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         },
         d202005,
     )
@@ -771,10 +1811,13 @@ fn test_rebalance() {
                 deviation: None,
             },
             fractions: &[0.5, 0.5],
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         },
         d202005,
     );
-    let (x, _) = unzip_balance_iter(bom).unwrap();
+    let (x, _, _, _) = unzip_balance_iter(bom).unwrap();
     assert!((x[2] - 0.5).abs() < 1e-12);
 
     let v1s = vec![1.0, 1.0, 1.0];
@@ -790,10 +1833,13 @@ fn test_rebalance() {
                 deviation: Some(0.1),
             },
             fractions: &[0.5, 0.5],
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         },
         d202005,
     );
-    let (x, _) = unzip_balance_iter(bom).unwrap();
+    let (x, _, _, _) = unzip_balance_iter(bom).unwrap();
     assert!((x[2] - 1.125).abs() < 1e-12);
 }
 
@@ -802,9 +1848,15 @@ fn test_besttrigger() {
     let d202005 = Date::new(2020, 5).unwrap();
     let v1s = vec![1.0, 1.0, 1.0, 1.0, 0.5, 1.0];
     let v2s = vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0];
-    let (_, balance, _) = best_rebalance_trigger(&[&v1s, &v2s], 1.0, None, &[0.5, 0.5], d202005)
-        .unwrap()
-        .best;
+    let (_, balance, _) = best_rebalance_trigger(
+        &[&v1s, &v2s],
+        1.0,
+        None,
+        RebalanceData::from_fractions(&[0.5, 0.5]),
+        d202005,
+    )
+    .unwrap()
+    .best;
     assert!((balance - 1.125).abs() < 1e-12);
 }
 #[test]
@@ -823,6 +1875,9 @@ fn test_rebalancestats() {
                 deviation: None,
             },
             fractions: &[0.5, 0.5],
+            fees: Fees::default(),
+            dividend_payouts: DividendPayouts::default(),
+            margin: None,
         },
         d202005,
         min_n_months,
@@ -859,11 +1914,21 @@ fn test_rebalancestats() {
 #[test]
 fn test_monthly() {
     let d1 = Date::new(2000, 11).unwrap();
-    let mp = MonthlyPayments::from_single_payment(parse_val("cb / ib").unwrap());
-    let vars = &[Val::Float(2.0), Val::Float(2.9)];
-    let res = mp.compute(d1, vars).unwrap();
+    let vars_at = |date| PaymentVars {
+        initial_balance: 2.9,
+        month: 0,
+        date,
+        values: &[2.0],
+        debt: 0.0,
+        peak_balance: 2.0,
+        total_payments: 2.9,
+    };
+    let mp = MonthlyPayments::from_single_payment(
+        parse_val("current_balance / initial_balance").unwrap(),
+    );
+    let res = mp.compute(&vars_at(d1)).unwrap();
     assert!((res - 2.0 / 2.9).abs() < 1e-9);
-    let expr1 = parse_val("1.0 / cb").unwrap();
+    let expr1 = parse_val("1.0 / current_balance").unwrap();
     let expr2 = parse_val("7.0").unwrap();
     let payments = vec![expr1, expr2];
     let d2 = Date::new(2013, 11).unwrap();
@@ -874,12 +1939,517 @@ fn test_monthly() {
         Interval::new(d3, d4).unwrap(),
     ];
     let mp = MonthlyPayments::from_intervals(payments, intervals).unwrap();
-    let res = mp.compute(Date::new(2000, 10).unwrap(), vars).unwrap();
+    let res = mp.compute(&vars_at(Date::new(2000, 10).unwrap())).unwrap();
     assert!(res.abs() < 1e-9);
-    let res = mp.compute(Date::new(2001, 10).unwrap(), vars).unwrap();
+    let res = mp.compute(&vars_at(Date::new(2001, 10).unwrap())).unwrap();
     assert!((res - 0.5).abs() < 1e-9);
-    let res = mp.compute(Date::new(2007, 10).unwrap(), vars).unwrap();
+    let res = mp.compute(&vars_at(Date::new(2007, 10).unwrap())).unwrap();
     assert!((res - 0.5).abs() < 1e-9);
-    let res = mp.compute(Date::new(2013, 10).unwrap(), vars).unwrap();
+    let res = mp.compute(&vars_at(Date::new(2013, 10).unwrap())).unwrap();
     assert!((res - 7.5).abs() < 1e-9);
+
+    let mp = MonthlyPayments::from_single_payment(parse_val("value_0").unwrap());
+    assert!(mp.compute(&vars_at(d1)).is_ok());
+    let mp = MonthlyPayments::from_single_payment(parse_val("value_1").unwrap());
+    assert!(mp.compute(&vars_at(d1)).is_err());
+}
+
+#[test]
+fn test_payment_vars() {
+    let values = [30.0, 10.0];
+    let vars = PaymentVars {
+        initial_balance: 50.0,
+        month: 18,
+        date: Date::new(2001, 7).unwrap(),
+        values: &values,
+        debt: 0.0,
+        peak_balance: 50.0,
+        total_payments: 60.0,
+    };
+    let mp = MonthlyPayments::from_single_payment(
+        parse_val("salary * 1.03^floor(years_elapsed) + weight_0 + drawdown").unwrap(),
+    )
+    .with_constants(vec![("salary".to_string(), 100.0)])
+    .unwrap();
+    let res = mp.compute(&vars).unwrap();
+    assert!((res - (103.0 + 0.75 + 0.2)).abs() < 1e-9);
+    let mp = MonthlyPayments::from_single_payment(
+        parse_val("calendar_year * 100.0 + calendar_month + month - total_payments").unwrap(),
+    );
+    let res = mp.compute(&vars).unwrap();
+    assert!((res - (200107.0 + 18.0 - 60.0)).abs() < 1e-9);
+
+    let mp = MonthlyPayments::from_single_payment(parse_val("1").unwrap());
+    assert!(mp
+        .clone()
+        .with_constants(vec![("month".to_string(), 1.0)])
+        .is_err());
+    assert!(mp
+        .clone()
+        .with_constants(vec![("1x".to_string(), 1.0)])
+        .is_err());
+    assert!(mp
+        .with_constants(vec![("x".to_string(), 1.0), ("x".to_string(), 2.0)])
+        .is_err());
+
+    let named = |expr| name_positional_variables(expr, &["salary"]).unwrap();
+    assert_eq!(named("b * 0.01 + exp(a) / x_2"), "b * 0.01 + exp(a) / x_2");
+    assert_eq!(
+        named("b * 0.01 + exp(a) / 2.5"),
+        "initial_balance * 0.01 + exp(current_balance) / 2.5"
+    );
+    assert_eq!(named("x"), "current_balance");
+    assert_eq!(named("month + x"), "month + x");
+    assert_eq!(named("salary * x"), "salary * x");
+    assert_eq!(named("100"), "100");
+
+    // the month after November is December of the same year
+    let flat = [1.0; 3];
+    let mp = MonthlyPayments::from_single_payment(
+        parse_val("calendar_year * 100.0 + calendar_month").unwrap(),
+    );
+    let (_, payments, _, _) = unzip_balance_iter(compute_balance_over_months(
+        &[&flat],
+        0.0,
+        Some(&mp),
+        RebalanceData::from_fractions(&[1.0]),
+        Date::new(2000, 11).unwrap(),
+    ))
+    .unwrap();
+    assert!((payments[1] - 200012.0).abs() < 1e-9);
+    assert!((payments[2] - payments[1] - 200101.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_goal_seek() {
+    let x = bisection(|x| Ok(x * x - 2.0), 0.0, 1.0).unwrap();
+    assert!((x - 2f64.sqrt()).abs() < 1e-6);
+    let x = bisection(|x| Ok(x - 2.0), 5.0, 6.0).unwrap();
+    assert!((x - 5.0).abs() < 1e-12);
+    assert!(bisection(|_| Ok(-1.0), 0.0, 1.0).is_err());
+    assert!(bisection(|x| Ok(if x < 0.5 { f64::NAN } else { x }), 0.0, 1.0).is_err());
+    let x = bisection(|x| Ok(x + 500.0), f64::NEG_INFINITY, 1.0).unwrap();
+    assert!((x + 500.0).abs() < 1e-6);
+    assert!(bisection(|_| Ok(1.0), f64::NEG_INFINITY, 1.0).is_err());
+
+    let price_dev = [1.0, 1.1, 0.9, 1.3];
+    let rescaled = with_yearly_return(&price_dev, 5.0);
+    let monthly = (rescaled[3] / rescaled[0]).powf(1.0 / 3.0);
+    assert!((monthly.powf(12.0) - 1.05).abs() < 1e-9);
+    assert!(
+        (rescaled[2] / rescaled[1] / (price_dev[2] / price_dev[1])
+            - monthly / 1.3f64.powf(1.0 / 3.0))
+        .abs()
+            < 1e-9
+    );
+
+    let d202005 = Date::new(2020, 5).unwrap();
+    let flat = vec![vec![1.0; 13]; 4];
+    let p = success_probability(
+        &flat,
+        100.0,
+        &MonthlyPayments::from_constant(10.0),
+        d202005,
+        GoalTarget::FinalBalance(220.0),
+    )
+    .unwrap();
+    assert!((p - 1.0).abs() < 1e-12);
+    let payment = solve_for_success_probability(
+        &flat,
+        100.0,
+        &MonthlyPayments::default(),
+        d202005,
+        GoalVariable::MonthlyPayment,
+        GoalTarget::FinalBalance(220.0),
+        0.9,
+    )
+    .unwrap();
+    assert!((payment - 10.0).abs() < 1e-6);
+    let withdrawal = solve_for_success_probability(
+        &flat,
+        100.0,
+        &MonthlyPayments::default(),
+        d202005,
+        GoalVariable::MonthlyPayment,
+        GoalTarget::FinalBalance(40.0),
+        0.9,
+    )
+    .unwrap();
+    assert!((withdrawal + 5.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_inflation() {
+    let price_index = [100.0, 110.0, 121.0];
+    let real = deflate(&[10.0, 11.0, 24.2], &price_index);
+    assert!((real[1] - 10.0).abs() < 1e-12);
+    assert!((real[2] - 20.0).abs() < 1e-12);
+    let real = deflate_cumulative(&[10.0, 21.0, 33.1], &price_index);
+    assert!((real[1] - 20.0).abs() < 1e-12);
+    assert!((real[2] - 30.0).abs() < 1e-12);
+
+    let vars = PaymentVars {
+        initial_balance: 10.0,
+        month: 2,
+        date: Date::new(2001, 7).unwrap(),
+        values: &[10.0],
+        debt: 0.0,
+        peak_balance: 10.0,
+        total_payments: 10.0,
+    };
+    let mp = MonthlyPayments::from_single_payment(parse_val("10.0").unwrap())
+        .with_price_index(price_index.to_vec());
+    assert!((mp.compute(&vars).unwrap() - 10.0).abs() < 1e-12);
+    let mp = mp.indexed_to_inflation(true);
+    assert!((mp.compute(&vars).unwrap() - 12.1).abs() < 1e-12);
+    let mp = MonthlyPayments::from_single_payment(parse_val("cpi_factor").unwrap());
+    assert!((mp.compute(&vars).unwrap() - 1.0).abs() < 1e-12);
+    // sessions may define their own constant named inflation
+    let mp = MonthlyPayments::from_single_payment(parse_val("100.0 * inflation").unwrap())
+        .with_constants(vec![("inflation".to_string(), 1.02)])
+        .unwrap();
+    assert!((mp.compute(&vars).unwrap() - 102.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_interest() {
+    let start = Date::new(2020, 1).unwrap();
+    let rates = InterestRates::new(
+        parse_val("12.0").unwrap(),
+        vec![(
+            parse_val("0.0").unwrap(),
+            Interval::new(Date::new(2020, 2).unwrap(), Date::new(2020, 3).unwrap()).unwrap(),
+        )],
+    );
+    let pd = rates.price_dev(start, 14).unwrap();
+    assert_eq!(pd.len(), 15);
+    assert!((pd[2] - pd[0]).abs() < 1e-9);
+    assert!((pd[14] / pd[2] - 1.12).abs() < 1e-9);
+    let rates = InterestRates::new(parse_val("calendar_year - 2018.0").unwrap(), vec![]);
+    let pd = rates.price_dev(start, 1).unwrap();
+    assert!((pd[1] / pd[0] - 1.02f64.powf(1.0 / 12.0)).abs() < 1e-12);
+    let rates = InterestRates::new(parse_val("x").unwrap(), vec![]);
+    assert!(rates.price_dev(start, 1).is_err());
+
+    // an override of December applies to the month after November of the same year
+    let december = Date::new(2020, 12).unwrap();
+    let rates = InterestRates::new(
+        parse_val("12.0").unwrap(),
+        vec![(
+            parse_val("0.0").unwrap(),
+            Interval::new(december, december).unwrap(),
+        )],
+    );
+    let pd = rates.price_dev(Date::new(2020, 11).unwrap(), 2).unwrap();
+    assert!((pd[1] - pd[0]).abs() < 1e-9);
+    assert!((pd[2] / pd[1] - 1.12f64.powf(1.0 / 12.0)).abs() < 1e-12);
+}
+
+#[test]
+fn test_fees() {
+    let d202005 = Date::new(2020, 5).unwrap();
+    let pd = vec![1.0; 13];
+    let price_devs = [pd.as_slice(), pd.as_slice()];
+    let compute = |expense_ratios: &[f64], advisory_fee: f64| {
+        let bom = compute_balance_over_months(
+            &price_devs,
+            100.0,
+            None,
+            RebalanceData {
+                fees: Fees {
+                    expense_ratios,
+                    advisory_fee,
+                },
+                ..RebalanceData::from_fractions(&[0.5, 0.5])
+            },
+            d202005,
+        );
+        let (balances, _, fees, _) = unzip_balance_iter(bom).unwrap();
+        (balances[12], fees[12])
+    };
+    let (b, f) = compute(&[], 0.0);
+    assert!((b - 100.0).abs() < 1e-12);
+    assert!(f.abs() < 1e-12);
+    // a yearly expense ratio reduces the value by exactly that percentage after one year
+    let (b, f) = compute(&[2.0, 2.0], 0.0);
+    assert!((b - 98.0).abs() < 1e-10);
+    assert!((f - 2.0).abs() < 1e-10);
+    let (b, f) = compute(&[2.0, 0.0], 0.0);
+    assert!((b - 99.0).abs() < 1e-10);
+    assert!((f - 1.0).abs() < 1e-10);
+    let (b, f) = compute(&[], 1.0);
+    assert!((b - 99.0).abs() < 1e-10);
+    assert!((f - 1.0).abs() < 1e-10);
+    let (b, f) = compute(&[2.0, 2.0], 1.0);
+    assert!((b - 98.0 * 0.99).abs() < 1e-10);
+    assert!((b + f - 100.0).abs() < 1e-10);
+}
+
+#[test]
+fn test_currency() {
+    let usd = [100.0, 110.0, 121.0];
+    let eur_per_usd = [1.0, 0.5, 2.0];
+    let eur = convert_currency(&usd, &eur_per_usd);
+    assert!((eur[0] - 100.0).abs() < 1e-12);
+    assert!((eur[1] - 55.0).abs() < 1e-12);
+    assert!((eur[2] - 242.0).abs() < 1e-12);
+
+    let pd = vec![1.0; 13];
+    let hedged = with_hedging_cost(&pd, 1.0);
+    assert!((hedged[0] - 1.0).abs() < 1e-12);
+    assert!((hedged[12] - 0.99).abs() < 1e-12);
+    assert_eq!(with_hedging_cost(&pd, 0.0), pd);
+}
+
+#[test]
+fn test_dividends() {
+    let d202005 = Date::new(2020, 5).unwrap();
+    let y = net_monthly_dividend_yield(12.0, 25.0);
+    assert!((y - 0.0075).abs() < 1e-12);
+
+    let pd = vec![1.0, 1.0, 2.0];
+    let tr = with_reinvested_dividends(&pd, &[y; 3]);
+    assert_eq!(tr.len(), 3);
+    assert!((tr[0] - 1.0).abs() < 1e-12);
+    assert!((tr[1] - 1.0075).abs() < 1e-12);
+    assert!((tr[2] - 1.0075 * 2.0075).abs() < 1e-12);
+
+    let yields = [0.01; 3];
+    let net_monthly_yields = [yields.as_slice()];
+    let price_devs = [pd.as_slice()];
+    let bom = compute_balance_over_months(
+        &price_devs,
+        100.0,
+        None,
+        RebalanceData {
+            dividend_payouts: DividendPayouts {
+                net_monthly_yields: &net_monthly_yields,
+            },
+            ..RebalanceData::from_fractions(&[1.0])
+        },
+        d202005,
+    );
+    let (balances, payments, _, payouts) = unzip_balance_iter(bom).unwrap();
+    // payouts do not change the balance but are withdrawn
+    assert!((balances[2] - 200.0).abs() < 1e-12);
+    assert_eq!(payments, vec![100.0; 3]);
+    assert!((payouts[1] - 1.0).abs() < 1e-12);
+    assert!((payouts[2] - 2.0).abs() < 1e-12);
+    assert_eq!(net_payments(&payments, &payouts)[2], 98.0);
+}
+
+#[test]
+fn test_sharpe() {
+    let returns = monthly_returns(&[1.0, 1.1, 0.99, 1.089]);
+    assert!((returns[0] - 0.1).abs() < 1e-12);
+    assert!((returns[1] + 0.1).abs() < 1e-12);
+    let sr = sharpe_ratio(&returns, &[0.0; 3]).unwrap();
+    let mean: f64 = 0.1 / 3.0;
+    let std = ((2.0 * (0.1 - mean).powi(2) + (0.1 + mean).powi(2)) / 2.0).sqrt();
+    assert!((sr - mean / std * 12f64.sqrt()).abs() < 1e-12);
+    assert!(sharpe_ratio(&[0.01, 0.01], &[0.0, 0.0]).is_none());
+    let returns = portfolio_returns(&[1.0, 2.2, 2.2], &[1.0, 2.0, 2.0]);
+    assert!((returns[0] - 0.1).abs() < 1e-12);
+    assert!(returns[1].abs() < 1e-12);
+}
+
+#[test]
+fn test_risk_return() {
+    assert!((max_drawdown(&[1.0, 2.0, 1.5, 3.0, 1.2, 2.0]) - 60.0).abs() < 1e-12);
+    assert_eq!(max_drawdown(&[1.0, 1.1, 1.2]), 0.0);
+    let risk_return = RiskReturn::from_returns(&[0.1, -0.1, 0.1, -0.1]);
+    assert!((risk_return.yearly_return - 100.0 * (0.99f64.powi(6) - 1.0)).abs() < 1e-10);
+    let std = (4.0 * 0.01 / 3.0f64).sqrt();
+    assert!((risk_return.volatility - 100.0 * std * 12f64.sqrt()).abs() < 1e-10);
+    assert!((risk_return.max_drawdown - 100.0 * (1.0 - 0.9801 / 1.1)).abs() < 1e-10);
+}
+
+#[test]
+fn test_internal_rate_of_return() {
+    let irr = internal_rate_of_return(&[100.0; 13], 100.0 * 1.01f64.powi(12)).unwrap();
+    assert!((irr - 100.0 * (1.01f64.powi(12) - 1.0)).abs() < 1e-6);
+    // the second payment is made at the start of the first month
+    let irr = internal_rate_of_return(&[100.0, 200.0], 202.0).unwrap();
+    assert!((irr - 100.0 * (1.01f64.powi(12) - 1.0)).abs() < 1e-6);
+    let irr = internal_rate_of_return(&[100.0, 100.0, 150.0], 150.0).unwrap();
+    assert!(irr.abs() < 1e-6);
+    assert!(internal_rate_of_return(&[100.0], 100.0).is_err());
+}
+
+#[test]
+fn test_returns_levels() {
+    let levels = [100.0, 110.0, 99.0, 108.9];
+    for kind in [ReturnKind::Simple, ReturnKind::Log] {
+        let returns = levels_to_returns(&levels, kind);
+        assert_eq!(returns.len(), 3);
+        let back = returns_to_levels(&returns, kind, 100.0);
+        for (l, b) in levels.iter().zip(back.iter()) {
+            assert!((l - b).abs() < 1e-10);
+        }
+    }
+    let log_returns = levels_to_returns(&levels, ReturnKind::Log);
+    assert!((log_returns[0] - 1.1f64.ln()).abs() < 1e-12);
+    assert_eq!(returns_to_levels(&[], ReturnKind::Simple, 1.0), vec![1.0]);
+}
+
+#[test]
+fn test_calibrate_random_walk() {
+    let price_dev = (0..25).map(|i| 1.01f64.powi(i)).collect::<Vec<_>>();
+    let (eyr, vola) = calibrate_random_walk(&price_dev).unwrap();
+    assert!((eyr - 100.0 * (1.01f64.powi(12) - 1.0)).abs() < 1e-10);
+    assert!(vola < 1e-10);
+    let walk = random_walk_from_seed(0, 8.0, true, 0.04, 1, 1200, &[]).unwrap();
+    let (eyr, vola) = calibrate_random_walk(&walk).unwrap();
+    assert!((eyr - 8.0).abs() < 2.0);
+    assert!((vola - 0.04).abs() < 0.01);
+    assert!(calibrate_random_walk(&[1.0, 1.1]).is_err());
+}
+
+#[test]
+fn test_inception() {
+    let d202005 = Date::new(2020, 5).unwrap();
+    let pd1 = [1.0, 2.0, 2.0, 2.0, 4.0];
+    let pd2 = [f64::NAN, f64::NAN, 1.0, 2.0, 2.0];
+    let price_devs = [pd1.as_slice(), pd2.as_slice()];
+    for deviation in [None, Some(0.3)] {
+        let bom = compute_balance_over_months(
+            &price_devs,
+            100.0,
+            None,
+            RebalanceData {
+                trigger: RebalanceTrigger {
+                    interval: None,
+                    deviation,
+                },
+                ..RebalanceData::from_fractions(&[0.5, 0.5])
+            },
+            d202005,
+        );
+        let (balances, payments, _, _) = unzip_balance_iter(bom).unwrap();
+        assert_eq!(balances, vec![100.0, 200.0, 200.0, 300.0, 400.0]);
+        assert_eq!(payments, vec![100.0; 5]);
+    }
+    assert_eq!(
+        available_fractions(&[0.2, 0.8], &price_devs, 0),
+        vec![1.0, 0.0]
+    );
+    assert_eq!(
+        available_fractions(&[0.0, 1.0], &price_devs, 0),
+        vec![1.0, 0.0]
+    );
+    assert_eq!(
+        available_fractions(&[0.2, 0.8], &price_devs, 2),
+        vec![0.2, 0.8]
+    );
+}
+
+#[test]
+fn test_evaluate_over_months() {
+    let start = Date::new(2020, 1).unwrap();
+    let world = [1.0, 2.0, 4.0];
+    let em = [2.0, 2.0];
+    let inputs = [("world", &world[..]), ("em", &em[..])];
+    let expr = parse_val("0.5*world + 0.5*em").unwrap();
+    assert_eq!(
+        evaluate_over_months(&expr, &inputs, start).unwrap(),
+        vec![1.5, 2.0]
+    );
+    let expr = parse_val("world * 0.5^month").unwrap();
+    assert_eq!(
+        evaluate_over_months(&expr, &inputs, start).unwrap(),
+        vec![1.0, 1.0]
+    );
+    let expr = parse_val("world + calendar_month").unwrap();
+    assert_eq!(
+        evaluate_over_months(&expr, &inputs[..1], start).unwrap(),
+        vec![2.0, 4.0, 7.0]
+    );
+    assert!(evaluate_over_months(&parse_val("sp500").unwrap(), &inputs, start).is_err());
+    assert!(evaluate_over_months(&parse_val("world / 0").unwrap(), &inputs, start).is_err());
+    assert!(evaluate_over_months(&parse_val("month").unwrap(), &[], start).is_err());
+}
+
+#[test]
+fn test_leveraged() {
+    let steady = [1.0, 1.01, 1.0201];
+    let lev = leveraged(&steady, 2.0, 0.0).unwrap();
+    assert!((lev[2] - 1.01f64.powi(4)).abs() < 1e-12);
+    let lev = leveraged(&steady, 2.0, 12.0).unwrap();
+    assert!((lev[2] - 1.01f64.powi(4) / 1.12f64.powf(2.0 / 12.0)).abs() < 1e-12);
+
+    let choppy = [1.0, 1.1, 1.0, 1.1, 1.0];
+    let lev = leveraged(&choppy, 1.0, 5.0).unwrap();
+    assert!(lev.iter().zip(choppy).all(|(l, c)| (l - c).abs() < 1e-12));
+    let lev = leveraged(&choppy, 3.0, 0.0).unwrap();
+    assert!(lev[4] < 1.0);
+    let inverse = leveraged(&choppy, -1.0, 0.0).unwrap();
+    assert!(inverse[1] < 1.0 && inverse[4] < 1.0);
+    assert!(leveraged(&choppy, 0.0, 0.0).is_err());
+    assert!(leveraged(&[1.0, -1.0], 2.0, 0.0).is_err());
+
+    // a volatile second half does not change the first half
+    let calm_then_wild = [1.0, 1.01, 1.0, 1.01, 1.0, 1.5, 0.8, 1.6, 0.7];
+    let lev = leveraged(&calm_then_wild, 2.0, 0.0).unwrap();
+    let first_half = leveraged(&calm_then_wild[..5], 2.0, 0.0).unwrap();
+    assert_eq!(lev[..5], first_half[..]);
+    assert!(lev[4] < 1.0);
+}
+
+#[test]
+fn test_margin() {
+    let start = Date::new(2020, 1).unwrap();
+    let final_equity = |prices: &[f64], margin: Margin, trigger: RebalanceTrigger| {
+        let (balance, _) = compute_total_balance(
+            &[prices],
+            100.0,
+            None,
+            RebalanceData {
+                trigger,
+                margin: Some(margin),
+                ..RebalanceData::from_fractions(&[1.0])
+            },
+            start,
+        )
+        .unwrap();
+        balance
+    };
+    let no_trigger = RebalanceTrigger::default();
+    let margin = Margin::new(2.0, 0.0, 0.25).unwrap();
+    assert!((final_equity(&[1.0, 1.1], margin, no_trigger) - 120.0).abs() < 1e-12);
+    let with_interest = Margin::new(2.0, 12.0, 0.25).unwrap();
+    let expected = 200.0 - 100.0 * 1.12f64.powf(1.0 / 12.0);
+    assert!((final_equity(&[1.0, 1.0], with_interest, no_trigger) - expected).abs() < 1e-12);
+
+    // the margin call sells assets and misses the recovery
+    let crash = [1.0, 0.6, 0.9];
+    assert!((final_equity(&crash, margin, no_trigger) - 40.0).abs() < 1e-12);
+    let no_maintenance = Margin::new(2.0, 0.0, 0.0).unwrap();
+    assert!((final_equity(&crash, no_maintenance, no_trigger) - 80.0).abs() < 1e-12);
+    assert!((final_equity(&[1.0, 0.4, 1.0], margin, no_trigger) + 20.0).abs() < 1e-12);
+
+    // rebalancing restores the leverage
+    let rally = [1.0, 1.5, 3.0];
+    assert!((final_equity(&rally, margin, no_trigger) - 500.0).abs() < 1e-12);
+    let monthly = RebalanceTrigger::from_interval(1);
+    assert!((final_equity(&rally, margin, monthly) - 600.0).abs() < 1e-12);
+
+    // payment rules see the equity, not the assets
+    let buy_the_dip = MonthlyPayments::from_single_payment(
+        parse_val("drawdown * 1000 + current_balance - peak_balance").unwrap(),
+    );
+    let (_, total_payments) = compute_total_balance(
+        &[&[1.0, 0.9, 0.9]],
+        100.0,
+        Some(&buy_the_dip),
+        RebalanceData {
+            margin: Some(no_maintenance),
+            ..RebalanceData::from_fractions(&[1.0])
+        },
+        start,
+    )
+    .unwrap();
+    assert!((total_payments - 280.0).abs() < 1e-12);
+
+    assert!(Margin::new(0.5, 0.0, 0.25).is_err());
+    assert!(Margin::new(2.0, 0.0, 0.5).is_err());
 }
@@ -139,6 +139,14 @@ impl Date {
         }
     }
 
+    pub fn prev_month(&self) -> BlcResult<Date> {
+        if self.month() == 1 {
+            Date::new(self.year() - 1, 12)
+        } else {
+            Date::new(self.year(), self.month() - 1)
+        }
+    }
+
     pub fn n_month_until(&self, later: Date) -> BlcResult<usize> {
         (later - *self).ok_or_else(|| blcerr!("later must be after self"))
     }
@@ -146,11 +154,8 @@ impl Date {
 impl Add<usize> for Date {
     type Output = BlcResult<Date>;
     fn add(self, rhs: usize) -> Self::Output {
-        let month = self.month() + rhs;
-        let year = self.year() + month / 12;
-        let month = month % 12;
-        let month = if month == 0 { 12 } else { month };
-        Date::new(year, month)
+        let month = self.month() - 1 + rhs;
+        Date::new(self.year() + month / 12, month % 12 + 1)
     }
 }
 impl Sub for Date {
@@ -180,6 +185,30 @@ impl FromStr for Date {
     }
 }
 
+/// Parses dates like `2020/03`, `2020-03`, `03/2020`, `2020-03-31`, `2020/03/31`, or
+/// `31.03.2020`. The day is dropped and the second return value is true if there was a day.
+pub fn parse_month(d: &str) -> BlcResult<(Date, bool)> {
+    let d = d.trim();
+    // ignore the time of ISO timestamps like 2020-03-31T00:00:00
+    let d = d.split(['T', ' ']).next().unwrap_or(d);
+    let parts = d.split(['/', '-', '.']).collect::<Vec<_>>();
+    let parse = |s: &str| s.parse::<usize>().map_err(to_blc);
+    let is_year = |s: &str| s.len() == 4;
+    match parts.as_slice() {
+        [y, m] if is_year(y) => Ok((Date::new(parse(y)?, parse(m)?)?, false)),
+        [m, y] if is_year(y) => Ok((Date::new(parse(y)?, parse(m)?)?, false)),
+        [y, m, day] if is_year(y) && parse(day)? > 0 => {
+            Ok((Date::new(parse(y)?, parse(m)?)?, true))
+        }
+        [day, m, y] if is_year(y) && d.contains('.') && parse(day)? > 0 => {
+            Ok((Date::new(parse(y)?, parse(m)?)?, true))
+        }
+        _ => Err(blcerr!(
+            "unknown date format of {d}, expected YYYY/MM, YYYY-MM, MM/YYYY, YYYY-MM-DD, or DD.MM.YYYY"
+        )),
+    }
+}
+
 #[test]
 fn test_fromym() {
     fn test(year: usize, month: usize, reference: usize) {
@@ -254,6 +283,8 @@ fn test_arith() {
     let d1 = Date::from_str("1988/12").unwrap();
     let d2 = Date::from_str("1989/01").unwrap();
     assert_eq!((d1 + 1).unwrap(), d2);
+    assert_eq!((d1 + 0).unwrap(), d1);
+    assert_eq!((Date::from_str("1988/11").unwrap() + 1).unwrap(), d1);
     assert_eq!(d2 - d1, Some(1));
     assert_eq!(d1 - d2, None);
     let d1 = Date::from_str("1988/02").unwrap();
@@ -271,3 +302,20 @@ fn test_interval() {
     assert!(inter.contains(d2));
     assert!(inter.contains(Date::from_str("1989/07").unwrap()));
 }
+
+#[test]
+fn test_parse_month() {
+    let d202003 = Date::new(2020, 3).unwrap();
+    assert_eq!(parse_month("2020/03").unwrap(), (d202003, false));
+    assert_eq!(parse_month("2020-03").unwrap(), (d202003, false));
+    assert_eq!(parse_month("03/2020").unwrap(), (d202003, false));
+    assert_eq!(parse_month("3/2020").unwrap(), (d202003, false));
+    assert_eq!(parse_month("2020-03-31").unwrap(), (d202003, true));
+    assert_eq!(parse_month(" 2020/03/01 ").unwrap(), (d202003, true));
+    assert_eq!(parse_month("31.03.2020").unwrap(), (d202003, true));
+    assert_eq!(parse_month("2020-03-31T00:00:00").unwrap(), (d202003, true));
+    assert!(parse_month("03/31/2020").is_err());
+    assert!(parse_month("2020-13").is_err());
+    assert!(parse_month("date").is_err());
+    assert!(parse_month("202003").is_err());
+}
@@ -1,9 +1,11 @@
 use crate::{
-    core_types::{to_blc, BlcResult},
-    date::Date,
+    blcerr,
+    compute::{returns_to_levels, ReturnKind},
+    core_types::{to_blc, BlcError, BlcResult},
+    date::{date_after_nmonths, parse_month, Date},
 };
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
+use std::{fmt::Display, iter};
 pub const URL_WRITE_SHARELINK: &str = "https://www.bertiqwerty.com/balance_storage/write.php";
 pub const URL_READ_SHARELINK: &str = "https://www.bertiqwerty.com/balance_storage/read.php";
 
@@ -33,39 +35,711 @@ pub struct ResponsePayload<T> {
     pub json_data: T,
 }
 
-pub fn read_csv_from_str(csv: &str) -> BlcResult<(Vec<Date>, Vec<f64>)> {
-    let reader = csv::Reader::from_reader(csv.as_bytes());
-    read_csv(reader)
+/// How missing months in imported data are handled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GapRepair {
+    /// missing months are an error
+    #[default]
+    Reject,
+    /// missing months get the value of the previous month
+    ForwardFill,
+    LinearInterpolation,
+    /// interpolation of the logarithm, i.e., constant growth rate during the gap
+    LogInterpolation,
+    /// only the longest range without missing months is kept, the most recent one in case of ties
+    LongestGapFreeRange,
+}
+impl Display for GapRepair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GapRepair::Reject => "reject",
+            GapRepair::ForwardFill => "forward-fill",
+            GapRepair::LinearInterpolation => "linear interpolation",
+            GapRepair::LogInterpolation => "log interpolation",
+            GapRepair::LongestGapFreeRange => "longest range without gaps",
+        })
+    }
+}
+
+/// What has been changed to obtain consecutive months
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepairSummary {
+    /// last month before and first month after each gap
+    pub gaps: Vec<(Date, Date)>,
+    pub n_filled: usize,
+    pub n_dropped: usize,
+}
+impl RepairSummary {
+    pub fn is_empty(&self) -> bool {
+        self.gaps.is_empty()
+    }
+}
+impl Display for RepairSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let gaps = self
+            .gaps
+            .iter()
+            .map(|(before, after)| format!("{before}-{after}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "{} gap(s) ({gaps}), {} month(s) filled, {} row(s) dropped",
+            self.gaps.len(),
+            self.n_filled,
+            self.n_dropped
+        )
+    }
+}
+
+fn invalid_rows_error(issues: &[String]) -> BlcError {
+    blcerr!("invalid rows in csv\n{}", issues.join("\n"))
 }
 
-fn read_csv<R>(mut reader: csv::Reader<R>) -> BlcResult<(Vec<Date>, Vec<f64>)>
-where
-    R: std::io::Read,
-{
-    let (dates, values): (Vec<Date>, Vec<f64>) = reader
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Delimiter {
+    /// the most frequent candidate in the first line
+    #[default]
+    Detect,
+    Comma,
+    Semicolon,
+    Tab,
+}
+impl Delimiter {
+    fn to_byte(self, csv: &str) -> u8 {
+        match self {
+            Delimiter::Detect => {
+                let first_line = csv.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+                let count = |c| first_line.matches(c).count();
+                [b'\t', b';', b',']
+                    .into_iter()
+                    .rev()
+                    .max_by_key(|c| count(*c as char))
+                    .unwrap_or(b',')
+            }
+            Delimiter::Comma => b',',
+            Delimiter::Semicolon => b';',
+            Delimiter::Tab => b'\t',
+        }
+    }
+}
+impl Display for Delimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Delimiter::Detect => "detect",
+            Delimiter::Comma => "comma",
+            Delimiter::Semicolon => "semicolon",
+            Delimiter::Tab => "tab",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Header {
+    /// there is a header if the first field cannot be parsed as date
+    #[default]
+    Detect,
+    Yes,
+    No,
+}
+impl Display for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Header::Detect => "detect",
+            Header::Yes => "yes",
+            Header::No => "no",
+        })
+    }
+}
+
+/// What the values of an imported file represent
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValueKind {
+    /// prices or index values
+    #[default]
+    Levels,
+    /// simple returns in percent
+    SimpleReturns,
+    LogReturns,
+}
+impl ValueKind {
+    fn return_kind(&self) -> Option<ReturnKind> {
+        match self {
+            ValueKind::Levels => None,
+            ValueKind::SimpleReturns => Some(ReturnKind::Simple),
+            ValueKind::LogReturns => Some(ReturnKind::Log),
+        }
+    }
+}
+impl Display for ValueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ValueKind::Levels => "levels",
+            ValueKind::SimpleReturns => "returns in %",
+            ValueKind::LogReturns => "log returns",
+        })
+    }
+}
+
+/// Simple returns are read and written in percent
+fn return_scale(kind: ReturnKind) -> f64 {
+    match kind {
+        ReturnKind::Simple => 100.0,
+        ReturnKind::Log => 1.0,
+    }
+}
+
+fn default_base_level() -> f64 {
+    100.0
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CsvImportOptions {
+    pub delimiter: Delimiter,
+    /// values like `1,5` instead of `1.5`
+    pub decimal_comma: bool,
+    pub header: Header,
+    pub gap_repair: GapRepair,
+    #[serde(default)]
+    pub resampling: Resampling,
+    #[serde(default)]
+    pub value_kind: ValueKind,
+    /// level of return series one month before the first return
+    #[serde(default = "default_base_level")]
+    pub base_level: f64,
+}
+impl Default for CsvImportOptions {
+    fn default() -> Self {
+        CsvImportOptions {
+            delimiter: Delimiter::default(),
+            decimal_comma: false,
+            header: Header::default(),
+            gap_repair: GapRepair::default(),
+            resampling: Resampling::default(),
+            value_kind: ValueKind::default(),
+            base_level: default_base_level(),
+        }
+    }
+}
+
+/// How daily or weekly data is turned into monthly data
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resampling {
+    /// last value of each month
+    #[default]
+    MonthEnd,
+    MonthlyAverage,
+}
+impl Resampling {
+    fn apply(&self, values: &[f64]) -> f64 {
+        match self {
+            Resampling::MonthEnd => values.last().copied().unwrap_or(f64::NAN),
+            Resampling::MonthlyAverage => values.iter().sum::<f64>() / values.len() as f64,
+        }
+    }
+}
+impl Display for Resampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Resampling::MonthEnd => "month-end",
+            Resampling::MonthlyAverage => "monthly average",
+        })
+    }
+}
+
+/// Records how a chart was obtained from daily or weekly data
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResamplingInfo {
+    pub rule: Resampling,
+    /// number of observations of each month, 0 for filled gaps
+    pub observations_per_month: Vec<usize>,
+}
+impl ResamplingInfo {
+    pub fn mean_observations_per_month(&self) -> f64 {
+        self.observations_per_month.iter().sum::<usize>() as f64
+            / self.observations_per_month.len().max(1) as f64
+    }
+}
+impl Display for ResamplingInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "resampled to {} values from {:0.1} observations per month on average",
+            self.rule,
+            self.mean_observations_per_month()
+        )
+    }
+}
+
+/// One value column of an imported file with consecutive months
+#[derive(Clone, Debug)]
+pub struct ImportedSeries {
+    pub name: String,
+    pub dates: Vec<Date>,
+    pub values: Vec<f64>,
+    pub summary: RepairSummary,
+    /// only if the file contains days
+    pub resampling: Option<ResamplingInfo>,
+}
+
+/// Imports a file with dates in the first column and one or more value columns. Rows with days,
+/// e.g., daily or weekly data, are resampled to one value per month. Returns of the same month
+/// are compounded instead and return series are converted to levels that start one month before
+/// the first return.
+pub fn import_csv(csv: &str, options: &CsvImportOptions) -> BlcResult<Vec<ImportedSeries>> {
+    let delimiter = options.delimiter.to_byte(csv);
+    if options.decimal_comma && delimiter == b',' {
+        Err(blcerr!("decimal comma needs a delimiter other than comma"))?;
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes());
+    let records = reader
         .records()
-        .flat_map(|record| -> BlcResult<Option<(Date, f64)>> {
-            let record = record.map_err(to_blc)?;
-            if let (Some(date), Some(val)) = (record.get(0), record.get(1)) {
-                let val: f64 = val.parse().map_err(to_blc)?;
-                let date = Date::from_str(date)?;
-                Ok(Some((date, val)))
+        .map(|r| r.map_err(to_blc))
+        .collect::<BlcResult<Vec<_>>>()?;
+    let has_header = match options.header {
+        Header::Detect => records
+            .first()
+            .is_some_and(|r| parse_month(r.get(0).unwrap_or("")).is_err()),
+        Header::Yes => true,
+        Header::No => false,
+    };
+    let (header, records) = if has_header && !records.is_empty() {
+        (Some(&records[0]), &records[1..])
+    } else {
+        (None, &records[..])
+    };
+    let n_value_columns = records.iter().map(|r| r.len()).max().unwrap_or(0).max(1) - 1;
+    if n_value_columns == 0 {
+        Err(blcerr!(
+            "csv needs a date column and at least one value column"
+        ))?;
+    }
+
+    let mut issues = vec![];
+    let mut dates = vec![];
+    for record in records {
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        match parse_month(record.get(0).unwrap_or("")) {
+            Ok((date, has_day)) => dates.push(Some((line, date, has_day))),
+            Err(e) => {
+                issues.push(format!("row {line}: {e}"));
+                dates.push(None);
+            }
+        }
+    }
+    let mut imported = vec![];
+    for col in 1..(n_value_columns + 1) {
+        let name = header
+            .and_then(|h| h.get(col))
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("column {col}"));
+        let col_prefix = if n_value_columns > 1 {
+            format!("column {name}, ")
+        } else {
+            "".to_string()
+        };
+        // rows with days of the same month are collected and resampled afterwards
+        let mut groups: Vec<(u64, Date, bool, Vec<f64>)> = vec![];
+        for (record, date) in records.iter().zip(dates.iter()) {
+            let (Some((line, date, has_day)), Some(val)) = (date, record.get(col)) else {
+                continue;
+            };
+            if val.is_empty() {
+                continue;
+            }
+            let val = if options.decimal_comma {
+                val.replace(',', ".")
             } else {
-                Ok(None)
+                val.to_string()
+            };
+            match val.parse::<f64>() {
+                Ok(val) => match groups.last_mut() {
+                    Some((_, d, true, vals)) if d == date && *has_day => vals.push(val),
+                    _ => groups.push((*line, *date, *has_day, vec![val])),
+                },
+                Err(e) => issues.push(format!("{col_prefix}row {line}: value '{val}', {e}")),
             }
+        }
+        if groups.is_empty() {
+            issues.push(format!("column {name} has no values"));
+            continue;
+        }
+        let rows = groups
+            .iter()
+            .map(|(line, date, _, vals)| {
+                let val = match options.value_kind.return_kind() {
+                    Some(kind) => {
+                        let scale = return_scale(kind);
+                        let compounded = vals
+                            .iter()
+                            .map(|r| 1.0 + kind.to_simple(r / scale))
+                            .product::<f64>();
+                        kind.convert_simple(compounded - 1.0) * scale
+                    }
+                    None => options.resampling.apply(vals),
+                };
+                (*line, *date, val)
+            })
+            .collect::<Vec<_>>();
+        let is_returns = options.value_kind.return_kind().is_some();
+        match consecutive_months(&rows, options.gap_repair, is_returns) {
+            Ok((dates, values, summary)) => {
+                let (dates, values) = match options.value_kind.return_kind() {
+                    Some(kind) => match dates[0].prev_month() {
+                        Ok(base_date) => {
+                            let scale = return_scale(kind);
+                            let returns = values.iter().map(|r| r / scale).collect::<Vec<_>>();
+                            (
+                                iter::once(base_date).chain(dates).collect::<Vec<_>>(),
+                                returns_to_levels(&returns, kind, options.base_level),
+                            )
+                        }
+                        Err(e) => {
+                            issues.push(format!("{col_prefix}{e}"));
+                            continue;
+                        }
+                    },
+                    None => (dates, values),
+                };
+                let resampling = if groups.iter().any(|(_, _, has_day, _)| *has_day) {
+                    let observations_per_month = dates
+                        .iter()
+                        .map(|d| {
+                            groups
+                                .binary_search_by_key(d, |(_, date, _, _)| *date)
+                                .map(|idx| groups[idx].3.len())
+                                .unwrap_or(0)
+                        })
+                        .collect();
+                    Some(ResamplingInfo {
+                        rule: options.resampling,
+                        observations_per_month,
+                    })
+                } else {
+                    None
+                };
+                imported.push(ImportedSeries {
+                    name,
+                    dates,
+                    values,
+                    summary,
+                    resampling,
+                })
+            }
+            Err(col_issues) => {
+                issues.extend(col_issues.into_iter().map(|i| format!("{col_prefix}{i}")))
+            }
+        }
+    }
+    if !issues.is_empty() {
+        Err(invalid_rows_error(&issues))?;
+    }
+    Ok(imported)
+}
+
+/// Writes monthly returns as csv, `dates[i]` is the month at the end of which `returns[i]` was
+/// realized. Simple returns are written in percent.
+pub fn returns_to_csv(name: &str, dates: &[Date], returns: &[f64], kind: ReturnKind) -> String {
+    let scale = return_scale(kind);
+    let scaled = returns.iter().map(|r| r * scale).collect::<Vec<_>>();
+    series_to_csv(name, dates, &scaled)
+}
+
+/// Writes a monthly series as csv with one row per month
+pub fn series_to_csv(name: &str, dates: &[Date], values: &[f64]) -> String {
+    let rows = dates
+        .iter()
+        .zip(values.iter())
+        .map(|(d, v)| format!("{d},{v}\n"))
+        .collect::<String>();
+    format!("date,{name}\n{rows}")
+}
+
+/// Writes a square matrix as csv with the names as header and as first column
+pub fn matrix_to_csv(names: &[String], matrix: &[Vec<f64>]) -> String {
+    let rows = names
+        .iter()
+        .zip(matrix.iter())
+        .map(|(name, row)| {
+            let values = row.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+            format!("{name},{}\n", values.join(","))
         })
-        .flatten()
-        .unzip();
-
-    // validate all months are there
-    for (d1, d2) in dates.iter().zip(dates[1..].iter()) {
-        if d1.month() == 12 {
-            assert_eq!(d2.month(), 1);
-            assert_eq!(d1.year() + 1, d2.year());
-        } else {
-            assert_eq!(d2.month() - d1.month(), 1);
-            assert_eq!(d2.year() - d1.year(), 0);
+        .collect::<String>();
+    format!(",{}\n{rows}", names.join(","))
+}
+
+/// Validates that all months are there or repairs the gaps, returns the issues otherwise.
+/// Missing months of return series are filled with a return of zero independent of the repair
+/// rule, interpolating or repeating returns would invent performance.
+fn consecutive_months(
+    rows: &[(u64, Date, f64)],
+    repair: GapRepair,
+    is_returns: bool,
+) -> Result<(Vec<Date>, Vec<f64>, RepairSummary), Vec<String>> {
+    let mut issues = vec![];
+    let mut gaps = vec![];
+    for (i, ((_, d1, _), (line, d2, _))) in rows.iter().zip(rows.iter().skip(1)).enumerate() {
+        match *d2 - *d1 {
+            Some(1) => (),
+            Some(0) | None => issues.push(format!(
+                "row {line}: {d2} does not come after the previous month {d1}"
+            )),
+            Some(_) => {
+                if repair == GapRepair::Reject {
+                    issues.push(format!(
+                        "row {line}: months between {d1} and {d2} are missing"
+                    ));
+                }
+                gaps.push(i);
+            }
         }
     }
+    if !issues.is_empty() {
+        return Err(issues);
+    }
+    let mut summary = RepairSummary {
+        gaps: gaps.iter().map(|i| (rows[*i].1, rows[*i + 1].1)).collect(),
+        ..RepairSummary::default()
+    };
+    let (dates, values) = if repair == GapRepair::LongestGapFreeRange {
+        let bounds = iter::once(0)
+            .chain(gaps.iter().map(|i| i + 1))
+            .zip(gaps.iter().map(|i| i + 1).chain(iter::once(rows.len())));
+        let (start, end) = bounds
+            .max_by_key(|(start, end)| end - start)
+            .unwrap_or((0, 0));
+        summary.n_dropped = rows.len() - (end - start);
+        rows[start..end].iter().map(|(_, d, v)| (*d, *v)).unzip()
+    } else {
+        let mut dates: Vec<Date> = vec![];
+        let mut values: Vec<f64> = vec![];
+        for (line, date, val) in rows.iter() {
+            if let (Some(prev_date), Some(prev_val)) =
+                (dates.last().copied(), values.last().copied())
+            {
+                let n_steps = (*date - prev_date).unwrap_or(1);
+                for step in 1..n_steps {
+                    let t = step as f64 / n_steps as f64;
+                    let filled = match repair {
+                        _ if is_returns => 0.0,
+                        GapRepair::LinearInterpolation => prev_val + t * (val - prev_val),
+                        GapRepair::LogInterpolation => {
+                            if prev_val <= 0.0 || *val <= 0.0 {
+                                return Err(vec![format!(
+                                    "row {line}: log interpolation needs positive values"
+                                )]);
+                            }
+                            (prev_val.ln() + t * (val.ln() - prev_val.ln())).exp()
+                        }
+                        _ => prev_val,
+                    };
+                    dates.push(date_after_nmonths(prev_date, step));
+                    values.push(filled);
+                    summary.n_filled += 1;
+                }
+            }
+            dates.push(*date);
+            values.push(*val);
+        }
+        (dates, values)
+    };
+    Ok((dates, values, summary))
+}
+
+#[cfg(test)]
+const CSV_WITH_GAP: &str = "date,value\n2020/01,1.0\n2020/02,2.0\n2020/05,16.0\n2020/06,17.0\n";
+
+#[cfg(test)]
+fn read_csv_from_str_with_repair(
+    csv: &str,
+    gap_repair: GapRepair,
+) -> BlcResult<(Vec<Date>, Vec<f64>, RepairSummary)> {
+    let options = CsvImportOptions {
+        gap_repair,
+        ..CsvImportOptions::default()
+    };
+    let imported = import_csv(csv, &options)?.swap_remove(0);
+    Ok((imported.dates, imported.values, imported.summary))
+}
+
+#[cfg(test)]
+fn read_csv_from_str(csv: &str) -> BlcResult<(Vec<Date>, Vec<f64>)> {
+    let (dates, values, _) = read_csv_from_str_with_repair(csv, GapRepair::Reject)?;
     Ok((dates, values))
 }
+
+#[test]
+fn test_read_csv_reject() {
+    let (dates, values) =
+        read_csv_from_str("date,value\n2020/11,1.0\n2020/12,2.0\n2021/01,3.0\n").unwrap();
+    assert_eq!(dates.len(), 3);
+    assert_eq!(values, vec![1.0, 2.0, 3.0]);
+
+    let err = read_csv_from_str(CSV_WITH_GAP).unwrap_err();
+    assert!(err.msg.contains("row 4"));
+    assert!(err.msg.contains("2020/02 and 2020/05"));
+    let err = read_csv_from_str("date,value\n2020/01,1.0\n2020/13,x\n2020/01,3.0\n").unwrap_err();
+    assert!(err.msg.contains("row 3"));
+    assert!(err.msg.contains("row 4"));
+}
+
+#[test]
+fn test_read_csv_repair() {
+    let repair = |r| read_csv_from_str_with_repair(CSV_WITH_GAP, r).unwrap();
+    let (dates, values, summary) = repair(GapRepair::ForwardFill);
+    assert_eq!(dates.len(), 6);
+    assert_eq!(dates[2], Date::new(2020, 3).unwrap());
+    assert_eq!(values, vec![1.0, 2.0, 2.0, 2.0, 16.0, 17.0]);
+    assert_eq!(summary.n_filled, 2);
+    assert_eq!(summary.gaps.len(), 1);
+
+    let (_, values, _) = repair(GapRepair::LinearInterpolation);
+    assert!((values[2] - (2.0 + 14.0 / 3.0)).abs() < 1e-12);
+    assert!((values[3] - (2.0 + 28.0 / 3.0)).abs() < 1e-12);
+
+    let (_, values, _) = repair(GapRepair::LogInterpolation);
+    assert!((values[2] - 4.0).abs() < 1e-12);
+    assert!((values[3] - 8.0).abs() < 1e-12);
+
+    let (dates, values, summary) = repair(GapRepair::LongestGapFreeRange);
+    assert_eq!(dates[0], Date::new(2020, 5).unwrap());
+    assert_eq!(values, vec![16.0, 17.0]);
+    assert_eq!(summary.n_dropped, 2);
+    assert_eq!(summary.n_filled, 0);
+}
+
+#[test]
+fn test_import_formats() {
+    let tsv = "Date\tWorld\tEM\n2020-01-15\t1,5\t\n2020-01-31\t2,5\t\n2020-02-29\t3,0\t7,0\n";
+    let options = CsvImportOptions {
+        decimal_comma: true,
+        ..CsvImportOptions::default()
+    };
+    let imported = import_csv(tsv, &options).unwrap();
+    assert_eq!(imported.len(), 2);
+    assert_eq!(imported[0].name, "World");
+    assert_eq!(imported[0].dates[0], Date::new(2020, 1).unwrap());
+    assert_eq!(imported[0].values, vec![2.5, 3.0]);
+    assert_eq!(imported[1].name, "EM");
+    assert_eq!(imported[1].dates, vec![Date::new(2020, 2).unwrap()]);
+    assert_eq!(imported[1].values, vec![7.0]);
+
+    let no_header = "01/2020;1.0;2.0\n02/2020;1.5;2.5\n";
+    let imported = import_csv(no_header, &CsvImportOptions::default()).unwrap();
+    assert_eq!(imported.len(), 2);
+    assert_eq!(imported[0].name, "column 1");
+    assert_eq!(imported[1].values, vec![2.0, 2.5]);
+
+    let comma = CsvImportOptions {
+        delimiter: Delimiter::Comma,
+        decimal_comma: true,
+        ..CsvImportOptions::default()
+    };
+    assert!(import_csv(no_header, &comma).is_err());
+    let err = import_csv("date,a,b\n2020-01,1.0,x\n2020-02,1.0,2.0\n", &comma).unwrap_err();
+    assert!(err.msg.contains("decimal comma"));
+    let err = import_csv(
+        "date,a,b\n2020-01,1.0,x\n2020-02,1.0,2.0\n",
+        &CsvImportOptions::default(),
+    )
+    .unwrap_err();
+    assert!(err.msg.contains("column b, row 2"));
+}
+
+#[test]
+fn test_import_resampling() {
+    let daily = "date,value\n2020-01-02,1.0\n2020-01-15,2.0\n2020-01-31,3.0\n\
+                 2020-02-03,4.0\n2020-02-28,6.0\n2020-04-30,8.0\n";
+    let options = CsvImportOptions {
+        gap_repair: GapRepair::ForwardFill,
+        ..CsvImportOptions::default()
+    };
+    let imported = import_csv(daily, &options).unwrap().swap_remove(0);
+    assert_eq!(imported.values, vec![3.0, 6.0, 6.0, 8.0]);
+    let resampling = imported.resampling.unwrap();
+    assert_eq!(resampling.rule, Resampling::MonthEnd);
+    assert_eq!(resampling.observations_per_month, vec![3, 2, 0, 1]);
+    assert!((resampling.mean_observations_per_month() - 1.5).abs() < 1e-12);
+
+    let options = CsvImportOptions {
+        resampling: Resampling::MonthlyAverage,
+        ..options
+    };
+    let imported = import_csv(daily, &options).unwrap().swap_remove(0);
+    assert_eq!(imported.values, vec![2.0, 5.0, 5.0, 8.0]);
+
+    let monthly = import_csv(CSV_WITH_GAP, &options).unwrap().swap_remove(0);
+    assert!(monthly.resampling.is_none());
+}
+
+#[test]
+fn test_import_export_returns() {
+    let options = CsvImportOptions {
+        value_kind: ValueKind::SimpleReturns,
+        ..CsvImportOptions::default()
+    };
+    let csv = "date,World\n2020/02,10.0\n2020/03,-10.0\n";
+    let imported = import_csv(csv, &options).unwrap().swap_remove(0);
+    assert_eq!(imported.dates[0], Date::new(2020, 1).unwrap());
+    assert_eq!(imported.dates.len(), 3);
+    assert!((imported.values[1] - 110.0).abs() < 1e-10);
+    assert!((imported.values[2] - 99.0).abs() < 1e-10);
+
+    let log = CsvImportOptions {
+        value_kind: ValueKind::LogReturns,
+        base_level: 1.0,
+        ..options
+    };
+    let daily = "date,value\n2020-01-02,0.1\n2020-01-31,0.2\n2020-02-03,-0.3\n";
+    let imported = import_csv(daily, &log).unwrap().swap_remove(0);
+    assert_eq!(imported.dates[0], Date::new(2019, 12).unwrap());
+    assert!((imported.values[1] - 0.3f64.exp()).abs() < 1e-10);
+    assert!((imported.values[2] - 1.0).abs() < 1e-10);
+    assert_eq!(
+        imported.resampling.unwrap().observations_per_month,
+        vec![0, 2, 1]
+    );
+
+    let returns = crate::compute::levels_to_returns(&[100.0, 110.0, 99.0], ReturnKind::Simple);
+    let exported = returns_to_csv("World", &imported.dates[1..], &returns, ReturnKind::Simple);
+    assert!(exported.starts_with("date,World\n2020/01,10"));
+    let reimported = import_csv(&exported, &options).unwrap().swap_remove(0);
+    assert!((reimported.values[2] - 99.0).abs() < 1e-10);
+}
+
+#[test]
+fn test_import_returns_with_gap() {
+    let csv = "date,World\n2020/01,10.0\n2020/02,10.0\n2020/04,10.0\n";
+    for gap_repair in [
+        GapRepair::ForwardFill,
+        GapRepair::LinearInterpolation,
+        GapRepair::LogInterpolation,
+    ] {
+        let options = CsvImportOptions {
+            value_kind: ValueKind::SimpleReturns,
+            gap_repair,
+            ..CsvImportOptions::default()
+        };
+        let imported = import_csv(csv, &options).unwrap().swap_remove(0);
+        assert_eq!(imported.dates[0], Date::new(2019, 12).unwrap());
+        assert_eq!(imported.summary.n_filled, 1);
+        let expected = [100.0, 110.0, 121.0, 121.0, 133.1];
+        assert_eq!(imported.values.len(), expected.len());
+        for (v, e) in imported.values.iter().zip(expected) {
+            assert!((v - e).abs() < 1e-10);
+        }
+    }
+}
+
+#[test]
+fn test_matrix_to_csv() {
+    let names = ["World".to_string(), "EM".to_string()];
+    let csv = matrix_to_csv(&names, &[vec![1.0, 0.5], vec![0.5, 1.0]]);
+    assert_eq!(csv, ",World,EM\nWorld,1,0.5\nEM,0.5,1\n");
+}
@@ -1,9 +1,11 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
+mod catalog;
 mod compute;
 mod container_util;
 mod core_types;
 mod date;
 mod io;
+mod optimize;
 pub use app::BalanceApp;
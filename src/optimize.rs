@@ -0,0 +1,339 @@
+use crate::{blcerr, core_types::BlcResult};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Criterion to propose the fractions of a portfolio
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Objective {
+    #[default]
+    MinVariance,
+    MaxSharpe,
+    /// all assets contribute equally to the variance of the portfolio
+    RiskParity,
+    EqualWeight,
+}
+impl Display for Objective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Objective::MinVariance => "minimum variance",
+            Objective::MaxSharpe => "maximum Sharpe ratio",
+            Objective::RiskParity => "risk parity",
+            Objective::EqualWeight => "equal weight",
+        })
+    }
+}
+
+/// Minimum and maximum fraction of an asset
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WeightBounds {
+    pub min: f64,
+    pub max: f64,
+}
+impl WeightBounds {
+    pub fn fixed(fraction: f64) -> Self {
+        WeightBounds {
+            min: fraction,
+            max: fraction,
+        }
+    }
+}
+impl Default for WeightBounds {
+    fn default() -> Self {
+        WeightBounds { min: 0.0, max: 1.0 }
+    }
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Sample covariances of equally long return series
+pub fn covariance_matrix(returns: &[&[f64]]) -> Vec<Vec<f64>> {
+    let means = returns.iter().map(|r| mean(r)).collect::<Vec<_>>();
+    returns
+        .iter()
+        .zip(means.iter())
+        .map(|(ri, mi)| {
+            returns
+                .iter()
+                .zip(means.iter())
+                .map(|(rj, mj)| {
+                    let n = ri.len().min(rj.len());
+                    ri.iter()
+                        .zip(rj.iter())
+                        .map(|(x, y)| (x - mi) * (y - mj))
+                        .sum::<f64>()
+                        / (n as f64 - 1.0)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Correlations from a covariance matrix, `NaN` for assets without variance
+pub fn correlation_matrix(cov: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    cov.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(j, c)| {
+                    let denominator = (cov[i][i] * cov[j][j]).sqrt();
+                    if denominator > 0.0 {
+                        c / denominator
+                    } else {
+                        f64::NAN
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Correlations of the windows of `window` months that end at each month from `window - 1`
+/// on. Windows that contain `NaN` yield `NaN`.
+pub fn rolling_correlation(a: &[f64], b: &[f64], window: usize) -> Vec<f64> {
+    let n = a.len().min(b.len());
+    if window < 2 || window > n {
+        return vec![];
+    }
+    (window..=n)
+        .map(|end| {
+            let cov = covariance_matrix(&[&a[(end - window)..end], &b[(end - window)..end]]);
+            correlation_matrix(&cov)[0][1]
+        })
+        .collect()
+}
+
+fn matvec(matrix: &[Vec<f64>], v: &[f64]) -> Vec<f64> {
+    matrix
+        .iter()
+        .map(|row| row.iter().zip(v).map(|(a, b)| a * b).sum())
+        .collect()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Projects onto the fractions that sum up to 1 and respect the bounds by shifting all values
+/// by the same amount before clipping
+fn project(v: &[f64], bounds: &[WeightBounds]) -> Vec<f64> {
+    let shifted = |shift: f64| {
+        v.iter()
+            .zip(bounds)
+            .map(move |(x, b)| (x - shift).clamp(b.min, b.max))
+    };
+    let mut lower = v
+        .iter()
+        .zip(bounds)
+        .map(|(x, b)| x - b.max)
+        .fold(f64::INFINITY, f64::min);
+    let mut upper = v
+        .iter()
+        .zip(bounds)
+        .map(|(x, b)| x - b.min)
+        .fold(f64::NEG_INFINITY, f64::max);
+    for _ in 0..100 {
+        let mid = 0.5 * (lower + upper);
+        if shifted(mid).sum::<f64>() > 1.0 {
+            lower = mid;
+        } else {
+            upper = mid;
+        }
+    }
+    shifted(0.5 * (lower + upper)).collect()
+}
+
+fn numerical_gradient(f: &impl Fn(&[f64]) -> f64, w: &[f64]) -> Vec<f64> {
+    const H: f64 = 1e-7;
+    let mut w = w.to_vec();
+    (0..w.len())
+        .map(|i| {
+            let wi = w[i];
+            w[i] = wi + H;
+            let f_plus = f(&w);
+            w[i] = wi - H;
+            let f_minus = f(&w);
+            w[i] = wi;
+            (f_plus - f_minus) / (2.0 * H)
+        })
+        .collect()
+}
+
+/// Projected gradient descent with an adaptive step size
+fn minimize(f: impl Fn(&[f64]) -> f64, start: &[f64], bounds: &[WeightBounds]) -> Vec<f64> {
+    let mut w = project(start, bounds);
+    let mut f_w = f(&w);
+    let mut step = 1.0;
+    for _ in 0..2000 {
+        let gradient = numerical_gradient(&f, &w);
+        let mut improved = false;
+        while step > 1e-14 {
+            let candidate = w
+                .iter()
+                .zip(gradient.iter())
+                .map(|(wi, gi)| wi - step * gi)
+                .collect::<Vec<_>>();
+            let candidate = project(&candidate, bounds);
+            let f_candidate = f(&candidate);
+            if f_candidate < f_w {
+                improved = f_w - f_candidate > 1e-16;
+                w = candidate;
+                f_w = f_candidate;
+                step *= 2.0;
+                break;
+            }
+            step *= 0.5;
+        }
+        if !improved {
+            break;
+        }
+    }
+    w
+}
+
+/// Proposes fractions for assets with the given monthly returns. `risk_free` is the mean monthly
+/// return of the risk-free asset and `start` the current fractions.
+pub fn optimize_fractions(
+    returns: &[&[f64]],
+    risk_free: f64,
+    objective: Objective,
+    bounds: &[WeightBounds],
+    start: &[f64],
+) -> BlcResult<Vec<f64>> {
+    if returns.is_empty() || bounds.len() != returns.len() || start.len() != returns.len() {
+        Err(blcerr!("need bounds and fractions for each asset"))?;
+    }
+    if bounds
+        .iter()
+        .any(|b| b.min > b.max || b.min < 0.0 || b.max > 1.0)
+    {
+        Err(blcerr!("bounds need to satisfy 0 <= min <= max <= 1"))?;
+    }
+    let (min_sum, max_sum) = bounds
+        .iter()
+        .fold((0.0, 0.0), |(lo, hi), b| (lo + b.min, hi + b.max));
+    if min_sum > 1.0 + 1e-9 || max_sum < 1.0 - 1e-9 {
+        Err(blcerr!(
+            "fractions cannot sum up to 1 within the bounds, minima sum up to {min_sum:0.2}, \
+             maxima to {max_sum:0.2}"
+        ))?;
+    }
+    if returns.iter().map(|r| r.len()).min().unwrap_or(0) < 2 {
+        Err(blcerr!("optimization needs at least 2 months of returns"))?;
+    }
+    let cov = covariance_matrix(returns);
+    let variance = |w: &[f64]| dot(w, &matvec(&cov, w));
+    let n = returns.len() as f64;
+    Ok(match objective {
+        Objective::EqualWeight => project(&vec![1.0 / n; returns.len()], bounds),
+        Objective::MinVariance => minimize(variance, start, bounds),
+        Objective::MaxSharpe => {
+            let means = returns.iter().map(|r| mean(r)).collect::<Vec<_>>();
+            let neg_sharpe = |w: &[f64]| {
+                let var = variance(w);
+                if var > 0.0 {
+                    -(dot(w, &means) - risk_free) / var.sqrt()
+                } else {
+                    f64::INFINITY
+                }
+            };
+            minimize(neg_sharpe, start, bounds)
+        }
+        Objective::RiskParity => {
+            let contribution_spread = |w: &[f64]| {
+                let marginal = matvec(&cov, w);
+                let var = dot(w, &marginal);
+                if var > 0.0 {
+                    w.iter()
+                        .zip(marginal.iter())
+                        .map(|(wi, mi)| (wi * mi / var - 1.0 / n).powi(2))
+                        .sum()
+                } else {
+                    f64::INFINITY
+                }
+            };
+            minimize(contribution_spread, start, bounds)
+        }
+    })
+}
+
+#[cfg(test)]
+fn assert_fractions(fractions: &[f64], reference: &[f64]) {
+    for (f, r) in fractions.iter().zip(reference) {
+        assert!((f - r).abs() < 1e-4, "{fractions:?} vs {reference:?}");
+    }
+}
+
+#[test]
+fn test_covariance_matrix() {
+    let a = [1.0, -1.0, 1.0, -1.0];
+    let b = [2.0, 2.0, -2.0, -2.0];
+    let cov = covariance_matrix(&[&a, &b]);
+    assert!((cov[0][0] - 4.0 / 3.0).abs() < 1e-12);
+    assert!((cov[1][1] - 16.0 / 3.0).abs() < 1e-12);
+    assert!(cov[0][1].abs() < 1e-12);
+}
+
+#[test]
+fn test_correlation() {
+    let a = [1.0, 2.0, 3.0, 4.0];
+    let b = [2.0, 4.0, 6.0, 8.0];
+    let c = [4.0, 3.0, 2.0, 1.0];
+    let cor = correlation_matrix(&covariance_matrix(&[&a, &b, &c]));
+    assert!((cor[0][1] - 1.0).abs() < 1e-12);
+    assert!((cor[1][2] + 1.0).abs() < 1e-12);
+    assert!((cor[2][2] - 1.0).abs() < 1e-12);
+    assert!(correlation_matrix(&covariance_matrix(&[&a, &[1.0; 4]]))[0][1].is_nan());
+
+    let rolling = rolling_correlation(&[1.0, 2.0, 1.0, f64::NAN, 1.0], &a, 2);
+    assert_eq!(rolling.len(), 3);
+    assert!((rolling[0] - 1.0).abs() < 1e-12);
+    assert!((rolling[1] + 1.0).abs() < 1e-12);
+    assert!(rolling[2].is_nan());
+    assert!(rolling_correlation(&a, &b, 5).is_empty());
+}
+
+#[test]
+fn test_optimize_fractions() {
+    // uncorrelated with variances 4/3 and 16/3 and means 1 and 2
+    let a = [2.0, 0.0, 2.0, 0.0];
+    let b = [4.0, 4.0, 0.0, 0.0];
+    let returns = [&a[..], &b[..]];
+    let free = [WeightBounds::default(); 2];
+    let start = [0.5, 0.5];
+    let optimize = |objective, bounds: &[WeightBounds]| {
+        optimize_fractions(&returns, 0.0, objective, bounds, &start).unwrap()
+    };
+    assert_fractions(&optimize(Objective::MinVariance, &free), &[0.8, 0.2]);
+    assert_fractions(
+        &optimize(Objective::RiskParity, &free),
+        &[2.0 / 3.0, 1.0 / 3.0],
+    );
+    assert_fractions(
+        &optimize(Objective::MaxSharpe, &free),
+        &[2.0 / 3.0, 1.0 / 3.0],
+    );
+    assert_fractions(&optimize(Objective::EqualWeight, &free), &[0.5, 0.5]);
+
+    let capped = [WeightBounds { min: 0.0, max: 0.7 }, WeightBounds::default()];
+    assert_fractions(&optimize(Objective::MinVariance, &capped), &[0.7, 0.3]);
+    let fixed = [WeightBounds::default(), WeightBounds::fixed(0.5)];
+    assert_fractions(&optimize(Objective::MinVariance, &fixed), &[0.5, 0.5]);
+
+    let returns = [&a[..], &b[..], &a[..]];
+    let bounds = [
+        WeightBounds::fixed(0.5),
+        WeightBounds::default(),
+        WeightBounds::default(),
+    ];
+    let fractions =
+        optimize_fractions(&returns, 0.0, Objective::EqualWeight, &bounds, &[0.3; 3]).unwrap();
+    assert_fractions(&fractions, &[0.5, 0.25, 0.25]);
+
+    let infeasible = [WeightBounds { min: 0.0, max: 0.4 }; 2];
+    assert!(
+        optimize_fractions(&[&a, &b], 0.0, Objective::MinVariance, &infeasible, &start).is_err()
+    );
+}